@@ -0,0 +1,41 @@
+import { FinalizeHandler, Raw } from "./finalize";
+import { CanvasWasm } from "./canvas";
+import { Context2D } from "./context";
+import { JsBuffer, JsString } from "./jstypes";
+import { getWasmBridge } from "./registry";
+import { checkPtr, checkStatus } from "./errors";
+import { debug } from "./logger";
+
+// Collects successive frames of a canvas and encodes them as one APNG or animated GIF
+export class Animation extends Raw {
+  constructor() {
+    let ptr = getWasmBridge()._new_frame_collector();
+    super(new FinalizeHandler(()=> {
+      getWasmBridge()._drop_frame_collector(ptr);
+    }));
+    this.ptr = ptr;
+  }
+
+  // snapshot what has been drawn so far, shown for `delay` milliseconds
+  addFrame(source: CanvasWasm | Context2D, delay: number = 100, options?: { matte?: string }) {
+    debug('addFrame', delay, options);
+    const ctx = source instanceof CanvasWasm ? source.getContext('2d') : source;
+    checkStatus(this.bridge._frame_collector_add_frame(this.raw(), ctx.raw(), delay, new JsString(options?.matte ?? '').raw()));
+    return this;
+  }
+
+  get frameCount(): number {
+    return this.bridge._frame_collector_frame_count(this.raw());
+  }
+
+  // loops: 0 repeats forever; quality (0-1) trades gif quantization speed for accuracy
+  encode(format: 'png' | 'gif', options?: { loops?: number, quality?: number }): Uint8Array {
+    const bufPtr = checkPtr(this.bridge._frame_collector_encode(
+      this.raw(),
+      new JsString(format).raw(),
+      options?.loops ?? 0,
+      options?.quality ?? 1
+    ));
+    return JsBuffer.fromPtr(bufPtr).toBuffer();
+  }
+}
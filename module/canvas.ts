@@ -1,11 +1,12 @@
 import { FinalizeHandler, Raw } from "./finalize";
-import { Context2D } from "./context";
+import { CanvasRenderingContext2DSettings, Context2D } from "./context";
 import { getWasmBridge, registerWasmBridge } from "./registry";
-import { fetchBuffer } from "./utils";
-import { JsBuffer, JsF32Array, JsString } from "./jstypes";
+import { DirtyRect, fetchBuffer, toDirtyRect } from "./utils";
+import { JsBuffer, JsF32Array, JsStrMap, JsString } from "./jstypes";
 import { debug } from "./logger";
+import { checkPtr, checkStatus } from "./errors";
 
-type cuttingOptions = {
+export type cuttingOptions = {
   targetWidth: number;
   targetHeight: number;
   targetOffsetLeft: number;
@@ -15,6 +16,57 @@ type cuttingOptions = {
   sourceOffset: number;
 }
 
+export function cuttingArray(options?: cuttingOptions): JsF32Array {
+  const arr = new JsF32Array(7);
+  if(options) {
+    arr.push(
+      options.sourceOffset,
+      options.targetOffsetTop,
+      options.targetOffsetRight,
+      options.targetOffsetBottom,
+      options.targetOffsetLeft,
+      options.targetWidth,
+      options.targetHeight
+    );
+  }
+  return arr;
+}
+
+// the pdf document information dictionary
+export type PdfInfo = {
+  title?: string;
+  author?: string;
+  subject?: string;
+  keywords?: string | string[];
+  creator?: string;
+  creationDate?: Date;
+  modDate?: Date;
+  pdfa?: boolean;
+}
+
+export function pdfInfoMap(info?: PdfInfo): JsStrMap {
+  const map = new JsStrMap();
+  if(info) {
+    for(const key of ['title', 'author', 'subject', 'creator'] as const) {
+      if(info[key] !== undefined) {
+        map.insert(key, info[key] as string);
+      }
+    }
+    if(info.keywords !== undefined) {
+      map.insert('keywords', Array.isArray(info.keywords) ? info.keywords.join(', ') : info.keywords);
+    }
+    if(info.creationDate) {
+      map.insert('creationDate', String(info.creationDate.getTime()));
+    }
+    if(info.modDate) {
+      map.insert('modDate', String(info.modDate.getTime()));
+    }
+    if(info.pdfa) {
+      map.insert('pdfa', 'true');
+    }
+  }
+  return map;
+}
 
 function resizeCanvasToDisplaySize(canvas: HTMLCanvasElement) {
   const width = canvas.clientWidth | 1;
@@ -31,39 +83,46 @@ function resizeCanvasToDisplaySize(canvas: HTMLCanvasElement) {
 export class CanvasWasm extends Raw {
   private surfacePtr: SurfacePtr = 0;
   private context?: Context2D;
-  constructor(public el: HTMLCanvasElement | OffscreenCanvas) {
+  constructor(public el: HTMLCanvasElement | OffscreenCanvas, options?: { raster?: boolean }) {
     let canvasPtr: Ptr;
     let surfacePtr: Ptr;
 
     super(new FinalizeHandler(()=> {
-      // TODO 可以挂载到canvas元素上避免回收
+      // also frees the context (and surface) if one was created
+      getWasmBridge()._drop_canvas(canvasPtr);
     }));
     let bridge = getWasmBridge();
-    const context = el.getContext("webgl2", {
-      antialias: true,
-      depth: true,
-      stencil: true,
-      alpha: true,
-      preserveDrawingBuffer: true,
-    });
-  
-    // Register the context with emscripten
-    // @ts-ignore
-    const handle = bridge.GL.registerContext(context, { majorVersion: 2 });
-    // @ts-ignore
-    bridge.GL.makeContextCurrent(handle);
-    surfacePtr = this.bridge._init_surface(el.width, el.height);
+    if(options?.raster) {
+      // render on the CPU, no WebGL context needed (e.g. inside a Web Worker)
+      surfacePtr = this.bridge._init_raster_surface(el.width, el.height);
+    } else {
+      const context = el.getContext("webgl2", {
+        antialias: true,
+        depth: true,
+        stencil: true,
+        alpha: true,
+        preserveDrawingBuffer: true,
+      });
+    
+      // Register the context with emscripten
+      // @ts-ignore
+      const handle = bridge.GL.registerContext(context, { majorVersion: 2 });
+      // @ts-ignore
+      bridge.GL.makeContextCurrent(handle);
+      surfacePtr = this.bridge._init_surface(el.width, el.height);
+    }
     this.surfacePtr = surfacePtr;
     canvasPtr = bridge._new_canvas(surfacePtr, el.width, el.height);
     this.ptr = canvasPtr;
   }
 
-  getContext(type: string): Context2D {
+  // settings only take effect when the context is first created, as in the DOM
+  getContext(type: string, settings?: CanvasRenderingContext2DSettings): Context2D {
     if(type === '2d') {
       if(this.context) {
         return this.context;
       }
-      this.context = new Context2D(this);
+      this.context = new Context2D(this, settings);
       return this.context;
     } else {
       throw new Error('unsupport type: ' + type);
@@ -98,40 +157,54 @@ export class CanvasWasm extends Raw {
   }
 
 
-  flush() {
+  // presents what was drawn since the last flush, returning the redrawn area (from the top left)
+  // so a compositor can scissor to it, or null if nothing changed
+  flush(): DirtyRect | null {
     if(this.context) {
-      getWasmBridge()._render_to_surface(this.surfacePtr, this.context.raw());
+      checkStatus(getWasmBridge()._render_to_surface(this.surfacePtr, this.context.raw()));
+      return toDirtyRect(JsF32Array.fromPtr(getWasmBridge()._surface_get_presented_rect(this.surfacePtr)).toArray());
     } else {
       throw new Error('no context');
     }
   }
 
-  saveAs(format: 'pdf' | 'png' | 'jpeg', options?: {quality?: number, density?: number, matte?: string, bacground?: string, cuttingOptions?: cuttingOptions}) {
+  // quality is 0-1; for webp a quality of 1 selects lossless compression
+  // outline turns svg text into paths so it renders without the fonts installed
+  saveAs(format: 'pdf' | 'svg' | 'png' | 'jpeg' | 'webp' | 'avif', options?: {quality?: number, density?: number, matte?: string, bacground?: string, cuttingOptions?: cuttingOptions, outline?: boolean, info?: PdfInfo}) {
     if(this.context) {
       let f = (new JsString(format)).raw();
       let q = options?.quality ?? 1;
       let d = options?.density ?? 1;
       let m = (new JsString(options?.matte ?? '')).raw();
       let b = (new JsString(options?.bacground ?? '')).raw();
-      let cuttingOptions = new JsF32Array(6);
-      if(options?.cuttingOptions) {
-        cuttingOptions.push(
-          options.cuttingOptions.sourceOffset, 
-          options.cuttingOptions.targetOffsetTop,
-          options.cuttingOptions.targetOffsetRight, 
-          options.cuttingOptions.targetOffsetBottom,
-          options.cuttingOptions.targetOffsetLeft, 
-          options.cuttingOptions.targetWidth,
-          options.cuttingOptions.targetHeight
-        ); 
-      }
-      let bufPtr = this.bridge._canvas_save_as(this.raw(), f, q, d, m, b, cuttingOptions.raw());
+      let cuttingOptions = cuttingArray(options?.cuttingOptions);
+      let o = options?.outline ? 1 : 0;
+      let info = pdfInfoMap(options?.info);
+      let bufPtr = checkPtr(this.bridge._canvas_save_as(this.raw(), f, q, d, m, b, cuttingOptions.raw(), o, info.raw()));
       return JsBuffer.fromPtr(bufPtr).toBuffer();
     } else {
       throw new Error('no context');
     }
   }
 
+  toDataURL(type: string = 'image/png', quality?: number): string {
+    const formats: Record<string, 'png' | 'jpeg' | 'webp' | 'avif'> = {
+      'image/png': 'png',
+      'image/jpeg': 'jpeg',
+      'image/webp': 'webp',
+      'image/avif': 'avif',
+    };
+    // like browsers, fall back to png for unknown types and to the default quality when out of range
+    const format = formats[type.toLowerCase()] ?? 'png';
+    const q = quality !== undefined && quality >= 0 && quality <= 1 ? quality : 0.92;
+    const bytes = this.saveAs(format, { quality: q });
+    let binary = '';
+    for(let i = 0; i < bytes.length; i += 0x8000) {
+      binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+    }
+    return `data:image/${format};base64,${btoa(binary)}`;
+  }
+
   async loadFonts(alias?: string | string[], sources: string[] = []): Promise<any> {
     let _alias = '';
     if(typeof alias !== 'string') {
@@ -151,7 +224,7 @@ export class CanvasWasm extends Raw {
     for(let i = 0; i < buf.length; i++) {
       jsbuff.push(buf[i]);
     }
-    this.bridge._add_font_family(jsbuff.raw(), alias ? new JsString(alias).raw() : new JsString("_default").raw());
+    checkStatus(this.bridge._add_font_family(jsbuff.raw(), alias ? new JsString(alias).raw() : new JsString("_default").raw()));
   }
 
   raw(): number {
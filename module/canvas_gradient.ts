@@ -1,33 +1,34 @@
 import { FinalizeHandler, Raw } from "./finalize";
-import { JsBuffer, JsString } from "./jstypes";
+import { JsF32Array, JsString } from "./jstypes";
 import { getWasmBridge } from "./registry";
+import { checkPtr } from "./errors";
 
 export class CanvasGradient extends Raw {
   constructor(ptr: CanvasGradientPtr) {
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_canvas_gradient(ptr);
     }))
     this.ptr = ptr;
   }
 
   static createLinearGradient(x0: number, y0: number, x1: number, y1: number): CanvasGradient {
-    const arr = new JsBuffer(4);
+    const arr = new JsF32Array(4);
     arr.push(x0, y0, x1, y1);
-    let ptr = getWasmBridge()._new_linear_gradient(arr.raw());
+    let ptr = checkPtr(getWasmBridge()._new_linear_gradient(arr.raw()));
     return new CanvasGradient(ptr);
   }
 
   static createRadialGradient(x0: number, y0: number, r0: number, x1: number, y1: number, r1: number): CanvasGradient {
-    const arr = new JsBuffer(6);
+    const arr = new JsF32Array(6);
     arr.push(x0, y0, r0, x1, y1, r1);
-    let ptr = getWasmBridge()._new_radial_gradient(arr.raw());
+    let ptr = checkPtr(getWasmBridge()._new_radial_gradient(arr.raw()));
     return new CanvasGradient(ptr);
   }
 
   static createConicGradient(startAngle: number, x: number, y: number): CanvasGradient {
-    const arr = new JsBuffer(3);
+    const arr = new JsF32Array(3);
     arr.push(startAngle, x, y);
-    let ptr = getWasmBridge()._new_conic_gradient(arr.raw());
+    let ptr = checkPtr(getWasmBridge()._new_conic_gradient(arr.raw()));
     return new CanvasGradient(ptr);
   }
 
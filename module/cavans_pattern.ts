@@ -3,24 +3,25 @@ import { FinalizeHandler, Raw } from "./finalize";
 import { KImage } from "./image";
 import { JsString } from "./jstypes";
 import { getWasmBridge } from "./registry";
+import { checkPtr } from "./errors";
 
 export class CanvasPattern extends Raw {
 
   constructor(ptr: CanvasPatternPtr) {
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_canvas_pattern(ptr);
     }))
     this.ptr = ptr;
   }
 
 
   static fromImage(img: KImage, repetition: string = "repeat") {
-    let ptr = getWasmBridge()._new_canvas_pattern_from_image(img.raw(), (new JsString(repetition).raw()))
+    let ptr = checkPtr(getWasmBridge()._new_canvas_pattern_from_image(img.raw(), (new JsString(repetition).raw())));
     return new CanvasPattern(ptr);
   }
 
   static fromContext(ctx: Context2D, repetition: string = "repeat") {
-    let ptr = getWasmBridge()._new_canvas_pattern_from_canvas(ctx.raw(), (new JsString(repetition).raw()));
+    let ptr = checkPtr(getWasmBridge()._new_canvas_pattern_from_canvas(ctx.raw(), (new JsString(repetition).raw())));
     return new CanvasPattern(ptr);
   }
 
@@ -5,11 +5,24 @@ import { KImage } from "./image";
 import { CanvasWasm } from './canvas';
 import { FonSlant, FontSpec, FontWidth } from "./font_spec";
 import { Path2D } from "./path2d";
-import { ImageData } from "./image_data";
-import { parseCornerRadii, parseVariant, weightMap } from "./utils";
+import { CanvasColorType, ImageData, ImageDataPixelFormat, PredefinedColorSpace, createPixelArray, decodePixels } from "./image_data";
+import { DirtyRect, parseCornerRadii, parseVariant, toDirtyRect, weightMap } from "./utils";
 import { CanvasGradient } from './canvas_gradient';
 import { CanvasPattern } from "./cavans_pattern";
 import { debug, warn } from "./logger";
+import { checkPtr, checkStatus } from "./errors";
+import { RichText, RichTextMetrics, toRichTextMetrics } from "./rich_text";
+
+export interface CanvasRenderingContext2DSettings {
+  colorSpace?: PredefinedColorSpace;
+  colorType?: CanvasColorType;
+  willReadFrequently?: boolean;
+}
+
+export interface ImageDataSettings {
+  colorSpace?: PredefinedColorSpace;
+  pixelFormat?: ImageDataPixelFormat;
+}
 
 type FillRule = "nonzero" | "evenodd";
 
@@ -27,10 +40,22 @@ export class Context2D extends Raw {
     strokeStyle: null,
   }]
 
-  constructor(private canvas: CanvasWasm) {
-    let contextPtr = getWasmBridge()._new_context(canvas.raw());
+  private layerDepths: number[] = [];
+
+  constructor(private canvas: CanvasWasm, settings?: CanvasRenderingContext2DSettings) {
+    const map = new JsStrMap();
+    if(settings?.colorSpace) {
+      map.insert('colorSpace', settings.colorSpace);
+    }
+    if(settings?.colorType) {
+      map.insert('colorType', settings.colorType);
+    }
+    if(settings?.willReadFrequently) {
+      map.insert('willReadFrequently', 'true');
+    }
+    let contextPtr = checkPtr(getWasmBridge()._new_context(canvas.raw(), map.raw()));
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(this.ptr);
+      // the context is released together with its canvas, see CanvasWasm
     }));
     this.ptr = contextPtr;
     // set defualt font
@@ -114,22 +139,53 @@ export class Context2D extends Raw {
     return ''
   }
 
-  set fontKerning(kerning: string) {}
+  set fontKerning(kerning: string) {
+    debug("setFontKerning", kerning);
+    this.bridge._set_fontKerning(this.raw(), new JsString(kerning).raw());
+  }
 
   get fontKerning() {
-    return "normal"
+    debug("getFontKerning");
+    return JsString.fromPtr(this.bridge._get_fontKerning(this.raw())).value;
   }
 
-  set fontStretch(s: string) {}
+  set fontStretch(stretch: string) {
+    debug("setFontStretch", stretch);
+    this.bridge._set_fontStretch(this.raw(), new JsString(stretch).raw());
+  }
 
   get fontStretch() {
-    return "nomal";
+    debug("getFontStretch");
+    return JsString.fromPtr(this.bridge._get_fontStretch(this.raw())).value;
   }
 
-  set fontVariantCaps(cap: string) {}
+  set fontVariantCaps(caps: string) {
+    debug("setFontVariantCaps", caps);
+    this.bridge._set_fontVariantCaps(this.raw(), new JsString(caps).raw());
+  }
 
   get fontVariantCaps() {
-    return "nomal"
+    debug("getFontVariantCaps");
+    return JsString.fromPtr(this.bridge._get_fontVariantCaps(this.raw())).value;
+  }
+
+  // in retained mode, drawing done while elementId is set is remembered for hitTest()
+  set retained(retained: boolean) {
+    debug("setRetained", retained);
+    this.bridge._set_retained(this.raw(), retained ? 1 : 0);
+  }
+
+  get retained(): boolean {
+    return this.bridge._get_retained(this.raw()) === 1;
+  }
+
+  set elementId(id: string) {
+    debug("setElementId", id);
+    this.bridge._set_elementId(this.raw(), new JsString(id).raw());
+  }
+
+  get elementId(): string {
+    return JsString.fromPtr(this.bridge._get_elementId(this.raw())).value;
   }
 
   set globalAlpha(alpha: number) {
@@ -172,11 +228,14 @@ export class Context2D extends Raw {
     return JsString.fromPtr(this.bridge._get_imageSmoothingQuality(this.raw())).value;
   }
 
-  // TODO TextTraking
-  set letterSpacing(spacing: string) {}
+  set letterSpacing(spacing: string) {
+    debug("setLetterSpacing", spacing);
+    this.bridge._set_letterSpacing(this.raw(), new JsString(spacing).raw());
+  }
 
   get letterSpacing() {
-    return "0px"
+    debug("getLetterSpacing");
+    return JsString.fromPtr(this.bridge._get_letterSpacing(this.raw())).value;
   }
 
   set lineCap(cap: string) {
@@ -279,16 +338,24 @@ export class Context2D extends Raw {
     return JsString.fromPtr(this.bridge._get_textBaseline(this.raw())).value
   }
 
-  set textRendering(rendering: string) {}
+  set textRendering(rendering: string) {
+    debug("setTextRendering", rendering);
+    this.bridge._set_textRendering(this.raw(), new JsString(rendering).raw());
+  }
 
   get textRendering() {
-    return "auto"
+    debug("getTextRendering");
+    return JsString.fromPtr(this.bridge._get_textRendering(this.raw())).value;
   }
 
-  set wordSpacing(spacing: string) {}
+  set wordSpacing(spacing: string) {
+    debug("setWordSpacing", spacing);
+    this.bridge._set_wordSpacing(this.raw(), new JsString(spacing).raw());
+  }
 
   get wordSpacing() {
-    return "0px";
+    debug("getWordSpacing");
+    return JsString.fromPtr(this.bridge._get_wordSpacing(this.raw())).value;
   }
 
   get font() {
@@ -361,7 +428,7 @@ export class Context2D extends Raw {
     this.bridge._fillRect(this.ptr, arr.raw());
   }
 
-  drawImage(image: CanvasWasm | HTMLCanvasElement | OffscreenCanvas | HTMLImageElement | SVGImageElement | HTMLVideoElement | VideoFrame | ImageBitmap, sx: number, sy: number, sw?: number, sh?: number, dx?: number, dy?: number, dw?: number, dh?: number) {
+  drawImage(image: KImage | CanvasWasm | HTMLCanvasElement | OffscreenCanvas | HTMLImageElement | SVGImageElement | HTMLVideoElement | VideoFrame | ImageBitmap, sx: number, sy: number, sw?: number, sh?: number, dx?: number, dy?: number, dw?: number, dh?: number) {
     debug('drawImage', image);
     if(window.CANVAS_WASM_RENDERER_CONTEXT === 'html2canvas' && image instanceof HTMLImageElement) {
       // fix html2canvas一些绘制异常
@@ -380,7 +447,7 @@ export class Context2D extends Raw {
     })
     
     const img = KImage.fromImage(image);
-    this.bridge._drawImage(this.raw(), img.raw(), arr.raw());
+    checkStatus(this.bridge._drawImage(this.raw(), img.raw(), arr.raw()));
   }
 
   drawImageFromBuffer(imageBuffer: Uint8Array, sx: number, sy: number, sw?: number, sh?: number, dx?: number, dy?: number, dw?: number, dh?: number) {
@@ -393,7 +460,7 @@ export class Context2D extends Raw {
     for(let i = 0; i < imageBuffer.length; i++) {
       jsbuff.push(imageBuffer[i]);
     }
-    this.bridge._drawImageFromBuffer(this.raw(), jsbuff.raw(), arr.raw());
+    checkStatus(this.bridge._drawImageFromBuffer(this.raw(), jsbuff.raw(), arr.raw()));
   }
 
   fillText(text: string , x: number, y: number, maxWidth?: number) {
@@ -407,15 +474,11 @@ export class Context2D extends Raw {
     this.bridge._fillText(this.raw(), t.raw(), arr.raw())
   }
 
-  drawRichText(text: string , x: number, y: number, maxWidth?: number) {
-    debug('drawRichText', text, x, y, maxWidth);
-    const t = new JsString(text);
-    const args = [x, y, maxWidth].filter((v)=> v !== undefined) as number[];
-    const arr = new JsF32Array(args.length);
-    args.forEach((v)=> {
-      arr.push(v);
-    });
-    this.bridge._drawRichText(this.raw(), t.raw(), arr.raw())
+  drawRichText(text: RichText, x: number, y: number): RichTextMetrics {
+    debug('drawRichText', x, y);
+    const arr = new JsF32Array(2);
+    arr.push(x, y);
+    return toRichTextMetrics(JsF32Array.fromPtr(this.bridge._drawRichText(this.raw(), text.raw(), arr.raw())).toArray());
   }
 
   strokeText(text: string, x: number, y: number, maxWidth?: number) {
@@ -519,17 +582,34 @@ export class Context2D extends Raw {
     return CanvasGradient.createRadialGradient(x0, y0, r0, x1, y1, r1);
   }
 
-  createImageData(width: number | ImageData, height?: number) {
+  createImageData(width: number | ImageData, height?: number, settings?: ImageDataSettings) {
     debug('createImageData', width, height);
     if(width instanceof ImageData) {
-      return new ImageData(width.data, width.width, width.height);
+      const data = createPixelArray(width.data.length, width.pixelFormat);
+      return new ImageData(data, width.width, width.height, width.colorSpace, width.pixelFormat);
     } else {
-      return new ImageData(new Uint8Array(width * (height as number) * 4), width, (height as number));
+      const colorSpace = settings?.colorSpace ?? this.colorSpace;
+      const pixelFormat = settings?.pixelFormat ?? `rgba-${this.colorType}` as ImageDataPixelFormat;
+      const data = createPixelArray(width * (height as number) * 4, pixelFormat);
+      return new ImageData(data, width, (height as number), colorSpace, pixelFormat);
     }
   }
+
+  get colorSpace(): PredefinedColorSpace {
+    return JsString.fromPtr(this.bridge._get_colorSpace(this.raw())).value as PredefinedColorSpace;
+  }
+
+  // the area drawn to since the last flush, or null if there's nothing new to present
+  get dirtyRect(): DirtyRect | null {
+    return toDirtyRect(JsF32Array.fromPtr(this.bridge._get_dirtyRect(this.raw())).toArray());
+  }
+
+  get colorType(): CanvasColorType {
+    return JsString.fromPtr(this.bridge._get_colorType(this.raw())).value as CanvasColorType;
+  }
   
 
-  createPattern(image: HTMLImageElement | SVGImageElement | HTMLVideoElement | HTMLCanvasElement | VideoFrame | ImageBitmap | OffscreenCanvas | CanvasWasm, repetition = "repeat") {
+  createPattern(image: KImage | HTMLImageElement | SVGImageElement | HTMLVideoElement | HTMLCanvasElement | VideoFrame | ImageBitmap | OffscreenCanvas | CanvasWasm, repetition = "repeat") {
     debug('createPattern', repetition);
     return CanvasPattern.fromImage(KImage.fromImage(image))
   }
@@ -543,7 +623,7 @@ export class Context2D extends Raw {
     debug('ellipse', x, y, radiusX, radiusY, rotation, startAngle, endAngle, anticlockwise);
     const arr = new JsF32Array(8);
     arr.push(x, y, radiusX, radiusY, rotation, startAngle, endAngle, anticlockwise ? 1.0 : 0);
-    this.bridge._ellipse(this.raw(), arr.raw());
+    checkStatus(this.bridge._ellipse(this.raw(), arr.raw()));
   }
   // fileRule https://www.zhangxinxu.com/wordpress/2018/10/nonzero-evenodd-fill-mode-rule/
   fill(path?: Path2D | FillRule, fillRule?: FillRule) {
@@ -561,16 +641,21 @@ export class Context2D extends Raw {
     debug('getContextAttributes');
     return {
       alpha: true,
-      colorSpace: 'srgb',
+      colorSpace: this.colorSpace,
+      colorType: this.colorType,
       desynchronized: false,
-      willReadFrequently: false,
+      willReadFrequently: this.bridge._get_willReadFrequently(this.raw()) === 1,
     }
   }
 
-  getImageData(x: number, y: number, width: number, height: number): ImageData {
+  getImageData(x: number, y: number, width: number, height: number, settings?: ImageDataSettings): ImageData {
     debug('getImageData', x, y, width, height);
-    const imageData = JsImageData.fromPtr(this.bridge._getImageData(this.raw(), x, y, width, height));
-    return new ImageData(imageData.data, imageData.width, imageData.height);
+    const colorSpace = new JsString(settings?.colorSpace ?? '');
+    const pixelFormat = new JsString(settings?.pixelFormat ?? '');
+    const ptr = checkPtr(this.bridge._getImageData(this.raw(), x, y, width, height, colorSpace.raw(), pixelFormat.raw()));
+    const imageData = JsImageData.fromPtr(ptr);
+    const format = imageData.pixelFormat;
+    return new ImageData(decodePixels(imageData.data, format), imageData.width, imageData.height, imageData.colorSpace, format);
   }
 
   putImageData(imageData: ImageData, dx: number, dy: number, dirtyX?: number, dirtyY?: number, dirtyWidth?: number, dirtyHeight?: number) {
@@ -580,8 +665,8 @@ export class Context2D extends Raw {
     args.forEach((v)=> {
       arr.push(v);
     });
-    const jsImageData = new JsImageData(imageData.data, imageData.width, imageData.height);
-    getWasmBridge()._putImageData(this.raw(), jsImageData.raw(), arr.raw());
+    const jsImageData = new JsImageData(imageData.data, imageData.width, imageData.height, imageData.colorSpace, imageData.pixelFormat);
+    checkStatus(getWasmBridge()._putImageData(this.raw(), jsImageData.raw(), arr.raw()));
   }
 
   getLineDash() {
@@ -612,6 +697,18 @@ export class Context2D extends Raw {
     throw new Error('unsupport arguments length');
   }
 
+  // the elementId of the topmost retained element at (x, y) in canvas pixels
+  hitTest(x: number, y: number): string | null {
+    debug('hitTest', x, y);
+    const id = JsString.fromPtr(this.bridge._hitTest(this.raw(), x, y)).value;
+    return id === '' ? null : id;
+  }
+
+  getElementBounds(id: string): DirtyRect | null {
+    debug('getElementBounds', id);
+    return toDirtyRect(JsF32Array.fromPtr(this.bridge._getElementBounds(this.raw(), new JsString(id).raw())).toArray());
+  }
+
   isPointInStroke(path: Path2D | number, x: number, y?: number): boolean {
     debug('isPointInStroke', path, x, y);
     if(path instanceof Path2D) {
@@ -621,7 +718,7 @@ export class Context2D extends Raw {
     }
   }
 
-  measureText(text: string) {
+  measureText(text: string): TextMetrics {
     debug('measureText', text);
     const t = new JsString(text);
     const arr = new JsF32Array(0);
@@ -642,8 +739,9 @@ export class Context2D extends Raw {
         actualBoundingBoxDescent: line.get(6),
         emHeightAscent: line.get(7),
         emHeightDescent: line.get(8),
-        alphabeticBaseline: line.get(9),
-        ideographicBaseline: line.get(10)
+        hangingBaseline: line.get(9),
+        alphabeticBaseline: line.get(10),
+        ideographicBaseline: line.get(11)
       }
     }
   }
@@ -658,6 +756,8 @@ export class Context2D extends Raw {
   reset() {
     debug('reset');
     this.bridge._reset(this.raw());
+    this.layerDepths = [];
+    this.stack = [{ fillStyle: null, strokeStyle: null }];
   }
 
   resetTransform() {
@@ -698,11 +798,30 @@ export class Context2D extends Raw {
   restore() {
     debug('restore');
     this.bridge._restore(this.raw());
-    if(this.stack.length !== 1) {
+    // as on the rust side, restore() stops at the start of the innermost layer
+    const floor = this.layerDepths[this.layerDepths.length - 1] ?? 1;
+    if(this.stack.length > floor) {
       this.stack.pop();
     }
   }
 
+  // group subsequent drawing and composite it as a unit once endLayer() is called; omitted options
+  // fall back to the current globalAlpha and globalCompositeOperation
+  beginLayer(options?: { alpha?: number, compositeOperation?: string }) {
+    debug('beginLayer', options);
+    const operation = new JsString(options?.compositeOperation ?? '');
+    checkStatus(this.bridge._beginLayer(this.raw(), options?.alpha ?? NaN, operation.raw(), 0));
+    this.stack.push(Object.assign({}, this.currentState()));
+    this.layerDepths.push(this.stack.length);
+  }
+
+  endLayer() {
+    debug('endLayer');
+    checkStatus(this.bridge._endLayer(this.raw()));
+    const depth = this.layerDepths.pop() as number;
+    this.stack.length = depth - 1;
+  }
+
   scale(x: number, y: number) {
     debug('scale', x, y);
     const arr = new JsF32Array(2);
@@ -762,4 +881,20 @@ export class Context2D extends Raw {
       height: size[1]
     }
   }
+
+  // pdf-only annotations, in the current transform; other export formats ignore them
+  annotateLinkUrl(x: number, y: number, width: number, height: number, url: string) {
+    debug('annotateLinkUrl', x, y, width, height, url);
+    this.bridge._annotateLinkUrl(this.raw(), x, y, width, height, new JsString(url).raw());
+  }
+
+  annotateLinkToDestination(x: number, y: number, width: number, height: number, name: string) {
+    debug('annotateLinkToDestination', x, y, width, height, name);
+    this.bridge._annotateLinkToDestination(this.raw(), x, y, width, height, new JsString(name).raw());
+  }
+
+  annotateNamedDestination(x: number, y: number, name: string) {
+    debug('annotateNamedDestination', x, y, name);
+    this.bridge._annotateNamedDestination(this.raw(), x, y, new JsString(name).raw());
+  }
 }
\ No newline at end of file
@@ -0,0 +1,30 @@
+import { JsString } from "./jstypes";
+import { getWasmBridge } from "./registry";
+
+// indexed by the ErrorCode values in src/error.rs
+const ERROR_NAMES = ["", "TypeError", "IndexSizeError", "InvalidStateError", "NotSupportedError", "SyntaxError", "EncodingError"];
+
+export function takeLastError(): Error {
+  const bridge = getWasmBridge();
+  const code = bridge._get_last_error();
+  const messagePtr = bridge._get_last_error_message();
+  const message = messagePtr ? JsString.fromPtr(messagePtr).value : "unknown error";
+  bridge._clear_last_error();
+  if(code === 1) {
+    return new TypeError(message);
+  }
+  return new DOMException(message, ERROR_NAMES[code] ?? "UnknownError");
+}
+
+export function checkStatus(status: number) {
+  if(status !== 0) {
+    throw takeLastError();
+  }
+}
+
+export function checkPtr<T extends number>(ptr: T): T {
+  if(!ptr) {
+    throw takeLastError();
+  }
+  return ptr;
+}
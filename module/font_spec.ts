@@ -35,7 +35,7 @@ export class FontSpec extends Raw {
     let ptr: FontSpecPtr = 0;
     ptr = getWasmBridge()._new_font_spec();
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_font_spec(ptr);
     }))
     this.ptr = ptr;
     this.setFamiles(...this.familes);
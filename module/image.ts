@@ -1,7 +1,8 @@
 import { CanvasWasm } from "./canvas";
 import { FinalizeHandler, Raw } from "./finalize";
-import { JsBuffer } from "./jstypes";
+import { JsBuffer, JsF32Array, JsStrMap, JsString } from "./jstypes";
 import { getWasmBridge } from "./registry";
+import { checkPtr, checkStatus } from "./errors";
 
 function createJsBufferFromBase64(dataURL: string): JsBuffer {
   var BASE64_MARKER = ';base64,';
@@ -20,7 +21,26 @@ function createJsBufferFromBase64(dataURL: string): JsBuffer {
   return jsbuff
 }
 
-export class KImage extends Raw {
+type ImageBitmapSource = Uint8Array | Blob | KImage | Parameters<typeof KImage.fromImage>[0];
+
+export type ImageBitmapOptions = {
+  resizeWidth?: number;
+  resizeHeight?: number;
+  resizeQuality?: 'pixelated' | 'low' | 'medium' | 'high';
+  premultiplyAlpha?: 'default' | 'premultiply' | 'none';
+  imageOrientation?: 'from-image' | 'flipY' | 'none';
+  colorSpaceConversion?: 'default' | 'none';
+}
+
+function bufferFromBytes(bytes: Uint8Array): JsBuffer {
+  const jsbuff = new JsBuffer(bytes.length);
+  for(let i = 0; i < bytes.length; i++) {
+    jsbuff.push(bytes[i]);
+  }
+  return jsbuff;
+}
+
+
 
   constructor(ptr?: ImagePtr) {
     let p: ImagePtr = 0;
@@ -30,7 +50,7 @@ export class KImage extends Raw {
       p = getWasmBridge()._new_image();
     }
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(p);
+      getWasmBridge()._drop_image(p);
     }))
     this.ptr = p;
   }
@@ -40,15 +60,52 @@ export class KImage extends Raw {
   }
 
   get width() {
-    return getWasmBridge()._image_data_get_width(this.raw());
+    return getWasmBridge()._image_get_width(this.raw());
   }
 
   get height() {
-    return getWasmBridge()._image_data_get_height(this.raw());
+    return getWasmBridge()._image_get_height(this.raw());
+  }
+
+  // EXIF orientation of the source data (1 means upright), reported even when it wasn't applied
+  get orientation(): number {
+    return getWasmBridge()._image_get_orientation(this.raw());
+  }
+
+  // description of the embedded ICC profile, or '' if there is none
+  get colorProfile(): string {
+    return JsString.fromPtr(getWasmBridge()._image_get_color_profile(this.raw())).value;
+  }
+
+  // 1 for still images
+  get frameCount(): number {
+    return getWasmBridge()._image_get_frame_count(this.raw());
+  }
+
+  // repeats after the first play-through, or Infinity for endless animations
+  get loopCount(): number {
+    const loops = getWasmBridge()._image_get_loop_count(this.raw());
+    return loops < 0 ? Infinity : loops;
   }
 
-  static fromImage(image: HTMLImageElement | SVGImageElement | HTMLVideoElement | HTMLCanvasElement | VideoFrame | ImageBitmap | OffscreenCanvas | CanvasWasm): KImage {
-    if(image instanceof CanvasWasm) {
+  // milliseconds that the given frame stays on screen
+  frameDuration(index: number): number {
+    return getWasmBridge()._image_get_frame_duration(this.raw(), index);
+  }
+
+  // the frame drawn by drawImage and used by patterns
+  get frame(): number {
+    return getWasmBridge()._image_get_frame(this.raw());
+  }
+
+  set frame(index: number) {
+    checkStatus(getWasmBridge()._image_set_frame(this.raw(), index));
+  }
+
+  static fromImage(image: HTMLImageElement | SVGImageElement | HTMLVideoElement | HTMLCanvasElement | VideoFrame | ImageBitmap | OffscreenCanvas | CanvasWasm | KImage): KImage {
+    if(image instanceof KImage) {
+      return image;
+    } else if(image instanceof CanvasWasm) {
       const ctx = image.getContext('2d');
       return this.fromPtr(getWasmBridge()._get_image(ctx.raw()))
     } else {
@@ -82,12 +139,14 @@ export class KImage extends Raw {
     }
   }
 
-  static fromBuffer(binaryData: Uint8Array): KImage {
-    const jsbuff = new JsBuffer(binaryData.length);
-    for(let i = 0; i < binaryData.length; i++) {
-      jsbuff.push(binaryData[i]);
-    }
+  // SVG documents are accepted too and stay vector when drawn at any scale. Like CSS
+  // image-orientation, 'none' leaves EXIF-rotated photos as they were stored
+  static fromBuffer(binaryData: Uint8Array, options?: { imageOrientation?: 'from-image' | 'none' }): KImage {
+    const jsbuff = bufferFromBytes(binaryData);
     const img = new KImage();
+    if(options?.imageOrientation === 'none') {
+      getWasmBridge()._image_set_honor_orientation(img.raw(), 0);
+    }
     getWasmBridge()._image_set_data(img.raw(), jsbuff.raw());
     return img;
   }
@@ -97,3 +156,31 @@ export class KImage extends Raw {
     return this.ptr
   }
 }
+
+// Mirrors the global createImageBitmap: the image is fully decoded before the promise resolves, so
+// drawing it never waits on the decoder
+export function createImageBitmap(source: ImageBitmapSource, options?: ImageBitmapOptions): Promise<KImage>;
+export function createImageBitmap(source: ImageBitmapSource, sx: number, sy: number, sw: number, sh: number, options?: ImageBitmapOptions): Promise<KImage>;
+export async function createImageBitmap(source: ImageBitmapSource, ...args: any[]): Promise<KImage> {
+  const crop = new JsF32Array(4);
+  if(typeof args[0] === 'number') {
+    crop.push(...(args.splice(0, 4) as number[]));
+  }
+  const options: ImageBitmapOptions = args[0] ?? {};
+  const map = new JsStrMap();
+  for(const [key, value] of Object.entries(options)) {
+    if(value !== undefined) {
+      map.insert(key, String(value));
+    }
+  }
+
+  const bridge = getWasmBridge();
+  if(source instanceof Blob) {
+    source = new Uint8Array(await source.arrayBuffer());
+  }
+  if(source instanceof Uint8Array) {
+    return KImage.fromPtr(checkPtr(bridge._new_image_bitmap(bufferFromBytes(source).raw(), crop.raw(), map.raw())));
+  }
+  const image = KImage.fromImage(source);
+  return KImage.fromPtr(checkPtr(bridge._image_bitmap_from_image(image.raw(), crop.raw(), map.raw())));
+}
@@ -1,3 +1,102 @@
+export type PredefinedColorSpace = 'srgb' | 'display-p3';
+export type ImageDataPixelFormat = 'rgba-unorm8' | 'rgba-float16' | 'rgba-float32';
+export type CanvasColorType = 'unorm8' | 'float16' | 'float32';
+
+// float16 pixels are exposed as a Float16Array where the runtime has one, and as a Float32Array
+// holding the same values elsewhere
+export type ImageDataArray = Uint8Array | Float32Array;
+
 export class ImageData {
-  constructor(public readonly data: Uint8Array, public readonly width: number, public readonly height: number) {}
-}
\ No newline at end of file
+  constructor(
+    public readonly data: ImageDataArray,
+    public readonly width: number,
+    public readonly height: number,
+    public readonly colorSpace: PredefinedColorSpace = 'srgb',
+    public readonly pixelFormat: ImageDataPixelFormat = 'rgba-unorm8',
+  ) {}
+}
+
+export function bytesPerPixel(format: ImageDataPixelFormat): number {
+  return format === 'rgba-float32' ? 16 : format === 'rgba-float16' ? 8 : 4;
+}
+
+export function createPixelArray(length: number, format: ImageDataPixelFormat): ImageDataArray {
+  switch(format) {
+    case 'rgba-float32': return new Float32Array(length);
+    case 'rgba-float16': return newFloat16Array(length);
+    default: return new Uint8Array(length);
+  }
+}
+
+// interpret the little-endian bytes handed back by the bridge
+export function decodePixels(bytes: Uint8Array, format: ImageDataPixelFormat): ImageDataArray {
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  switch(format) {
+    case 'rgba-float32': {
+      const out = new Float32Array(bytes.byteLength / 4);
+      out.forEach((_, i)=> out[i] = view.getFloat32(i * 4, true));
+      return out;
+    }
+    case 'rgba-float16': {
+      const out = newFloat16Array(bytes.byteLength / 2);
+      out.forEach((_, i)=> out[i] = halfToFloat(view.getUint16(i * 2, true)));
+      return out;
+    }
+    default:
+      return bytes;
+  }
+}
+
+export function encodePixels(data: ImageDataArray, format: ImageDataPixelFormat): Uint8Array {
+  if(data instanceof Uint8Array) {
+    return data;
+  }
+  const width = format === 'rgba-float16' ? 2 : 4;
+  const bytes = new Uint8Array(data.length * width);
+  const view = new DataView(bytes.buffer);
+  data.forEach((v, i)=> {
+    if(width === 2) {
+      view.setUint16(i * 2, floatToHalf(v), true);
+    } else {
+      view.setFloat32(i * 4, v, true);
+    }
+  });
+  return bytes;
+}
+
+function newFloat16Array(length: number): Float32Array {
+  const Float16 = (globalThis as any).Float16Array;
+  return Float16 ? new Float16(length) : new Float32Array(length);
+}
+
+function halfToFloat(h: number): number {
+  const sign = h & 0x8000 ? -1 : 1;
+  const exp = (h >> 10) & 0x1f;
+  const frac = h & 0x3ff;
+  if(exp === 0) {
+    return sign * Math.pow(2, -14) * (frac / 1024);
+  } else if(exp === 0x1f) {
+    return frac ? NaN : sign * Infinity;
+  }
+  return sign * Math.pow(2, exp - 15) * (1 + frac / 1024);
+}
+
+function floatToHalf(v: number): number {
+  const f32 = new Float32Array([v]);
+  const bits = new Uint32Array(f32.buffer)[0];
+  const sign = (bits >> 16) & 0x8000;
+  const exp = ((bits >> 23) & 0xff) - 127 + 15;
+  const frac = bits & 0x7fffff;
+  if(isNaN(v)) {
+    return 0x7e00;
+  } else if(exp >= 0x1f) {
+    return sign | 0x7c00;
+  } else if(exp <= 0) {
+    if(exp < -10) {
+      return sign;
+    }
+    const m = (frac | 0x800000) >> (1 - exp);
+    return sign | ((m + 0x1000) >> 13);
+  }
+  return sign | (exp << 10) | ((frac + 0x1000) >> 13);
+}
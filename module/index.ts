@@ -3,13 +3,17 @@ import { registerWasmBridge } from './registry';
 
 export * from './canvas';
 export * from './path2d';
+export * from './pdf_document';
+export * from './animation';
+export * from './image';
+export * from './rich_text';
 
-export function initCanvas(el: HTMLCanvasElement | OffscreenCanvas): Promise<CanvasWasm> {
+export function initCanvas(el: HTMLCanvasElement | OffscreenCanvas, options?: { raster?: boolean }): Promise<CanvasWasm> {
   // @ts-ignore
   return import('../release/canvas-wasm.js').then((mod)=> {
     return mod.default().then((m: any) => {
       registerWasmBridge(m);
-      return new CanvasWasm(el)
+      return new CanvasWasm(el, options)
     });
   })
 }
\ No newline at end of file
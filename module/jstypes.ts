@@ -1,5 +1,7 @@
 import { FinalizeHandler, Raw } from "./finalize";
 import { getWasmBridge } from "./registry";
+import { checkPtr } from "./errors";
+import { ImageDataArray, ImageDataPixelFormat, encodePixels } from "./image_data";
 
 export class JsF32Array extends Raw {
   constructor(cap: number| JsF32ArrayPtr, fromPtr?: boolean) {
@@ -10,7 +12,7 @@ export class JsF32Array extends Raw {
       ptr = getWasmBridge()._new_js_f32_array(cap);
     }
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_js_f32_array(ptr);
     }));
     this.ptr = ptr;
   }
@@ -81,7 +83,7 @@ export class JsString extends Raw {
 export class Dye extends Raw {
   constructor(dyePtr: DyePtr) {
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(dyePtr)
+      getWasmBridge()._drop_dye(dyePtr)
     }))
     this.ptr = dyePtr;
   }
@@ -113,10 +115,10 @@ export class JsBuffer extends Raw {
     if(fromPtr) {
       ptr = cap;
     } else {
-      ptr = getWasmBridge()._new_js_f32_array(cap);
+      ptr = getWasmBridge()._new_js_buffer(cap);
     }
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_js_buffer(ptr);
     }));
     this.ptr = ptr;
   }
@@ -174,7 +176,7 @@ export class JsAnyArray extends Raw {
       ptr = getWasmBridge()._new_js_any_array(cap);
     }
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_js_any_array(ptr);
     }));
     this.ptr = ptr;
   }
@@ -210,7 +212,7 @@ export class JsStrMap extends Raw {
   constructor() {
     let ptr = getWasmBridge()._new_js_str_map();
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_js_str_map(ptr);
     }));
     this.ptr = ptr;
   }
@@ -235,19 +237,20 @@ export class JsStrMap extends Raw {
 export class JsImageData extends Raw {
   private _bufRef: JsBuffer;
 
-  constructor(data: Uint8Array | ImageDataPtr, width?: number, height?: number) {
-    if(data instanceof Uint8Array) {
-      let bufRef = JsBuffer.fromBuffer(data);
-      let ptr = getWasmBridge()._new_image_data(bufRef.raw(), width as number, height as number);
+  constructor(data: ImageDataArray | ImageDataPtr, width?: number, height?: number, colorSpace?: string, pixelFormat?: ImageDataPixelFormat) {
+    if(typeof data === 'object') {
+      let bufRef = JsBuffer.fromBuffer(encodePixels(data, pixelFormat ?? 'rgba-unorm8'));
+      let format = new JsString(pixelFormat ?? '');
+      let ptr = checkPtr(getWasmBridge()._new_image_data(bufRef.raw(), width as number, height as number, new JsString(colorSpace ?? '').raw(), format.raw()));
       super(new FinalizeHandler(()=> {
-        getWasmBridge()._free(ptr);
+        getWasmBridge()._drop_image_data(ptr);
       }))
       this._bufRef = bufRef;
       this.ptr = ptr;
     } else {
       let ptr = data as ImageDataPtr;
       super(new FinalizeHandler(()=> {
-        getWasmBridge()._free(ptr);
+        getWasmBridge()._drop_image_data(ptr);
       }))
       this._bufRef = JsBuffer.fromPtr(getWasmBridge()._image_data_get_data(ptr));
       this.ptr = ptr;
@@ -267,6 +270,14 @@ export class JsImageData extends Raw {
     return this.bridge._image_data_get_height(this.raw());
   }
 
+  get colorSpace(): 'srgb' | 'display-p3' {
+    return JsString.fromPtr(this.bridge._image_data_get_color_space(this.raw())).value as 'srgb' | 'display-p3';
+  }
+
+  get pixelFormat(): ImageDataPixelFormat {
+    return JsString.fromPtr(this.bridge._image_data_get_pixel_format(this.raw())).value as ImageDataPixelFormat;
+  }
+
   get data() {
     if(this._bufRef) {
       return this._bufRef.toBuffer();
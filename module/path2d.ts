@@ -17,7 +17,7 @@ export class Path2D extends Raw {
       throw new Error('unsupport Path2D type: ' + path);
     }
     super(new FinalizeHandler(()=> {
-      getWasmBridge()._free(ptr);
+      getWasmBridge()._drop_path2d(ptr);
     }))
     this.ptr = ptr;
   }
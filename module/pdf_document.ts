@@ -0,0 +1,52 @@
+import { FinalizeHandler, Raw } from "./finalize";
+import { CanvasWasm, cuttingArray, cuttingOptions, PdfInfo, pdfInfoMap } from "./canvas";
+import { Context2D } from "./context";
+import { JsBuffer, JsString } from "./jstypes";
+import { getWasmBridge } from "./registry";
+import { checkPtr, checkStatus } from "./errors";
+import { debug } from "./logger";
+
+type PageOptions = {
+  width?: number;
+  height?: number;
+  matte?: string;
+  background?: string;
+  cuttingOptions?: cuttingOptions;
+}
+
+// A PDF assembled page by page, from several canvases or from successive frames of one
+export class PdfDocument extends Raw {
+  constructor(options?: { quality?: number, density?: number, info?: PdfInfo }) {
+    let info = pdfInfoMap(options?.info);
+    let ptr = getWasmBridge()._new_pdf_document(options?.quality ?? 1, options?.density ?? 1, info.raw());
+    super(new FinalizeHandler(()=> {
+      getWasmBridge()._drop_pdf_document(ptr);
+    }));
+    this.ptr = ptr;
+  }
+
+  // snapshot what has been drawn so far; the canvas can keep drawing afterwards
+  addPage(source: CanvasWasm | Context2D, options?: PageOptions) {
+    debug('addPage', options);
+    const ctx = source instanceof CanvasWasm ? source.getContext('2d') : source;
+    checkStatus(this.bridge._pdf_document_add_page(
+      this.raw(),
+      ctx.raw(),
+      options?.width ?? 0,
+      options?.height ?? 0,
+      new JsString(options?.matte ?? '').raw(),
+      new JsString(options?.background ?? '').raw(),
+      cuttingArray(options?.cuttingOptions).raw()
+    ));
+    return this;
+  }
+
+  get pageCount(): number {
+    return this.bridge._pdf_document_page_count(this.raw());
+  }
+
+  close(): Uint8Array {
+    const bufPtr = checkPtr(this.bridge._pdf_document_close(this.raw()));
+    return JsBuffer.fromPtr(bufPtr).toBuffer();
+  }
+}
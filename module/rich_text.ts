@@ -0,0 +1,170 @@
+import { checkStatus } from "./errors";
+import { FinalizeHandler, Raw } from "./finalize";
+import { KImage } from "./image";
+import { JsF32Array, JsStrMap, JsString } from "./jstypes";
+import { getWasmBridge } from "./registry";
+
+export interface RichTextStyle {
+  fontFamily?: string;
+  fontSize?: number;
+  fontWeight?: number | "normal" | "bold";
+  fontStyle?: "normal" | "italic" | "oblique";
+  color?: string;
+  background?: string;
+  // space-separated list of underline, overline and line-through
+  decoration?: string;
+  decorationColor?: string;
+  decorationStyle?: "solid" | "double" | "dotted" | "dashed" | "wavy";
+  // pixels, or a CSS length such as "0.1em"
+  letterSpacing?: number | string;
+  wordSpacing?: number | string;
+  // a multiple of the font size
+  lineHeight?: number;
+}
+
+export interface RichTextOptions {
+  // lines wrap at this width; if omitted the paragraph is as wide as its longest line
+  width?: number;
+  maxLines?: number;
+  ellipsis?: string;
+  align?: CanvasTextAlign;
+}
+
+export interface PlaceholderOptions {
+  alignment?: "baseline" | "above-baseline" | "below-baseline" | "top" | "bottom" | "middle";
+  baseline?: "alphabetic" | "ideographic";
+  // distance from the top of the placeholder to its baseline, defaulting to its height
+  baselineOffset?: number;
+  // drawn to fill the placeholder, so it must have finished loading
+  image?: KImage;
+}
+
+export interface RichTextRect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface RichTextSelectionRect extends RichTextRect {
+  direction: "ltr" | "rtl";
+}
+
+export interface RichTextPosition {
+  // in UTF-16 code units, with each placeholder counting as one
+  index: number;
+  // whether the position belongs with the character after it rather than the one before
+  affinity: "upstream" | "downstream";
+}
+
+export interface RichTextCaret {
+  x: number;
+  y: number;
+  height: number;
+}
+
+export interface RichTextMetrics {
+  width: number;
+  height: number;
+  minIntrinsicWidth: number;
+  maxIntrinsicWidth: number;
+  alphabeticBaseline: number;
+  ideographicBaseline: number;
+  longestLine: number;
+  lineCount: number;
+  didExceedMaxLines: boolean;
+}
+
+function toStrMap(values: object): JsStrMap {
+  const map = new JsStrMap();
+  Object.entries(values).forEach(([k, v])=> {
+    if(v !== undefined) {
+      map.insert(k, String(v));
+    }
+  });
+  return map;
+}
+
+export function toRichTextMetrics(arr: number[]): RichTextMetrics {
+  const [width, height, minIntrinsicWidth, maxIntrinsicWidth, alphabeticBaseline,
+         ideographicBaseline, longestLine, lineCount, didExceedMaxLines] = arr;
+  return {
+    width, height, minIntrinsicWidth, maxIntrinsicWidth, alphabeticBaseline,
+    ideographicBaseline, longestLine, lineCount, didExceedMaxLines: didExceedMaxLines == 1
+  };
+}
+
+export class RichText extends Raw {
+  constructor(options?: RichTextOptions) {
+    let ptr: RichTextPtr = getWasmBridge()._new_rich_text();
+    super(new FinalizeHandler(()=> {
+      getWasmBridge()._drop_rich_text(ptr);
+    }))
+    this.ptr = ptr;
+    if(options) {
+      this.setOptions(options);
+    }
+  }
+
+  raw(): RichTextPtr {
+    return this.ptr;
+  }
+
+  addRun(text: string, style: RichTextStyle = {}): this {
+    const t = new JsString(text);
+    checkStatus(this.bridge._rich_text_add_run(this.raw(), t.raw(), toStrMap(style).raw()));
+    return this;
+  }
+
+  addPlaceholder(width: number, height: number, options: PlaceholderOptions = {}): this {
+    const { image, ...rest } = options;
+    const map = toStrMap(rest);
+    checkStatus(this.bridge._rich_text_add_placeholder(this.raw(), width, height, map.raw(), image ? image.raw() : 0));
+    return this;
+  }
+
+  setOptions(options: RichTextOptions) {
+    checkStatus(this.bridge._rich_text_set_options(this.raw(), toStrMap(options).raw()));
+  }
+
+  get metrics(): RichTextMetrics {
+    return toRichTextMetrics(JsF32Array.fromPtr(this.bridge._rich_text_get_metrics(this.raw())).toArray());
+  }
+
+  // the text position closest to a point relative to the paragraph's top-left corner
+  positionAt(x: number, y: number): RichTextPosition {
+    const [index, downstream] = JsF32Array.fromPtr(this.bridge._rich_text_get_position_at(this.raw(), x, y)).toArray();
+    return { index, affinity: downstream == 1 ? "downstream" : "upstream" };
+  }
+
+  // selection boxes covering the text from start to end, one per line and direction run
+  rectsForRange(start: number, end: number): RichTextSelectionRect[] {
+    const nums = JsF32Array.fromPtr(this.bridge._rich_text_get_rects_for_range(this.raw(), start, end)).toArray();
+    const rects: RichTextSelectionRect[] = [];
+    for(let i = 0; i + 4 < nums.length; i += 5) {
+      rects.push({ x: nums[i], y: nums[i + 1], width: nums[i + 2], height: nums[i + 3], direction: nums[i + 4] == 1 ? "rtl" : "ltr" });
+    }
+    return rects;
+  }
+
+  wordBoundary(index: number): { start: number, end: number } {
+    const [start, end] = JsF32Array.fromPtr(this.bridge._rich_text_get_word_boundary(this.raw(), index)).toArray();
+    return { start, end };
+  }
+
+  // where to draw the caret when it is placed before the character at index
+  caretAt(index: number): RichTextCaret {
+    const [x, y, height] = JsF32Array.fromPtr(this.bridge._rich_text_get_caret(this.raw(), index)).toArray();
+    return { x, y, height };
+  }
+
+  // where each placeholder was laid out, relative to the paragraph's top-left corner
+  get placeholderRects(): RichTextRect[] {
+    const nums = JsF32Array.fromPtr(this.bridge._rich_text_get_placeholder_rects(this.raw())).toArray();
+    const rects: RichTextRect[] = [];
+    for(let i = 0; i + 3 < nums.length; i += 4) {
+      rects.push({ x: nums[i], y: nums[i + 1], width: nums[i + 2], height: nums[i + 3] });
+    }
+    return rects;
+  }
+}
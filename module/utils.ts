@@ -79,6 +79,14 @@ export async function fetchBuffer(src: string): Promise<Uint8Array> {
   return new Uint8Array(fontArrayBuffer);
 }
 
+export type DirtyRect = { x: number, y: number, width: number, height: number };
+
+// unpack the [x, y, width, height] arrays returned by the bridge (empty when nothing is dirty)
+export function toDirtyRect(arr: number[]): DirtyRect | null {
+  const [x, y, width, height] = arr;
+  return arr.length == 4 ? { x, y, width, height } : null;
+}
+
 export function parseCornerRadii(r: any){
   r = [r].flat()
          .map(n => n instanceof DOMPoint ? n : new DOMPoint(n, n))
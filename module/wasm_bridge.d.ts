@@ -16,13 +16,25 @@ type ImageDataPtr = Ptr;
 type CanvasGradientPtr = Ptr;
 type CanvasPatternPtr = Ptr;
 type CanvasTexturePtr = Ptr;
+type PdfDocumentPtr = Ptr;
+type FrameCollectorPtr = Ptr;
+type RichTextPtr = Ptr;
 
 interface WasmBridge extends EmscriptenModule {
   _new_canvas(surface: SurfacePtr, width: number, height: number): CanvasPtr;
-  _new_context(canvas: CanvasPtr): Context2DPtr;
+  _drop_canvas(canvas: CanvasPtr);
+  _new_context(canvas: CanvasPtr, settings: JsStrMapPtr): Context2DPtr;
+  _get_colorSpace(contextPtr: Context2DPtr): StringPtr;
+  _get_colorType(contextPtr: Context2DPtr): StringPtr;
+  _get_willReadFrequently(contextPtr: Context2DPtr): number;
+  _get_dirtyRect(contextPtr: Context2DPtr): JsF32ArrayPtr;
+  _drop_context(context: Context2DPtr);
   _init_surface(width: number, height: number): SurfacePtr;
+  _init_raster_surface(width: number, height: number): SurfacePtr;
+  _surface_is_raster(surfacePtr: SurfacePtr): number;
   _resize_surface(surfacePtr: SurfacePtr, width: number, height: number);
-  _render_to_surface(surfacePtr: SurfacePtr, context: Context2DPtr);
+  _render_to_surface(surfacePtr: SurfacePtr, context: Context2DPtr): number;
+  _surface_get_presented_rect(surfacePtr: SurfacePtr): JsF32ArrayPtr;
 
   _rect(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
   _fillRect(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
@@ -30,7 +42,7 @@ interface WasmBridge extends EmscriptenModule {
   _strokeRect(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
   _clearRect(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
   _arc(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
-  _ellipse(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
+  _ellipse(contextPtr: Context2DPtr, array: JsF32ArrayPtr): number;
   _moveTo(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
   _lineTo(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
   _arcTo(contextPtr: Context2DPtr, array: JsF32ArrayPtr);
@@ -50,7 +62,7 @@ interface WasmBridge extends EmscriptenModule {
   _scale(contextPtr: Context2DPtr, arr: JsF32ArrayPtr);
   _rotate(contextPtr: Context2DPtr, radians: number);
   _resetTransform(contextPtr: Context2DPtr);
-  _createProjection(contextPtr: Context2DPtr, dstArr: JsF32ArrayPtr, src: JsF32ArrayPtr);
+  _createProjection(contextPtr: Context2DPtr, dstArr: JsF32ArrayPtr, src: JsF32ArrayPtr): JsF32ArrayPtr;
   _get_currentTransform(contextPtr: Context2DPtr): JsF32ArrayPtr;
   _set_currentTransform(contextPtr: Context2DPtr, arr: JsF32ArrayPtr);
   _get_fillStyle(contextPtr: Context2DPtr): DyePtr;
@@ -71,7 +83,7 @@ interface WasmBridge extends EmscriptenModule {
   _set_lineWidth(contextPtr: Context2DPtr, num: number);
   _get_miterLimit(contextPtr: Context2DPtr): number;
   _set_miterLimit(contextPtr: Context2DPtr, num: number);
-  _drawCanvas(contextPtr: Context2DPtr, targetContextPtr: Context2DPtr, arr: JsF32ArrayPtr);
+  _drawCanvas(contextPtr: Context2DPtr, targetContextPtr: Context2DPtr, arr: JsF32ArrayPtr): number;
   _get_imageSmoothingEnabled(contextPtr: Context2DPtr): number;
   _set_imageSmoothingEnabled(contextPtr: Context2DPtr, smoothing: number);
   _get_imageSmoothingQuality(contextPtr: Context2DPtr): StringPtr;
@@ -85,6 +97,24 @@ interface WasmBridge extends EmscriptenModule {
   _set_textBaseline(contextPtr: Context2DPtr, text_baseline: StringPtr);
   _get_direction(contextPtr: Context2DPtr): StringPtr;
   _set_direction(contextPtr: Context2DPtr, direction: StringPtr);
+  _get_letterSpacing(contextPtr: Context2DPtr): StringPtr;
+  _set_letterSpacing(contextPtr: Context2DPtr, spacing: StringPtr);
+  _get_wordSpacing(contextPtr: Context2DPtr): StringPtr;
+  _set_wordSpacing(contextPtr: Context2DPtr, spacing: StringPtr);
+  _get_fontKerning(contextPtr: Context2DPtr): StringPtr;
+  _set_fontKerning(contextPtr: Context2DPtr, kerning: StringPtr);
+  _get_fontStretch(contextPtr: Context2DPtr): StringPtr;
+  _set_fontStretch(contextPtr: Context2DPtr, stretch: StringPtr);
+  _get_fontVariantCaps(contextPtr: Context2DPtr): StringPtr;
+  _set_fontVariantCaps(contextPtr: Context2DPtr, caps: StringPtr);
+  _get_textRendering(contextPtr: Context2DPtr): StringPtr;
+  _set_textRendering(contextPtr: Context2DPtr, rendering: StringPtr);
+  _beginLayer(contextPtr: Context2DPtr, alpha: number, compositeOperation: StringPtr, filter: number): number;
+  _endLayer(contextPtr: Context2DPtr): number;
+  _get_retained(contextPtr: Context2DPtr): number;
+  _set_retained(contextPtr: Context2DPtr, retained: number);
+  _get_elementId(contextPtr: Context2DPtr): StringPtr;
+  _set_elementId(contextPtr: Context2DPtr, id: StringPtr);
   _get_globalAlpha(contextPtr: Context2DPtr): number;
   _set_globalAlpha(contextPtr: Context2DPtr, alpha: number);
   _get_globalCompositeOperation(contextPtr: Context2DPtr): StringPtr;
@@ -101,53 +131,61 @@ interface WasmBridge extends EmscriptenModule {
   _set_shadowOffsetY(contextPtr: Context2DPtr, offset_y: number);
   _set_font(contextPtr: Context2DPtr, spec: FontSpecPtr);
   _fillText(contextPtr: Context2DPtr, text: StringPtr, arr: JsF32ArrayPtr);
-  _drawRichText(contextPtr: Context2DPtr, text: StringPtr, arr: JsF32ArrayPtr);
+  _drawRichText(contextPtr: Context2DPtr, text: RichTextPtr, arr: JsF32ArrayPtr): JsF32ArrayPtr;
 
   _outlineText(contextPtr: Context2DPtr, text: StringPtr);
   _measureText(contextPtr: Context2DPtr, text: StringPtr, arr: JsF32ArrayPtr);
-  _getImageData(contextPtr: Context2DPtr, x: number, y: number, width: number, height: number): JsBufferPtr;
-  _putImageData(contextPtr: Context2DPtr, image_data_ptr: ImageDataPtr, arr: JsF32ArrayPtr);
+  _getImageData(contextPtr: Context2DPtr, x: number, y: number, width: number, height: number, colorSpace: StringPtr, pixelFormat: StringPtr): ImageDataPtr;
+  _putImageData(contextPtr: Context2DPtr, image_data_ptr: ImageDataPtr, arr: JsF32ArrayPtr): number;
   _set_lineDashMarker(contextPtr: Context2DPtr, path: Path2DPtr);
   _get_lineDashMarker(contextPtr: Context2DPtr): Path2DPtr;
   _isPointInPath(contextPtr: Context2DPtr, path: Path2DPtr, x: number, y: number, rule: number);
   _isPointInStroke(contextPtr: Context2DPtr, path: Path2DPtr, x: number, y: number, rule: number);
+  _hitTest(contextPtr: Context2DPtr, x: number, y: number): StringPtr;
+  _getElementBounds(contextPtr: Context2DPtr, id: StringPtr): JsF32ArrayPtr;
   _clip(contextPtr: Context2DPtr, path: Path2DPtr, rule: u32);
   _fill(contextPtr: Context2DPtr, path: Path2DPtr, rule: u32);
   _stroke(contextPtr: Context2DPtr, path: Path2DPtr);  
 
-  _drawImage(cx: Context2DPtr, image: ImagePtr, arr: JsF32ArrayPtr);
-  _drawImageFromContext(cx: Context2DPtr, context: Context2DPtr, arr: JsF32ArrayPtr);
-  _drawImageFromBuffer(cx: Context2DPtr, buf: JsBufferPtr, arr: JsF32ArrayPtr);
+  _drawImage(cx: Context2DPtr, image: ImagePtr, arr: JsF32ArrayPtr): number;
+  _drawImageFromContext(cx: Context2DPtr, context: Context2DPtr, arr: JsF32ArrayPtr): number;
+  _drawImageFromBuffer(cx: Context2DPtr, buf: JsBufferPtr, arr: JsF32ArrayPtr): number;
 
   _new_js_f32_array(cap: number): JsF32ArrayPtr;
   _js_f32_array_len(ptr: JsF32ArrayPtr): number;
   _js_f32_array_get(ptr: JsF32ArrayPtr, index: number): number;
   _js_f32_array_set(ptr: JsF32ArrayPtr, index: number, value: number);
   _js_f32_array_push(ptr: JsF32ArrayPtr, value: number);
+  _drop_js_f32_array(ptr: JsF32ArrayPtr);
 
   _new_js_buffer(cap: number): JsF32ArrayPtr;
   _js_buffer_len(ptr: JsF32ArrayPtr): number;
   _js_buffer_get(ptr: JsF32ArrayPtr, index: number): number;
   _js_buffer_set(ptr: JsF32ArrayPtr, index: number, value: number);
   _js_buffer_push(ptr: JsF32ArrayPtr, value: number);
+  _drop_js_buffer(ptr: JsBufferPtr);
   _new_dye_from_color(ptr: StringPtr): DyePtr;
   _new_dye_from_gradient(g: CanvasGradientPtr): DyePtr;
   _new_dye_from_pattern(p: CanvasPatternPtr): DyePtr;
   _new_dye_from_texture(t: CanvasTexturePtr): DyePtr;
+  _drop_dye(dye: DyePtr);
 
   _new_js_any_array(cap: number): JsAnyArrayPtr;
   _js_any_array_len(arr: JsAnyArrayPtr): number;
   _js_any_array_get(arr: JsAnyArrayPtr, index: number): Ptr;
   _js_any_array_set(arr: JsAnyArrayPtr, index: number, v: Ptr);
   _js_any_array_push(arr: JsAnyArrayPtr, v: Ptr);
+  _drop_js_any_array(arr: JsAnyArrayPtr);
 
   _new_js_str_map(): JsStrMapPtr;
   _js_str_map_insert(m: JsStrMapPtr, k: StringPtr, v: StringPtr);
   _js_str_map_delete(m: JsStrMapPtr, k: StringPtr): number;
   _js_str_map_has(m: JsStrMapPtr, k: StringPtr): number;
   _js_str_map_clear(m: JsStrMapPtr);
+  _drop_js_str_map(m: JsStrMapPtr);
 
   _new_font_spec(): FontSpecPtr;
+  _drop_font_spec(spec: FontSpecPtr);
   _font_spec_set_families(spec: FontSpecPtr, arr: JsAnyArrayPtr);
   _font_spec_set_size(spec: FontSpecPtr, size: number);
   _font_spec_set_leading(spec: FontSpecPtr, leading: number);
@@ -155,11 +193,25 @@ interface WasmBridge extends EmscriptenModule {
   _font_spec_set_canonical(spec: FontSpecPtr, canonical: StringPtr);
   _font_spec_set_features(spec: FontSpecPtr, features: JsStrMapPtr);
   _font_spec_set_variant(spec: FontSpecPtr, variant: StringPtr);
+  _new_rich_text(): RichTextPtr;
+  _drop_rich_text(text: RichTextPtr);
+  _rich_text_add_run(text: RichTextPtr, run: StringPtr, style: JsStrMapPtr): number;
+  _rich_text_add_placeholder(text: RichTextPtr, width: number, height: number, options: JsStrMapPtr, image: ImagePtr): number;
+  _rich_text_set_options(text: RichTextPtr, options: JsStrMapPtr): number;
+  _rich_text_get_placeholder_rects(text: RichTextPtr): JsF32ArrayPtr;
+  _rich_text_get_position_at(text: RichTextPtr, x: number, y: number): JsF32ArrayPtr;
+  _rich_text_get_rects_for_range(text: RichTextPtr, start: number, end: number): JsF32ArrayPtr;
+  _rich_text_get_word_boundary(text: RichTextPtr, index: number): JsF32ArrayPtr;
+  _rich_text_get_caret(text: RichTextPtr, index: number): JsF32ArrayPtr;
+  _rich_text_get_metrics(text: RichTextPtr): JsF32ArrayPtr;
 
-  _new_image_data(data: JsBufferPtr, width: number, height: number): ImageDataPtr;
+  _new_image_data(data: JsBufferPtr, width: number, height: number, colorSpace: StringPtr, pixelFormat: StringPtr): ImageDataPtr;
   _image_data_get_data(image_data: ImageDataPtr);
   _image_data_get_width(image_data: ImageDataPtr): number;
   _image_data_get_height(image_data: ImageDataPtr): number;
+  _image_data_get_color_space(image_data: ImageDataPtr): StringPtr;
+  _image_data_get_pixel_format(image_data: ImageDataPtr): StringPtr;
+  _drop_image_data(image_data: ImageDataPtr);
 
   stringToNewUTF8(str: string): StringPtr;
   UTF8ToString(str: StringPtr): string;
@@ -167,6 +219,7 @@ interface WasmBridge extends EmscriptenModule {
   _new_path2d(): Path2DPtr;
   _new_path2d_form_svg(svg_path: StringPtr): Path2DPtr;
   _new_path2d_from_path(path: Path2DPtr): Path2DPtr;
+  _drop_path2d(path: Path2DPtr);
   _path2d_add_path(path: Path2DPtr, otherPath: Path2DPtr, transform: MatrixPtr);
   _path2d_close_path(path: Path2DPtr);
   _path2d_move_to(path: Path2DPtr, x: number, y: number);
@@ -191,26 +244,40 @@ interface WasmBridge extends EmscriptenModule {
   _path2d_bounds(path: Path2DPtr): JSF32ArrayPtr;
   _path2d_contains(path: Path2DPtr, x: number, y: number): number;
   _path2d_get_d(path: Path2DPtr): StringPtr;
-  _path2d_set_d(path: Path2DPtr, svg_path: StringPtr);
+  _path2d_set_d(path: Path2DPtr, svg_path: StringPtr): number;
 
-  _add_font_family(fontBuf: JsBufferPtr, alias: StringPtr);
+  _add_font_family(fontBuf: JsBufferPtr, alias: StringPtr): number;
   _reset_fonts();
 
   _new_image(): ImagePtr;
+  _drop_image(image: ImagePtr);
   _image_set_data(image: ImagePtr, buffer: JsBufferPtr): number;
   _image_get_width(image: ImagePtr): number;
   _image_get_height(image: ImagePtr): number;
+  _image_get_orientation(image: ImagePtr): number;
+  _image_get_color_profile(image: ImagePtr): StringPtr;
+  _image_set_honor_orientation(image: ImagePtr, honor: number);
+  _image_get_frame_count(image: ImagePtr): number;
+  _image_get_frame_duration(image: ImagePtr, index: number): number;
+  _image_get_loop_count(image: ImagePtr): number;
+  _image_get_frame(image: ImagePtr): number;
+  _image_set_frame(image: ImagePtr, index: number): number;
+  _new_image_bitmap(buffer: JsBufferPtr, crop: JsF32ArrayPtr, options: JsStrMapPtr): ImagePtr;
+  _image_bitmap_from_image(image: ImagePtr, crop: JsF32ArrayPtr, options: JsStrMapPtr): ImagePtr;
 
-  _new_linear_gradient(arr: JsBufferPtr): CanvasGradientPtr;
-  _new_radial_gradient(arr: JsBufferPtr): CanvasGradientPtr;
-  _new_conic_gradient(arr: JsBufferPtr): CanvasGradientPtr;
+  _new_linear_gradient(arr: JsF32ArrayPtr): CanvasGradientPtr;
+  _new_radial_gradient(arr: JsF32ArrayPtr): CanvasGradientPtr;
+  _new_conic_gradient(arr: JsF32ArrayPtr): CanvasGradientPtr;
   _add_color_stop(g: CanvasGradientPtr, offset: number, color: StringPtr);
+  _drop_canvas_gradient(g: CanvasGradientPtr);
   
   _new_canvas_pattern_from_image(image: ImagePtr, repetition: StringPtr): CanvasPatternPtr;
   _new_canvas_pattern_from_canvas(cx: Context2DPtr, repetition: StringPtr): CanvasPatternPtr;
   _canvas_pattern_set_transform(cp: CanvasPatternPtr, arr: JsF32ArrayPtr);
+  _drop_canvas_pattern(cp: CanvasPatternPtr);
 
   _new_canvas_texture(path: Path2DPtr, color: StringPtr, line: number, nums: JsF32ArrayPtr): CanvasTexturePtr;
+  _drop_canvas_texture(t: CanvasTexturePtr);
 
   _get_image(cx: Context2DPtr): ImagePtr;
 
@@ -218,7 +285,27 @@ interface WasmBridge extends EmscriptenModule {
   _canvas_set_height(canvasPtr: CanvasPtr, height: number);
   _canvas_get_width(canvasPtr): number;
   _canvas_get_height(canvasPtr): number;
-  _canvas_save_as(canvasPtr, format: StringPtr, quality: number, density: number, matte: StringPtr, bacground: StringPtr, cuttingOptions: JsF32ArrayPtr): JsBufferPtr;
+  _canvas_save_as(canvasPtr, format: StringPtr, quality: number, density: number, matte: StringPtr, bacground: StringPtr, cuttingOptions: JsF32ArrayPtr, outline: number, info: JsStrMapPtr): JsBufferPtr;
+
+  _new_pdf_document(quality: number, density: number, info: JsStrMapPtr): PdfDocumentPtr;
+  _drop_pdf_document(doc: PdfDocumentPtr);
+  _pdf_document_add_page(doc: PdfDocumentPtr, cx: Context2DPtr, width: number, height: number, matte: StringPtr, background: StringPtr, cutting: JsF32ArrayPtr): number;
+  _pdf_document_page_count(doc: PdfDocumentPtr): number;
+  _pdf_document_close(doc: PdfDocumentPtr): JsBufferPtr;
+
+  _new_frame_collector(): FrameCollectorPtr;
+  _drop_frame_collector(fc: FrameCollectorPtr);
+  _frame_collector_add_frame(fc: FrameCollectorPtr, cx: Context2DPtr, delay: number, matte: StringPtr): number;
+  _frame_collector_frame_count(fc: FrameCollectorPtr): number;
+  _frame_collector_encode(fc: FrameCollectorPtr, format: StringPtr, loops: number, quality: number): JsBufferPtr;
+
+  _annotateLinkUrl(cx: Context2DPtr, x: number, y: number, width: number, height: number, url: StringPtr);
+  _annotateLinkToDestination(cx: Context2DPtr, x: number, y: number, width: number, height: number, name: StringPtr);
+  _annotateNamedDestination(cx: Context2DPtr, x: number, y: number, name: StringPtr);
+
+  _get_last_error(): number;
+  _get_last_error_message(): StringPtr;
+  _clear_last_error();
 
   ready: Promise<void>
 }
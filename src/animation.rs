@@ -0,0 +1,226 @@
+use std::ffi::c_char;
+
+use skia_safe::{AlphaType, ColorSpace, ColorType, EncodedImageFormat, ISize, Image as SkImage, ImageInfo, image::CachingHint};
+
+use crate::canvas::CRC32;
+use crate::context::jstypes::JsBuffer;
+use crate::context::Context2D;
+use crate::error::{boxed, status, Error, ErrorCode, Result};
+use crate::utils::*;
+
+pub(crate) const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+struct Frame {
+  image: SkImage,
+  delay: u16, // milliseconds
+}
+
+/// Snapshots of a context, collected one frame at a time and encoded as a single animation.
+#[derive(Default)]
+pub struct FrameCollector {
+  frames: Vec<Frame>,
+}
+
+impl FrameCollector {
+  pub fn new() -> Self {
+    FrameCollector { frames: vec![] }
+  }
+
+  pub fn len(&self) -> usize {
+    self.frames.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.frames.is_empty()
+  }
+
+  pub fn add_frame(&mut self, ctx: &mut Context2D, delay: f32, matte: Option<skia_safe::Color>) -> Result<()> {
+    // animations are encoded in sRGB whatever the context's color space
+    let image = ctx.get_image(matte)
+      .and_then(|image| image.new_color_space(ColorSpace::new_srgb()))
+      .ok_or_else(|| Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))?;
+    // ...and with 8 bits per channel, so frames from float contexts match the rest
+    let image = match image.color_type() {
+      ColorType::RGBA8888 | ColorType::BGRA8888 => image,
+      _ => {
+        let info = ImageInfo::new(image.dimensions(), ColorType::RGBA8888, AlphaType::Unpremul, Some(ColorSpace::new_srgb()));
+        let row_bytes = info.min_row_bytes();
+        SkImage::from_raster_data(&info, skia_safe::Data::new_copy(&rgba_pixels(&image)?), row_bytes)
+          .ok_or_else(|| Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))?
+      }
+    };
+    if let Some(first) = self.frames.first() {
+      if first.image.dimensions() != image.dimensions() {
+        return Err(Error::new(ErrorCode::IndexSize, format!(
+          "Every frame must be {}×{} (got {}×{})",
+          first.image.width(), first.image.height(), image.width(), image.height()
+        )))
+      }
+    }
+    let delay = delay.round().clamp(0.0, u16::MAX as f32) as u16;
+    self.frames.push(Frame { image, delay });
+    Ok(())
+  }
+
+  fn dims(&self) -> Result<ISize> {
+    match self.frames.first() {
+      Some(frame) => Ok(frame.image.dimensions()),
+      None => Err(Error::new(ErrorCode::InvalidState, "Cannot encode an animation without frames"))
+    }
+  }
+
+  /// Assemble an APNG from Skia's encoding of each frame. `loops` of 0 repeats forever.
+  pub fn to_apng(&self, loops: u32) -> Result<Vec<u8>> {
+    let dims = self.dims()?;
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut sequence = 0u32;
+
+    for (idx, frame) in self.frames.iter().enumerate() {
+      let png = frame.image.encode_to_data(EncodedImageFormat::PNG)
+        .ok_or_else(|| Error::new(ErrorCode::Encoding, "Could not encode an animation frame"))?;
+      let chunks = png_chunks(png.as_bytes())?;
+
+      if idx == 0 {
+        let ihdr = chunks.iter().find(|(kind, _)| kind == b"IHDR")
+          .ok_or_else(|| Error::new(ErrorCode::Encoding, "Encoded frame has no header"))?;
+        write_chunk(&mut out, b"IHDR", ihdr.1);
+        write_chunk(&mut out, b"acTL", &[
+          (self.frames.len() as u32).to_be_bytes(),
+          loops.to_be_bytes(),
+        ].concat());
+      }
+
+      // every frame covers the whole canvas and replaces the previous one
+      let fctl = [
+        &sequence.to_be_bytes()[..],
+        &(dims.width as u32).to_be_bytes(),
+        &(dims.height as u32).to_be_bytes(),
+        &0u32.to_be_bytes(), // x offset
+        &0u32.to_be_bytes(), // y offset
+        &frame.delay.to_be_bytes(),
+        &1000u16.to_be_bytes(), // delay is in ms
+        &[0, 0], // dispose: none, blend: source
+      ].concat();
+      write_chunk(&mut out, b"fcTL", &fctl);
+      sequence += 1;
+
+      for (kind, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+        if idx == 0 {
+          write_chunk(&mut out, kind, data);
+        } else {
+          write_chunk(&mut out, b"fdAT", &[&sequence.to_be_bytes()[..], data].concat());
+          sequence += 1;
+        }
+      }
+    }
+
+    write_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+  }
+
+  /// Encode as an animated GIF, quantizing each frame to its own 256-colour palette. A `quality`
+  /// of 1 gives the slowest, most accurate quantization. `loops` of 0 repeats forever.
+  pub fn to_gif(&self, loops: u32, quality: f32) -> Result<Vec<u8>> {
+    let dims = self.dims()?;
+    let (width, height) = match (u16::try_from(dims.width), u16::try_from(dims.height)) {
+      (Ok(w), Ok(h)) => (w, h),
+      _ => return Err(Error::new(ErrorCode::IndexSize, "GIF frames can be at most 65535 pixels on a side"))
+    };
+    let speed = (1.0 + (1.0 - quality.clamp(0.0, 1.0)) * 29.0).round() as i32;
+    let gif_err = |err: gif::EncodingError| Error::new(ErrorCode::Encoding, err.to_string());
+
+    let mut out = vec![];
+    {
+      let mut encoder = gif::Encoder::new(&mut out, width, height, &[]).map_err(gif_err)?;
+      encoder.set_repeat(match loops {
+        0 => gif::Repeat::Infinite,
+        n => gif::Repeat::Finite(n.min(u16::MAX as u32) as u16),
+      }).map_err(gif_err)?;
+
+      for frame in self.frames.iter() {
+        let mut pixels = rgba_pixels(&frame.image)?;
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, speed);
+        gif_frame.delay = ((frame.delay as u32 + 5) / 10).min(u16::MAX as u32) as u16; // gif delays are in centiseconds
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame).map_err(gif_err)?;
+      }
+    }
+    Ok(out)
+  }
+}
+
+fn rgba_pixels(image: &SkImage) -> Result<Vec<u8>> {
+  let info = ImageInfo::new(image.dimensions(), ColorType::RGBA8888, AlphaType::Unpremul, None);
+  let mut pixels = vec![0; info.compute_min_byte_size()];
+  match image.read_pixels(&info, &mut pixels, info.min_row_bytes(), (0, 0), CachingHint::Disallow) {
+    true => Ok(pixels),
+    false => Err(Error::new(ErrorCode::Encoding, "Could not read the pixels of an animation frame"))
+  }
+}
+
+pub(crate) fn png_chunks(bytes: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+  let malformed = || Error::new(ErrorCode::Encoding, "Encoded frame is not a valid PNG");
+  if !bytes.starts_with(&PNG_SIGNATURE) {
+    return Err(malformed())
+  }
+  let mut chunks = vec![];
+  let mut at = PNG_SIGNATURE.len();
+  while at + 12 <= bytes.len() {
+    let len = u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize;
+    let kind = [bytes[at + 4], bytes[at + 5], bytes[at + 6], bytes[at + 7]];
+    let data = bytes.get(at + 8..at + 8 + len).ok_or_else(malformed)?;
+    chunks.push((kind, data));
+    at += 12 + len;
+  }
+  Ok(chunks)
+}
+
+pub(crate) fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  let mut digest = CRC32.digest();
+  digest.update(kind);
+  digest.update(data);
+  out.extend((data.len() as u32).to_be_bytes());
+  out.extend(kind);
+  out.extend(data);
+  out.extend(digest.finalize().to_be_bytes());
+}
+
+//
+// Javascript Methods
+//
+
+#[no_mangle]
+pub extern "C" fn new_frame_collector() -> *mut FrameCollector {
+  Box::into_raw(Box::new(FrameCollector::new()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_frame_collector(fc: *mut FrameCollector) {
+  if !fc.is_null() {
+    drop(Box::from_raw(fc));
+  }
+}
+
+/// Capture what the context has drawn so far as the next frame, shown for `delay` milliseconds.
+#[no_mangle]
+pub unsafe extern "C" fn frame_collector_add_frame(fc: *mut FrameCollector, cx: *mut Context2D, delay: f32, matte: *mut c_char) -> u32 {
+  let matte = css_to_color(&char_to_string(matte));
+  status((*fc).add_frame(&mut *cx, delay, matte))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn frame_collector_frame_count(fc: *mut FrameCollector) -> u32 {
+  (*fc).len() as u32
+}
+
+/// Encode the collected frames as "png" (APNG) or "gif", returning null (see `get_last_error`) on
+/// failure.
+#[no_mangle]
+pub unsafe extern "C" fn frame_collector_encode(fc: *mut FrameCollector, format: *mut c_char, loops: u32, quality: f32) -> *mut JsBuffer {
+  let format = char_to_string(format);
+  boxed(match format.as_str() {
+    "png" | "apng" => (*fc).to_apng(loops),
+    "gif" => (*fc).to_gif(loops, quality),
+    _ => Err(Error::new(ErrorCode::NotSupported, format!("Unsupported animation format: {}", format)))
+  })
+}
@@ -1,12 +1,13 @@
 #![allow(non_snake_case)]
 use std::{cell::RefCell, ffi::c_char, ptr::null_mut};
 
-use skia_safe::{ pdf, ClipOp, Color, ColorSpace, Data, Document, EncodedImageFormat, Image as SkImage, Matrix, Picture, PictureRecorder, Rect, Size, Vector};
+use skia_safe::{ pdf, svg, ClipOp, Color, ColorSpace, Data, Document, EncodedImageFormat, ISize, Image as SkImage, Matrix, Picture, PictureRecorder, Rect, Size, Vector};
 
-use crate::{context::{jstypes::{JsBuffer, JsF32Array}, Context2D}, surface::SurfaceState, utils::{char_to_string, css_to_color}};
+use crate::{context::{jstypes::{JsBuffer, JsF32Array, JsStrMap}, Context2D}, surface::SurfaceState, utils::{char_to_string, css_to_color, to_date_time}};
+use crate::error::{boxed, Error, ErrorCode, Result};
 pub type BoxedCanvas = RefCell<Canvas>;
 use crc::{Crc, CRC_32_ISO_HDLC};
-const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+pub(crate) const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 pub struct Canvas{
   pub width: f32,
@@ -21,6 +22,22 @@ pub unsafe extern "C" fn new_canvas(surface: *mut SurfaceState, width: f32, heig
   Box::into_raw(c)
 }
 
+/// Free a canvas along with its surface and, if one was created, its context.
+///
+/// Once `new_context` has been called the context owns the canvas, so a canvas/context pair must
+/// be released through exactly one of `drop_canvas` or `drop_context`.
+#[no_mangle]
+pub unsafe extern "C" fn drop_canvas(c: *mut Canvas) {
+  if c.is_null() {
+    return
+  }
+  if (*c).ctx.is_null() {
+    drop(Box::from_raw(c));
+  } else {
+    drop(Box::from_raw((*c).ctx));
+  }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn canvas_get_width(c: *mut Canvas) -> f32 {
   (*c).width
@@ -41,21 +58,39 @@ pub unsafe extern "C" fn canvas_set_height(c: *mut Canvas, height: f32) {
   (*c).height = height;
 }
 
+/// Encode the canvas, returning null (see `get_last_error`) if it can't be exported.
+///
+/// `outline` only applies to svg output, where it replaces `<text>` elements with glyph paths, and
+/// `info` (which may be null) only to pdf output, see [pdf_document].
 #[no_mangle]
-pub unsafe extern "C" fn canvas_save_as(c: *mut Canvas, format: *mut c_char, quality: f32, density: f32,  matte: *mut c_char, background: *mut c_char, cutting: *mut JsF32Array) -> *mut JsBuffer {
+pub unsafe extern "C" fn canvas_save_as(c: *mut Canvas, format: *mut c_char, quality: f32, density: f32,  matte: *mut c_char, background: *mut c_char, cutting: *mut JsF32Array, outline: u32, info: *mut JsStrMap) -> *mut JsBuffer {
   let format = char_to_string(format);
   let matte = css_to_color(&char_to_string(matte));
   let background: Option<Color> = css_to_color(&char_to_string(background));
-  match format.as_str() {
-    "pdf" => save_to_pdf(&mut (*(*c).ctx), quality, density, matte, background, cutting.as_ref().unwrap().as_slice()),
-    "png" | "jpg" | "jpeg" => save_to_image(&mut (*(*c).ctx), format.as_str(), quality, density, matte, background),
-    _ => panic!("unsupport format {}", format)
-  }
+  let cutting = cutting.as_ref().map(|arr| arr.as_slice()).unwrap_or(&[]);
+  boxed(match format.as_str() {
+    "pdf" => save_to_pdf(&mut (*(*c).ctx), quality, density, matte, background, cutting, info.as_ref()),
+    "svg" => save_to_svg(&mut (*(*c).ctx), matte, background, outline == 1),
+    "png" | "jpg" | "jpeg" | "webp" | "avif" => save_to_image(&mut (*(*c).ctx), format.as_str(), quality, density, matte, background),
+    _ => Err(Error::new(ErrorCode::NotSupported, format!("Unsupported export format: {}", format)))
+  })
 }
 
-fn save_to_pdf(ctx: &mut Context2D, quality:f32, density:f32, matte:Option<Color>, background: Option<Color>, cutting: &[f32]) -> *mut JsBuffer {
-  let doc = pdf_document(quality, density);
-  let mut  bounds = ctx.bounds;
+fn save_to_pdf(ctx: &mut Context2D, quality:f32, density:f32, matte:Option<Color>, background: Option<Color>, cutting: &[f32], info: Option<&JsStrMap>) -> Result<Vec<u8>> {
+  let pic = match ctx.get_picture(matte) {
+    Some(pic) => pic,
+    None => return Err(Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))
+  };
+  let doc = pdf_document(quality, density, info);
+  let doc = add_pdf_page(doc, &pic, ctx.bounds, None, background, cutting);
+  let data = doc.close();
+  Ok(Vec::from(data.as_bytes()))
+}
+
+/// Draw a recorded canvas (of size `source`) as a new page, sized to `size` if given, otherwise to
+/// the `cutting` target or the canvas itself.
+pub(crate) fn add_pdf_page(doc: Document, pic: &Picture, source: Rect, size: Option<Size>, background: Option<Color>, cutting: &[f32]) -> Document {
+  let mut  bounds = source;
   let mut source_offset: f32 = 0.0;
   let mut target_offset_top: f32 = 0.0;
   let mut target_offset_right: f32 = 0.0;
@@ -69,44 +104,59 @@ fn save_to_pdf(ctx: &mut Context2D, quality:f32, density:f32, matte:Option<Color
     target_offset_left = cutting[4];
     bounds = Rect::from_xywh(0.0, 0.0, cutting[5], cutting[6]);
   }
+  if let Some(size) = size {
+    bounds = Rect::from_size(size);
+  }
   let mut page: Document<skia_safe::document::state::OnPage> = doc.begin_page(bounds.size(), None);
   let doc_canvas = page.canvas();
-  let p = ctx.get_picture(matte);
-  if let Some(pic) = p {
-    if let Some(background) = background {
-      doc_canvas.clip_rect(bounds, Some(ClipOp::Intersect), true);
-      doc_canvas.clear(background);
-    }
-    doc_canvas.restore();
-    let content_width = bounds.width() - target_offset_left - target_offset_right;
-    let content_height = bounds.height() - target_offset_bottom - target_offset_top;
-    let scale = content_width / ctx.bounds.width();
-    doc_canvas.clip_rect(Rect::from_xywh(target_offset_left, target_offset_top, content_width, content_height), Some(ClipOp::Intersect), true);
-    doc_canvas.translate(Vector::new(target_offset_left, -source_offset + target_offset_top));
-    doc_canvas.scale((scale, scale));
-    doc_canvas.draw_picture(pic, None, None);
-  } else {
-    panic!("no picture");
+  if let Some(background) = background {
+    doc_canvas.clip_rect(bounds, Some(ClipOp::Intersect), true);
+    doc_canvas.clear(background);
   }
-  let doc = page.end_page();
-  let data = doc.close();
-  let bytes = data.as_bytes();
-  Box::into_raw(Box::new(Vec::from(bytes)))
+  doc_canvas.restore();
+  let content_width = bounds.width() - target_offset_left - target_offset_right;
+  let content_height = bounds.height() - target_offset_bottom - target_offset_top;
+  let scale = content_width / source.width();
+  doc_canvas.clip_rect(Rect::from_xywh(target_offset_left, target_offset_top, content_width, content_height), Some(ClipOp::Intersect), true);
+  doc_canvas.translate(Vector::new(target_offset_left, -source_offset + target_offset_top));
+  doc_canvas.scale((scale, scale));
+  doc_canvas.draw_picture(pic, None, None);
+  page.end_page()
 }
 
-fn save_to_image(ctx: &mut Context2D, format: &str, quality:f32, density:f32, matte:Option<Color>, background: Option<Color>) -> *mut JsBuffer{
+fn save_to_svg(ctx: &mut Context2D, matte:Option<Color>, background: Option<Color>, outline: bool) -> Result<Vec<u8>> {
+  let pic = match ctx.get_picture(matte) {
+    Some(pic) => pic,
+    None => return Err(Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))
+  };
+  let flags = match outline {
+    true => svg::canvas::Flags::CONVERT_TEXT_TO_PATHS,
+    false => svg::canvas::Flags::empty()
+  };
+  let mut canvas = svg::Canvas::new(ctx.bounds, flags);
+  if let Some(background) = background {
+    canvas.clear(background);
+  }
+  canvas.draw_picture(&pic, None, None);
+  let data = canvas.end();
+  Ok(Vec::from(data.as_bytes()))
+}
+
+fn save_to_image(ctx: &mut Context2D, format: &str, quality:f32, density:f32, matte:Option<Color>, background: Option<Color>) -> Result<Vec<u8>>{
   let pic = if let Some(pic) =  ctx.get_picture(matte) {
     pic
   } else {
-    panic!("no picture")
+    return Err(Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))
   };
   if ctx.bounds.is_empty(){
-    panic!("Width and height must be non-zero to generate an image")
+    Err(Error::new(ErrorCode::IndexSize, "Width and height must be non-zero to generate an image"))
   } else {
     let img_dims = ctx.bounds.size();
     let img_format = match format {
       "jpg" | "jpeg" => Some(EncodedImageFormat::JPEG),
       "png" => Some(EncodedImageFormat::PNG),
+      "webp" => Some(EncodedImageFormat::WEBP),
+      "avif" => Some(EncodedImageFormat::AVIF),
       _ => None
     };
     if let Some(img_format) = img_format {
@@ -122,30 +172,49 @@ fn save_to_image(ctx: &mut Context2D, format: &str, quality:f32, density:f32, ma
             .set_matrix(&img_scale.into());
         pic.playback(canvas);
         let pic = recorder.finish_recording_as_picture(Some(&bounds));
-        let img = SkImage::from_picture(pic.unwrap(), img_dims, None, None, skia_safe::image::BitDepth::U8, Some(ColorSpace::new_srgb())); 
-        let data = img.unwrap().encode_to_data_with_quality(img_format, (quality*100.0) as i32).map(|data| with_dpi(data, img_format, density));
-        if data.is_some() {
-          let data  = data.unwrap();
-          let bytes = data.as_bytes();
-          Box::into_raw(Box::new(Vec::from(bytes)))
-        } else {
-          panic!("no data from canvas");
+        // PNG, JPEG and WebP output embed the color profile of non-sRGB contexts
+        let color_space = ctx.color_space.to_color_space();
+        // float contexts produce 16-bit PNGs; the other encoders reduce them to 8 bits
+        let bit_depth = ctx.pixel_format.bit_depth();
+        let img = pic.and_then(|pic| SkImage::from_picture(pic, img_dims, None, None, bit_depth, Some(color_space)));
+        // a WebP quality of 100 makes Skia switch to lossless compression
+        let data = img.and_then(|img| img.encode_to_data_with_quality(img_format, (quality*100.0) as i32)).map(|data| with_dpi(data, img_format, density, img_dims));
+        match data {
+          Some(data) => Ok(Vec::from(data.as_bytes())),
+          // Skia can decode AVIF but ships no encoder for it
+          None if img_format == EncodedImageFormat::AVIF => Err(Error::new(ErrorCode::NotSupported, "AVIF encoding is not available in this build")),
+          None => Err(Error::new(ErrorCode::Encoding, format!("Could not encode the canvas as {}", format)))
         }
     } else {
-      panic!("unsupport format")
+      Err(Error::new(ErrorCode::NotSupported, format!("Unsupported image format: {}", format)))
     }
   }
 }
 
-fn pdf_document(quality:f32, density:f32) -> Document{
+/// Start a PDF, taking the document info from `info`: `title`, `author`, `subject`, `keywords`,
+/// `creator`, `creationDate` and `modDate` (milliseconds since the epoch, like `Date.getTime()`)
+/// and `pdfa` ("true" to produce PDF/A). Unknown keys and unparsable dates are ignored.
+pub(crate) fn pdf_document(quality:f32, density:f32, info: Option<&JsStrMap>) -> Document{
   let mut meta = pdf::Metadata::default();
   meta.producer = "Canvas WASM <https://github.com/tain335/canvas-wasm>".to_string();
   meta.encoding_quality = Some((quality*1.0) as i32);
   meta.raster_dpi = Some(density * 72.0);
+  if let Some(info) = info {
+    let text = |key: &str| info.get(key).cloned().unwrap_or_default();
+    let date = |key: &str| info.get(key).and_then(|ms| ms.parse::<f64>().ok()).map(to_date_time);
+    meta.title = text("title");
+    meta.author = text("author");
+    meta.subject = text("subject");
+    meta.keywords = text("keywords");
+    meta.creator = text("creator");
+    meta.creation = date("creationDate");
+    meta.modified = date("modDate");
+    meta.pdfa = info.get("pdfa").map_or(false, |v| v == "true");
+  }
   pdf::new_document(Some(&meta))
 }
 
-fn with_dpi(data:Data, format:EncodedImageFormat, density:f32) -> Data{
+fn with_dpi(data:Data, format:EncodedImageFormat, density:f32, dims:ISize) -> Data{
   if density as u32 == 1 { return data }
 
   let mut bytes = data.as_bytes().to_vec();
@@ -171,6 +240,43 @@ fn with_dpi(data:Data, format:EncodedImageFormat, density:f32) -> Data{
       bytes.splice(33..33, [length, phys, checksum].concat());
       Data::new_copy(&bytes)
     }
+    EncodedImageFormat::WEBP => {
+      // WebP has no resolution field, so record it the way browsers read it: in an EXIF chunk,
+      // which requires the extended (VP8X) file layout
+      let dpi = (72.0 * density) as u32;
+      if &bytes[12..16] != b"VP8X" {
+        let alpha = &bytes[12..16] == b"VP8L" && bytes.get(24).map_or(false, |b| b & 0x10 != 0);
+        let [w0, w1, w2, _] = (dims.width as u32 - 1).to_le_bytes();
+        let [h0, h1, h2, _] = (dims.height as u32 - 1).to_le_bytes();
+        let vp8x = [
+          b'V', b'P', b'8', b'X', 10, 0, 0, 0,
+          if alpha { 0x10 } else { 0 }, 0, 0, 0,
+          w0, w1, w2,
+          h0, h1, h2,
+        ];
+        bytes.splice(12..12, vp8x.iter().cloned());
+      }
+      bytes[20] |= 0x08; // EXIF metadata present
+
+      let [a, b, c, d] = dpi.to_be_bytes();
+      let exif = vec![
+        b'E', b'X', b'I', b'F', 66, 0, 0, 0,
+        b'M', b'M', 0, 42, 0, 0, 0, 8, // big-endian TIFF header, first IFD at offset 8
+        0, 3, // three entries
+        0x01, 0x1A, 0, 5, 0, 0, 0, 1, 0, 0, 0, 50, // XResolution, rational at offset 50
+        0x01, 0x1B, 0, 5, 0, 0, 0, 1, 0, 0, 0, 58, // YResolution, rational at offset 58
+        0x01, 0x28, 0, 3, 0, 0, 0, 1, 0, 2, 0, 0,  // ResolutionUnit = inches
+        0, 0, 0, 0, // no further IFDs
+        a, b, c, d, 0, 0, 0, 1,
+        a, b, c, d, 0, 0, 0, 1,
+      ];
+      bytes.extend(exif);
+
+      let riff_size = (bytes.len() as u32 - 8).to_le_bytes();
+      bytes.splice(4..8, riff_size.iter().cloned());
+      Data::new_copy(&bytes)
+    }
+    // AVIF can only be decoded, so there is never an encoded file to annotate
     _ => data
   }
 }
@@ -8,7 +8,7 @@ use std::ffi::{c_char, c_void, CStr, CString};
 use std::{f32::consts::PI, ptr::null};
 use std::cell::RefCell;
 use skia_safe::{textlayout, Data, RCHandle};
-use skia_safe::{Image as SkImage, canvas, Matrix, PaintStyle, Path, PathDirection::{CCW, CW}, Point, RRect, Rect};
+use skia_safe::{Image as SkImage, canvas, Matrix, Picture, Size, PaintStyle, Path, PathDirection::{CCW, CW}, Point, RRect, Rect};
 use skia_safe::path::AddPathMode::Append;
 use skia_safe::path::AddPathMode::Extend;
 use skia_safe::textlayout::{ParagraphStyle, TextDirection};
@@ -22,7 +22,9 @@ use crate::path::Path2D;
 use crate::image::{Image, BoxedImage};
 use crate::filter::{Filter, FilterSpec};
 use crate::{typography::*, FONT_LIBRARY};
+use crate::rich_text::RichText;
 use crate::utils::*;
+use crate::error::{fail, status, Error, ErrorCode, Result};
 
 pub struct CanvasFilter {
   pub css: String,
@@ -32,10 +34,32 @@ pub struct CanvasFilter {
 //
 // The js interface for the Context2D struct
 //
-#[no_mangle]
-pub extern "C" fn new_context(canvas: *mut Canvas) -> *mut Context2D {
-  unsafe {
+/// `settings` may be null; otherwise its `colorSpace` ("srgb" or "display-p3") and `colorType`
+/// ("unorm8", "float16" or "float32") configure the context, as in `getContext('2d', settings)`.
+/// A `willReadFrequently` of "true" gives it a raster backing store so repeated reads stay fast.
+/// Returns null (see `get_last_error`) for unknown values.
+#[no_mangle]
+pub extern "C" fn new_context(canvas: *mut Canvas, settings: *mut JsStrMap) -> *mut Context2D {
+  unsafe {
+    let settings = settings.as_ref();
+    let color_space = match settings.and_then(|s| s.get("colorSpace")) {
+      Some(name) => match to_supported_color_space(name) {
+        Ok(space) => space,
+        Err(err) => return fail(err)
+      },
+      None => PredefinedColorSpace::Srgb
+    };
+    let pixel_format = match settings.and_then(|s| s.get("colorType")) {
+      Some(name) => match to_pixel_format(&format!("rgba-{}", name)) {
+        Some(format) => format,
+        None => return fail(Error::new(ErrorCode::Type, format!("Unknown color type: {}", name)))
+      },
+      None => PixelFormat::Unorm8
+    };
     let mut cx = Box::new(Context2D::new(Box::from_raw(canvas)));
+    cx.set_color_space(color_space);
+    cx.set_pixel_format(pixel_format);
+    cx.set_will_read_frequently(settings.and_then(|s| s.get("willReadFrequently")).map_or(false, |v| v == "true"));
     cx.reset_size(((*canvas).width, (*canvas).height));
     let ptr = Box::into_raw(cx);
     (*canvas).ctx = ptr;
@@ -43,6 +67,14 @@ pub extern "C" fn new_context(canvas: *mut Canvas) -> *mut Context2D {
   }
 }
 
+/// Free a context along with the canvas (and surface) it was created from. See `drop_canvas`.
+#[no_mangle]
+pub extern "C" fn drop_context(cx: *mut Context2D) {
+  if !cx.is_null() {
+    unsafe { drop(Box::from_raw(cx)) }
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn resetSize(cx: *mut Context2D, canvas: *mut Canvas) {
   unsafe {
@@ -50,6 +82,40 @@ pub extern "C" fn resetSize(cx: *mut Context2D, canvas: *mut Canvas) {
   }
 }
 
+#[no_mangle]
+pub extern "C" fn get_colorSpace(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char(from_color_space_name((*cx).color_space))
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_colorType(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char(from_pixel_format((*cx).pixel_format).trim_start_matches("rgba-").to_string())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_willReadFrequently(cx: *mut Context2D) -> u32 {
+  unsafe {
+    (*cx).will_read_frequently() as u32
+  }
+}
+
+/// The area drawn to since the last present as `[x, y, width, height]` in device pixels, or an
+/// empty array if there's nothing new to present.
+#[no_mangle]
+pub extern "C" fn get_dirtyRect(cx: *mut Context2D) -> *mut JsF32Array {
+  unsafe {
+    let rect = match (*cx).damage() {
+      Some(r) => vec![r.left as f32, r.top as f32, r.width() as f32, r.height() as f32],
+      None => vec![]
+    };
+    Box::into_raw(Box::new(rect))
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn get_size(cx: *mut Context2D) -> *const JsF32Array {
   unsafe {
@@ -94,6 +160,33 @@ pub extern "C" fn restore(cx: *mut Context2D) {
   }
 }
 
+/// `alpha` may be NaN, `composite_operation` empty and `filter` null to use the context's current
+/// globalAlpha, globalCompositeOperation and filter.
+#[no_mangle]
+pub extern "C" fn beginLayer(cx: *mut Context2D, alpha: f32, composite_operation: *mut c_char, filter: *mut CanvasFilter) -> u32 {
+  unsafe {
+    let name = char_to_string(composite_operation);
+    let blend_mode = match name.as_str() {
+      "" => None,
+      _ => match to_blend_mode(&name) {
+        Some(mode) => Some(mode),
+        None => return status(Err(Error::new(ErrorCode::Type, format!("Unknown composite operation: {}", name))))
+      }
+    };
+    let alpha = if alpha.is_nan() { None } else { Some(alpha) };
+    let filter = filter.as_ref().map(|f| Filter::new(&f.css, &f.specs));
+    (*cx).begin_layer(alpha, blend_mode, filter);
+    status(Ok(()))
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn endLayer(cx: *mut Context2D) -> u32 {
+  unsafe {
+    status((*cx).end_layer())
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn transform(cx: *mut Context2D, arr: *mut JsF32Array) {
   unsafe {
@@ -140,18 +233,11 @@ pub extern "C" fn resetTransform(cx: *mut Context2D) {
 #[no_mangle]
 pub extern "C" fn createProjection(cx: *mut Context2D, dstArr: *mut JsF32Array, srcArr: *mut JsF32Array) -> *mut JsF32Array {
   unsafe {
-    let mut src = vec![];
-    let mut dst = vec![];
-    let mut i = 0;
-    while i < (*srcArr).len() {
-      src.push(Point { x: (*srcArr)[0], y: (*srcArr)[1] });
-      i += 2
-    }
-    i = 0;
-    while i < (*dstArr).len() {
-      dst.push(Point { x: (*dstArr)[0], y: (*dstArr)[1] });
-      i += 2
+    if (*srcArr).len() % 2 != 0 || (*dstArr).len() % 2 != 0 {
+      return fail(Error::new(ErrorCode::Type, "Expected an even number of x/y coordinates"))
     }
+    let src:Vec<Point> = (*srcArr).as_slice().chunks(2).map(|xy| Point::new(xy[0], xy[1])).collect();
+    let dst:Vec<Point> = (*dstArr).as_slice().chunks(2).map(|xy| Point::new(xy[0], xy[1])).collect();
     let basis:Vec<Point> = match src.len(){
       0 => (*cx).bounds.to_quad().to_vec(), // use canvas dims
       1 => Rect::from_wh(src[0].x, src[0].y).to_quad().to_vec(), // implicit 0,0 origin
@@ -173,10 +259,10 @@ pub extern "C" fn createProjection(cx: *mut Context2D, dstArr: *mut JsF32Array,
         }
         arr
       },
-      _ => panic!(
+      _ => fail(Error::new(ErrorCode::Type, format!(
         "Expected 2 or 4 x/y points for output quad (got {}) and 0, 1, 2, or 4 points for the coordinate basis (got {})",
         quad.len(), basis.len()
-      )
+      )))
     }
   }
 }
@@ -264,8 +350,11 @@ pub extern "C" fn arc(cx: *mut Context2D, arr: *mut JsF32Array) {
 }
 
 #[no_mangle]
-pub extern "C" fn ellipse(cx: *mut Context2D, arr: *mut JsF32Array) {
+pub extern "C" fn ellipse(cx: *mut Context2D, arr: *mut JsF32Array) -> u32 {
   unsafe { 
+    if (*arr).len() < 7 {
+      return status(Err(Error::new(ErrorCode::Type, format!("ellipse needs 7 arguments (got {})", (*arr).len()))))
+    }
     let nums = &(*arr).as_slice()[0..7];
     let ccwVal = (*arr).get(7);
     let mut ccw = false;
@@ -276,13 +365,14 @@ pub extern "C" fn ellipse(cx: *mut Context2D, arr: *mut JsF32Array) {
     }
     if let [x, y, x_radius, y_radius, rotation, start_angle, end_angle] = nums {
       if *x_radius < 0.0 || *y_radius < 0.0 {
-        panic!("radii cannot be negative")
+        return status(Err(Error::new(ErrorCode::IndexSize, "radii cannot be negative")))
       }
       let matrix = (*cx).state.matrix;
       let mut arc = Path2D::new();
       arc.add_ellipse((*x, *y), (*x_radius, *y_radius), *rotation, *start_angle, *end_angle, ccw);
       (*cx).path.add_path(&arc.path.with_transform(&matrix), (0,0), Extend);
     }
+    status(Ok(()))
   }
 }
 
@@ -376,6 +466,29 @@ pub extern "C" fn isPointInStroke(cx: *mut Context2D, path: *mut Path2D, x: f32,
   _is_in(cx, path, x, y, rule, Stroke)
 }
 
+/// The `elementId` of the topmost retained element at a point in canvas pixels (regardless of the
+/// current transform), or an empty string if there's none or the context isn't in retained mode.
+#[no_mangle]
+pub extern "C" fn hitTest(cx: *mut Context2D, x: f32, y: f32) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).hit_test((x, y)).unwrap_or_default().to_string())
+  }
+}
+
+/// The device-space bounds of the retained elements with the given id as `[x, y, width, height]`,
+/// or an empty array if there are none.
+#[no_mangle]
+pub extern "C" fn getElementBounds(cx: *mut Context2D, id: *mut c_char) -> *mut JsF32Array {
+  unsafe {
+    let id = char_to_string(id);
+    let rect = match (*cx).display_list.as_ref().and_then(|list| list.bounds_of(&id)) {
+      Some(r) => vec![r.left, r.top, r.width(), r.height()],
+      None => vec![]
+    };
+    Box::into_raw(Box::new(rect))
+  }
+}
+
 fn _is_in(cx: *mut Context2D, mut path: *mut Path2D, x: f32, y:f32, rule: u32, ink:PaintStyle) -> u32 {
   unsafe {
     let rule_type = if rule == 0 {  FillType::Winding } else { FillType::EvenOdd };
@@ -680,14 +793,14 @@ fn _layout_rects(width:f32, height:f32, nums:&[f32]) -> Option<(Rect, Rect)> {
   Some((src, dst))
 }
 
-fn _drawImage(cx: *mut Context2D, image: Option<SkImage>, arr: *mut JsF32Array)  {
+fn _drawImage(cx: *mut Context2D, image: Option<SkImage>, arr: *mut JsF32Array) -> Result<()> {
   unsafe {
     let dims = image.as_ref().map(|img|
       (img.width(), img.height())
     );
     let (width, height) = match dims{
       Some((w,h)) => (w as f32, h as f32),
-      None => panic!("Cannot draw incomplete image (has it finished loading?)")
+      None => return Err(Error::new(ErrorCode::InvalidState, "Cannot draw incomplete image (has it finished loading?)"))
     };
     let nums = (*arr).as_slice();
     match _layout_rects(width, height, &nums){
@@ -695,8 +808,22 @@ fn _drawImage(cx: *mut Context2D, image: Option<SkImage>, arr: *mut JsF32Array)
         // shrink src to lie within the image bounds and adjust dst proportionately
         let (src, dst) = fit_bounds(width, height, src, dst);
         (*cx).draw_image(&image, &src, &dst);
+        Ok(())
       },
-      None => panic!("Expected 2, 4, or 8 coordinates (got {})", nums.len())
+      None => Err(Error::new(ErrorCode::Type, format!("Expected 2, 4, or 8 coordinates (got {})", nums.len())))
+    }
+  }
+}
+
+fn _drawPicture(cx: *mut Context2D, pict: &Option<Picture>, size: Size, arr: *mut JsF32Array) -> Result<()> {
+  unsafe {
+    let nums = (*arr).as_slice();
+    match _layout_rects(size.width, size.height, &nums){
+      Some((src, dst)) => {
+        (*cx).draw_picture(pict, &src, &dst);
+        Ok(())
+      },
+      None => Err(Error::new(ErrorCode::Type, format!("Expected 2, 4, or 8 coordinates (got {})", nums.len())))
     }
   }
 }
@@ -704,64 +831,79 @@ fn _drawImage(cx: *mut Context2D, image: Option<SkImage>, arr: *mut JsF32Array)
 #[no_mangle]
 pub extern "C" fn get_image(cx: *mut Context2D) -> *mut Image {
   unsafe {
-    Box::into_raw(Box::new(Image{ image: (*cx).get_image(None)}))
+    Box::into_raw(Box::new(Image::new((*cx).get_image(None))))
   }
 }
 
 #[no_mangle]
-pub extern "C" fn drawImage(cx: *mut Context2D, image: *mut Image, arr: *mut JsF32Array) {
+pub extern "C" fn drawImage(cx: *mut Context2D, image: *mut Image, arr: *mut JsF32Array) -> u32 {
   unsafe {
-    _drawImage(cx, (*image).image.clone(), arr)
+    match &(*image).picture {
+      Some(_) => status(_drawPicture(cx, &(*image).picture, (*image).size(), arr)),
+      None => status(_drawImage(cx, (*image).image.clone(), arr))
+    }
   }
 }
 
 #[no_mangle]
-pub extern "C" fn drawImageFromContext(cx: *mut Context2D, context: *mut Context2D, arr: *mut JsF32Array) {
+pub extern "C" fn drawImageFromContext(cx: *mut Context2D, context: *mut Context2D, arr: *mut JsF32Array) -> u32 {
   unsafe {
-    _drawImage(cx, (*context).get_image(None), arr)
+    status(_drawImage(cx, (*context).get_image(None), arr))
   }
 }
 
 #[no_mangle]
-pub extern "C" fn drawImageFromBuffer(cx: *mut Context2D, buffer: *mut JsBuffer, arr: *mut JsF32Array) {
+pub extern "C" fn drawImageFromBuffer(cx: *mut Context2D, buffer: *mut JsBuffer, arr: *mut JsF32Array) -> u32 {
   unsafe {
     let data = Data::new_copy((*buffer).as_slice());
-    let image = SkImage::from_encoded(data);
-    _drawImage(cx, image, arr)
+    match SkImage::from_encoded(data) {
+      Some(image) => status(_drawImage(cx, Some(image), arr)),
+      None => status(Err(Error::new(ErrorCode::Encoding, "Could not decode image data")))
+    }
   }
 }
 
 #[no_mangle]
-pub extern "C" fn drawCanvas(cx: *mut Context2D, ctx: *mut Context2D, arr: *mut JsF32Array) {
+pub extern "C" fn drawCanvas(cx: *mut Context2D, ctx: *mut Context2D, arr: *mut JsF32Array) -> u32 {
   unsafe {
 
-    let (width, height) = {
-      let bounds = (*ctx).bounds;
-      (bounds.width(), bounds.height())
-    };
-    let nums = (*arr).as_slice();
-    match _layout_rects(width, height, &nums){
-      Some((src, dst)) => {
-        let pict = (*ctx).get_picture(None);
-        (*cx).draw_picture(&pict, &src, &dst);
-      },
-      None => panic!("Expected 2, 4, or 8 coordinates (got {})", nums.len())
-    }
+    let size = (*ctx).bounds.size();
+    status(_drawPicture(cx, &(*ctx).get_picture(None), size, arr))
   }
 }
 
+/// Read a block of pixels into a new `ImageData`. `color_space` and `pixel_format` may be empty
+/// to use the context's own color space and storage format.
 #[no_mangle]
-pub extern "C" fn getImageData(cx: *mut Context2D, x: i32, y: i32, width: i32, height: i32) -> *mut JsBuffer {
+pub extern "C" fn getImageData(cx: *mut Context2D, x: i32, y: i32, width: i32, height: i32, color_space: *mut c_char, pixel_format: *mut c_char) -> *mut ImageData {
   unsafe {
-    let mut buffer: Box<Vec<u8>> = Box::new(Vec::with_capacity(4 * (width * height) as usize));
-    (*cx).get_pixels(buffer.as_mut().as_mut_slice(), (x, y), (width, height));
+    let name = char_to_string(color_space);
+    let space = match name.as_str() {
+      "" => (*cx).color_space,
+      _ => match to_supported_color_space(&name) {
+        Ok(space) => space,
+        Err(err) => return fail(err)
+      }
+    };
+    let name = char_to_string(pixel_format);
+    let format = match name.as_str() {
+      "" => (*cx).pixel_format,
+      _ => match to_pixel_format(&name) {
+        Some(format) => format,
+        None => return fail(Error::new(ErrorCode::Type, format!("Unknown pixel format: {}", name)))
+      }
+    };
+    let mut buffer: Box<Vec<u8>> = Box::new(vec![0; format.bytes_per_pixel() * (width * height) as usize]);
+    (*cx).get_pixels(buffer.as_mut().as_mut_slice(), (x, y), (width, height), space, format);
 
-    Box::into_raw(buffer)
+    Box::into_raw(Box::new(ImageData{
+      data: Box::into_raw(buffer), width: width as f32, height: height as f32, color_space: space, pixel_format: format
+    }))
   }
 }
 
 #[no_mangle]
-pub extern "C" fn putImageData(cx: *mut Context2D, image_data_ptr: *mut ImageData, arr: *mut JsF32Array) {
+pub extern "C" fn putImageData(cx: *mut Context2D, image_data_ptr: *mut ImageData, arr: *mut JsF32Array) -> u32 {
   // determine geometry
   unsafe {
     let width = (*image_data_ptr).width;
@@ -770,7 +912,7 @@ pub extern "C" fn putImageData(cx: *mut Context2D, image_data_ptr: *mut ImageDat
     let y = js_f32_array_get(arr, 1);
 
     if js_f32_array_len(arr) > 2 && js_f32_array_len(arr) != 6 {
-      panic!("expected either 2 or 6 numbers")
+      return status(Err(Error::new(ErrorCode::Type, "expected either 2 or 6 numbers")))
     }
 
     let (mut src, mut dst) = match (*arr).as_mut_slice(){
@@ -784,9 +926,9 @@ pub extern "C" fn putImageData(cx: *mut Context2D, image_data_ptr: *mut ImageDat
         Rect::from_xywh(x, y, width, height)
     )};
 
-    let info = Image::info(width, height);
+    let info = Image::info(width, height, (*image_data_ptr).color_space, (*image_data_ptr).pixel_format);
     (*cx).blit_pixels((*(*image_data_ptr).data).as_slice(), &info, &src, &dst);
-   
+    status(Ok(()))
   }
 }
 
@@ -832,20 +974,14 @@ pub extern "C" fn set_imageSmoothingQuality(cx: *mut Context2D, quality: *mut c_
 //
 // Typography
 //
-// 实现富文本绘制方法
+/// Draw a `RichText` paragraph with its top-left corner at `arr` [x, y], returning its metrics
+/// (see `rich_text_get_metrics`).
 #[no_mangle]
-pub unsafe extern "C" fn drawRichText(cx: *mut Context2D, text: *mut c_char, arr: *mut JsF32Array) {
-  let mut style = ParagraphStyle::new();
-  style.set_ellipsis("...");
-  style.set_max_lines(2);
-  let mut builder = textlayout::ParagraphBuilder::new(&style, &FONT_LIBRARY.lock().unwrap().collection);
-  builder.push_style(&(*cx).state.char_style);
-  builder.add_text(char_to_string(text));
-  let mut paragraph = builder.build();
-  paragraph.layout(100.0);
-  (*cx).with_canvas(|canvas| {
-    paragraph.paint(canvas, (100, 100));
-  });
+pub unsafe extern "C" fn drawRichText(cx: *mut Context2D, text: *mut RichText, arr: *mut JsF32Array) -> *mut JsF32Array {
+  let x = js_f32_array_get(arr, 0);
+  let y = js_f32_array_get(arr, 1);
+  (*cx).draw_rich_text(&mut *text, x, y);
+  Box::into_raw(Box::new((*text).metrics()))
 }
 
 #[no_mangle]
@@ -955,6 +1091,90 @@ pub extern "C" fn set_textBaseline(cx: *mut Context2D, text_baseline: *mut c_cha
   }
 }
 
+#[no_mangle]
+pub extern "C" fn get_letterSpacing(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).state.letter_spacing.css.clone())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_letterSpacing(cx: *mut Context2D, spacing: *mut c_char) {
+  unsafe {
+    (*cx).set_letter_spacing(&char_to_string(spacing));
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_wordSpacing(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).state.word_spacing.css.clone())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_wordSpacing(cx: *mut Context2D, spacing: *mut c_char) {
+  unsafe {
+    (*cx).set_word_spacing(&char_to_string(spacing));
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_fontKerning(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).state.font_kerning.clone())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_fontKerning(cx: *mut Context2D, kerning: *mut c_char) {
+  unsafe {
+    (*cx).set_font_kerning(&char_to_string(kerning));
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_fontStretch(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).font_stretch())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_fontStretch(cx: *mut Context2D, stretch: *mut c_char) {
+  unsafe {
+    (*cx).set_font_stretch(&char_to_string(stretch));
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_fontVariantCaps(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).state.font_variant_caps.clone())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_fontVariantCaps(cx: *mut Context2D, caps: *mut c_char) {
+  unsafe {
+    (*cx).set_font_variant_caps(&char_to_string(caps));
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_textRendering(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).state.text_rendering.clone())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_textRendering(cx: *mut Context2D, rendering: *mut c_char) {
+  unsafe {
+    (*cx).set_text_rendering(&char_to_string(rendering));
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn get_direction(cx: *mut Context2D) -> *mut c_char {
   unsafe {
@@ -986,6 +1206,35 @@ pub extern "C" fn set_direction(cx: *mut Context2D, direction: *mut c_char) {
 // Effects
 //
 
+// -- retained mode -----------------------------------------------------------------
+#[no_mangle]
+pub extern "C" fn get_retained(cx: *mut Context2D) -> u32 {
+  unsafe {
+    (*cx).display_list.is_some() as u32
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_retained(cx: *mut Context2D, retained: u32) {
+  unsafe {
+    (*cx).set_retained(retained == 1);
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn get_elementId(cx: *mut Context2D) -> *mut c_char {
+  unsafe {
+    string_to_char((*cx).element_id().to_string())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn set_elementId(cx: *mut Context2D, id: *mut c_char) {
+  unsafe {
+    (*cx).set_element_id(&char_to_string(id));
+  }
+}
+
 // -- compositing properties --------------------------------------------------------
 #[no_mangle]
 pub extern "C" fn get_globalAlpha(cx: *mut Context2D) -> f32 {
@@ -1101,4 +1350,44 @@ pub extern "C" fn set_shadowOffsetY(cx: *mut Context2D, offet_y: f32) {
   unsafe {
     (*cx).state.shadow_offset.y = offet_y;
   }
-}
\ No newline at end of file
+}
+
+//
+// Document Annotations
+//
+
+// only PDF output keeps these; other formats ignore them
+fn annotation_data(text: *mut c_char) -> Data {
+  let text = CString::new(char_to_string(text)).unwrap_or_default();
+  Data::new_copy(text.as_bytes_with_nul())
+}
+
+#[no_mangle]
+pub extern "C" fn annotateLinkUrl(cx: *mut Context2D, x: f32, y: f32, width: f32, height: f32, url: *mut c_char) {
+  let data = annotation_data(url);
+  unsafe {
+    (*cx).with_canvas(|canvas| {
+      canvas.annotate_rect_with_url(Rect::from_xywh(x, y, width, height), &data);
+    });
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn annotateLinkToDestination(cx: *mut Context2D, x: f32, y: f32, width: f32, height: f32, name: *mut c_char) {
+  let data = annotation_data(name);
+  unsafe {
+    (*cx).with_canvas(|canvas| {
+      canvas.annotate_link_to_destination(Rect::from_xywh(x, y, width, height), &data);
+    });
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn annotateNamedDestination(cx: *mut Context2D, x: f32, y: f32, name: *mut c_char) {
+  let data = annotation_data(name);
+  unsafe {
+    (*cx).with_canvas(|canvas| {
+      canvas.annotate_named_destination((x, y), &data);
+    });
+  }
+}
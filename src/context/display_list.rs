@@ -0,0 +1,92 @@
+use skia_safe::{Matrix, Paint, PaintStyle, Path, Point, Rect};
+
+/// A tagged drawing command, kept with enough geometry to answer hit tests after the fact.
+pub struct Element {
+  pub id: String,
+  path: Path,           // in the local coordinates it was drawn with
+  matrix: Matrix,       // the transform in effect at the time
+  paint: Paint,         // for its style and stroke parameters
+  clip: Option<Path>,   // in device space, like CanvasState::clip
+}
+
+impl Element {
+  pub fn new(id: &str, path: Path, matrix: Matrix, paint: &Paint, clip: &Option<Path>) -> Self {
+    Element{ id: id.to_string(), path, matrix, paint: paint.clone(), clip: clip.clone() }
+  }
+
+  /// Whether a device-space point falls on the visible part of the element.
+  pub fn contains(&self, point: Point) -> bool {
+    if let Some(clip) = &self.clip {
+      if !clip.contains(point) {
+        return false
+      }
+    }
+    match self.matrix.invert() {
+      Some(inverse) => path_contains(&self.path, inverse.map_point(point), &self.paint),
+      None => false // a degenerate transform draws nothing
+    }
+  }
+
+  pub fn bounds(&self) -> Rect {
+    let bounds = self.matrix.map_rect(self.path.bounds()).0;
+    match &self.clip {
+      Some(clip) => Rect::intersect(&bounds, clip.bounds()).unwrap_or_default(),
+      None => bounds
+    }
+  }
+}
+
+/// Hit-test a point given in the path's own coordinates, tracing its outline first if `paint`
+/// strokes it.
+pub fn path_contains(path: &Path, point: Point, paint: &Paint) -> bool {
+  match paint.style() {
+    PaintStyle::Stroke => {
+      let precision = 0.3; // this is what Chrome uses to compute this
+      match paint.get_fill_path(path, None, Some(precision)){
+        Some(traced_path) => traced_path.contains(point),
+        None => path.contains(point)
+      }
+    },
+    _ => path.contains(point)
+  }
+}
+
+/// The tagged elements drawn since the context was last cleared, in painting order.
+#[derive(Default)]
+pub struct DisplayList {
+  elements: Vec<Element>,
+}
+
+impl DisplayList {
+  pub fn push(&mut self, element: Element) {
+    self.elements.push(element);
+  }
+
+  pub fn clear(&mut self) {
+    self.elements.clear();
+  }
+
+  pub fn len(&self) -> usize {
+    self.elements.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.elements.is_empty()
+  }
+
+  /// The id of the topmost element under a device-space point.
+  pub fn hit_test(&self, point: impl Into<Point>) -> Option<&str> {
+    let point = point.into();
+    self.elements.iter().rev()
+      .find(|element| element.contains(point))
+      .map(|element| element.id.as_str())
+  }
+
+  /// The union of the device-space bounds of every element with the given id.
+  pub fn bounds_of(&self, id: &str) -> Option<Rect> {
+    self.elements.iter()
+      .filter(|element| element.id == id)
+      .map(|element| element.bounds())
+      .reduce(|a, b| Rect::join2(a, b))
+  }
+}
@@ -1,6 +1,9 @@
 use std::{collections::HashMap, ffi::c_char, os::raw::c_void, ptr};
 
 use super::char_to_string;
+use crate::error::{fail, Error, ErrorCode};
+use crate::utils::{from_color_space_name, from_pixel_format, string_to_char, to_supported_color_space, to_pixel_format,
+                   PixelFormat, PredefinedColorSpace};
 
 pub type JsF32Array = Vec<f32>;
 
@@ -81,15 +84,50 @@ pub extern "C" fn js_buffer_push(arr: *mut JsBuffer, value: u8) {
   }
 }
 
+#[no_mangle]
+pub extern "C" fn drop_js_buffer(arr: *mut JsBuffer) {
+  if !arr.is_null() {
+    unsafe { drop(Box::from_raw(arr)) }
+  }
+}
+
 pub struct ImageData {
   pub data: *mut JsBuffer,
   pub width: f32,
-  pub height: f32
+  pub height: f32,
+  pub color_space: PredefinedColorSpace,
+  pub pixel_format: PixelFormat,
 }
 
+/// `color_space` is "srgb" or "display-p3" and `pixel_format` one of "rgba-unorm8",
+/// "rgba-float16" or "rgba-float32" (float data is little-endian); either may be empty for the
+/// default. Returns null (see `get_last_error`) for other values or a buffer of the wrong length.
 #[no_mangle]
-pub extern "C" fn new_image_data(data: *mut JsBuffer, width: f32, height: f32) -> *mut ImageData {
-  Box::into_raw(Box::new(ImageData{data: data, width: width, height}))
+pub extern "C" fn new_image_data(data: *mut JsBuffer, width: f32, height: f32, color_space: *mut c_char, pixel_format: *mut c_char) -> *mut ImageData {
+  let name = char_to_string(color_space);
+  let color_space = match name.as_str() {
+    "" => PredefinedColorSpace::Srgb,
+    _ => match to_supported_color_space(&name) {
+      Ok(space) => space,
+      Err(err) => return fail(err)
+    }
+  };
+  let name = char_to_string(pixel_format);
+  let pixel_format = match name.as_str() {
+    "" => PixelFormat::Unorm8,
+    _ => match to_pixel_format(&name) {
+      Some(format) => format,
+      None => return fail(Error::new(ErrorCode::Type, format!("Unknown pixel format: {}", name)))
+    }
+  };
+  let expected = pixel_format.bytes_per_pixel() * width as usize * height as usize;
+  let len = unsafe { (*data).len() };
+  if len != expected {
+    return fail(Error::new(ErrorCode::InvalidState, format!(
+      "A {}×{} {} buffer needs {} bytes (got {})", width, height, from_pixel_format(pixel_format), expected, len
+    )))
+  }
+  Box::into_raw(Box::new(ImageData{data: data, width: width, height, color_space, pixel_format}))
 }
 
 #[no_mangle]
@@ -106,6 +144,35 @@ pub extern "C" fn image_data_get_width(image_data: *mut ImageData) -> f32 {
   }
 }
 
+#[no_mangle]
+pub extern "C" fn image_data_get_height(image_data: *mut ImageData) -> f32 {
+  unsafe {
+    (*image_data).height
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn image_data_get_pixel_format(image_data: *mut ImageData) -> *mut c_char {
+  unsafe {
+    string_to_char(from_pixel_format((*image_data).pixel_format))
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn image_data_get_color_space(image_data: *mut ImageData) -> *mut c_char {
+  unsafe {
+    string_to_char(from_color_space_name((*image_data).color_space))
+  }
+}
+
+// the pixel buffer is a separate handle and has to be dropped on its own
+#[no_mangle]
+pub extern "C" fn drop_image_data(image_data: *mut ImageData) {
+  if !image_data.is_null() {
+    unsafe { drop(Box::from_raw(image_data)) }
+  }
+}
+
 
 pub type JsAnyArray = Vec<*mut c_void>;
 
@@ -143,6 +210,14 @@ pub extern "C" fn js_any_array_push(arr: *mut JsAnyArray, value: *mut c_void) {
   }
 }
 
+// only frees the array itself, the pointers it holds are owned by their own handles
+#[no_mangle]
+pub extern "C" fn drop_js_any_array(arr: *mut JsAnyArray) {
+  if !arr.is_null() {
+    unsafe { drop(Box::from_raw(arr)) }
+  }
+}
+
 pub type JsStrMap = HashMap<String, String>;
 
 #[no_mangle]
@@ -13,25 +13,34 @@ use skia_safe::wrapper::PointerWrapper;
 use skia_safe::{Canvas as SkCanvas, Surface, Paint, Path, PathOp, Image, ImageInfo, Contains,
                 Matrix, Rect, Point, IPoint, Size, ISize, Color, Color4f, ColorType, Data,
                 PaintStyle, BlendMode, AlphaType, ClipOp, PictureRecorder, Picture, Drawable,
-                image::CachingHint, image_filters, dash_path_effect, path_1d_path_effect};
+                image::CachingHint, image_filters, dash_path_effect, path_1d_path_effect,
+                canvas::SaveLayerRec};
 use skia_safe::textlayout::{ParagraphStyle, TextStyle};
+use skia_safe::font_style::FontStyle;
 use skia_safe::canvas::SrcRectConstraint::Strict;
 use skia_safe::path::FillType;
+use skia_safe::paint::Join;
+use skia_safe::{IRect, RoundOut};
+use std::f32::consts::SQRT_2;
 
 pub mod recorder;
+pub mod display_list;
 pub mod api;
 pub mod jstypes;
 
 use crate::canvas::Canvas;
+use crate::error::{self, Error, ErrorCode};
 use crate::FONT_LIBRARY;
 use crate::utils::*;
 use crate::typography::*;
+use crate::rich_text::RichText;
 use crate::filter::{Filter, ImageFilter, FilterQuality};
 use crate::gradient::CanvasGradient;
 use crate::pattern::CanvasPattern;
 use crate::texture::CanvasTexture;
 
 use self::recorder::Recorder;
+use self::display_list::{path_contains, DisplayList, Element};
 // use page::{PageRecorder, Page};
 
 const BLACK:Color = Color::BLACK;
@@ -39,12 +48,16 @@ const TRANSPARENT:Color = Color::TRANSPARENT;
 
 pub struct Context2D{
   pub bounds: Rect,
+  pub color_space: PredefinedColorSpace,
+  pub pixel_format: PixelFormat,
+  damage: Option<Rect>, // device-space area drawn since the last present
+  pub display_list: Option<DisplayList>, // only kept in retained mode
+  layers: Vec<usize>, // the stack depth at which each open layer began
   recorder: RefCell<Recorder>,
   state: CanvasState,
   stack: Vec<CanvasState>,
   path: Path,
   pub canvas: Box<Canvas>,
-  pub picture: Option<Picture>
 }
 
 #[derive(Clone)]
@@ -76,8 +89,13 @@ pub struct CanvasState{
   char_style: TextStyle,
   graf_style: ParagraphStyle,
   text_baseline: Baseline,
-  text_tracking: i32,
+  letter_spacing: Spacing,
+  word_spacing: Spacing,
+  font_kerning: String,
+  font_variant_caps: String,
+  text_rendering: String,
   text_wrap: bool,
+  element_id: String,
 }
 
 impl Default for CanvasState {
@@ -99,8 +117,8 @@ impl Default for CanvasState {
       matrix: Matrix::new_identity(),
 
       paint,
-      stroke_style: Dye::Color(BLACK),
-      fill_style: Dye::Color(BLACK),
+      stroke_style: Dye::Color(BLACK.into()),
+      fill_style: Dye::Color(BLACK.into()),
       stroke_width: 1.0,
       line_dash_offset: 0.0,
       line_dash_list: vec![],
@@ -122,16 +140,42 @@ impl Default for CanvasState {
       char_style,
       graf_style,
       text_baseline: Baseline::Alphabetic,
-      text_tracking: 0,
-      text_wrap: false
+      letter_spacing: Spacing::default(),
+      word_spacing: Spacing::default(),
+      font_kerning: "auto".to_string(),
+      font_variant_caps: "normal".to_string(),
+      text_rendering: "auto".to_string(),
+      text_wrap: false,
+      element_id: String::new(),
     }
   }
 }
 
 impl CanvasState{
   pub fn typography(&self) -> (TextStyle, ParagraphStyle, Baseline, bool) {
+    // the font setter replaces the style's features (and may change its size), so these are
+    // layered on at layout time
+    let mut char_style = self.char_style.clone();
+    let size = char_style.font_size();
+    char_style.set_letter_spacing(self.letter_spacing.to_px(size));
+    char_style.set_word_spacing(self.word_spacing.to_px(size));
+    for feat in to_caps_features(&self.font_variant_caps).unwrap_or_default(){
+      char_style.add_font_feature(feat, 1);
+    }
+    let kern = match (self.font_kerning.as_str(), self.text_rendering.as_str()) {
+      ("normal", _) | ("auto", "optimizeLegibility") => Some(1),
+      ("none", _) | ("auto", "optimizeSpeed") => Some(0),
+      _ => None
+    };
+    if let Some(kern) = kern{
+      char_style.add_font_feature("kern", kern);
+    }
+    if self.text_rendering == "optimizeSpeed"{
+      char_style.add_font_feature("liga", 0);
+    }
+
     (
-      self.char_style.clone(),
+      char_style,
       self.graf_style.clone(),
       self.text_baseline,
       self.text_wrap
@@ -159,11 +203,15 @@ impl Context2D{
     Context2D{
       canvas,
       bounds,
+      color_space: PredefinedColorSpace::Srgb,
+      pixel_format: PixelFormat::Unorm8,
+      damage: None,
+      display_list: None,
+      layers: vec![],
       recorder: RefCell::new(Recorder::new(bounds)),
       path: Path::new(),
       stack: vec![],
       state: CanvasState::default(),
-      picture: None
     }
   }
 
@@ -277,9 +325,14 @@ impl Context2D{
     self.bounds = Rect::from_size(dims);
     self.path = Path::default();
     self.stack = vec![];
+    self.layers = vec![];
     self.state = CanvasState::default();
 
     // erase any existing content
+    self.damage = None;
+    if let Some(list) = &mut self.display_list {
+      list.clear();
+    }
     self.with_recorder(|mut recorder| {
       recorder.set_bounds(self.bounds);
     });
@@ -299,6 +352,11 @@ impl Context2D{
   }
 
   pub fn pop(&mut self){
+    // restore() can't close a layer, only endLayer() can
+    if self.layers.last().map_or(false, |depth| self.stack.len() <= *depth){
+      return
+    }
+
     // don't do anything if we're already back at the initial stack frame
     if let Some(old_state) = self.stack.pop(){
       self.state = old_state;
@@ -309,6 +367,138 @@ impl Context2D{
       });
     }
   }
+  /// Save the state and divert drawing into a layer that is composited as a unit by `end_layer`,
+  /// using the given opacity, blend mode and filter (the current ones where omitted). Inside the
+  /// layer those properties start out at their defaults.
+  pub fn begin_layer(&mut self, alpha:Option<f32>, blend_mode:Option<BlendMode>, filter:Option<Filter>){
+    let mut paint = Paint::default();
+    let mut filter = filter.unwrap_or_else(|| self.state.filter.clone());
+    filter.mix_into(&mut paint, Matrix::new_identity(), true); // the layer is composited in device space
+    paint.set_alpha_f(alpha.unwrap_or(self.state.global_alpha).clamp(0.0, 1.0));
+    paint.set_blend_mode(blend_mode.unwrap_or(self.state.global_composite_operation));
+
+    self.push();
+    self.layers.push(self.stack.len());
+    self.state.global_alpha = 1.0;
+    self.state.global_composite_operation = BlendMode::SrcOver;
+    self.state.filter = Filter::default();
+    self.with_recorder(|mut recorder| recorder.begin_layer(paint.clone()));
+
+    // the composite can reach beyond what's drawn in the layer
+    if paint.image_filter().is_some() || paint.as_blend_mode() != Some(BlendMode::SrcOver){
+      self.damage = Some(self.bounds);
+    }
+  }
+
+  /// Composite the innermost layer and restore the state from before it began, discarding any
+  /// unmatched saves made within it.
+  pub fn end_layer(&mut self) -> error::Result<()>{
+    let depth = self.layers.pop().ok_or_else(||
+      Error::new(ErrorCode::InvalidState, "endLayer() called without a matching beginLayer()")
+    )?;
+    self.stack.truncate(depth);
+    self.with_recorder(|mut recorder| { recorder.end_layer(); });
+    self.pop();
+    Ok(())
+  }
+
+  pub fn layer_count(&self) -> usize {
+    self.layers.len()
+  }
+
+
+  /// Start (or stop) keeping a display list of the elements drawn while `element_id` is set, so
+  /// they can be found again with `hit_test`.
+  pub fn set_retained(&mut self, retained: bool) {
+    self.display_list = match retained {
+      true => self.display_list.take().or_else(|| Some(DisplayList::default())),
+      false => None
+    };
+  }
+
+  pub fn element_id(&self) -> &str {
+    &self.state.element_id
+  }
+
+  pub fn set_element_id(&mut self, id: &str) {
+    self.state.element_id = id.to_string();
+  }
+
+  /// The id of the topmost retained element under a point in device coordinates.
+  pub fn hit_test(&self, point: impl Into<Point>) -> Option<&str> {
+    self.display_list.as_ref()?.hit_test(point)
+  }
+
+  // add an element to the display list if we're in retained mode and an id has been set
+  fn retain(&mut self, path: Path, paint: &Paint) {
+    if self.state.element_id.is_empty() {
+      return
+    }
+    if let Some(list) = &mut self.display_list {
+      list.push(Element::new(&self.state.element_id, path, self.state.matrix, paint, &self.state.clip));
+    }
+  }
+
+  /// Grow the damaged area by the device-space footprint of drawing `local` with `paint`,
+  /// including its stroke, filter and shadow, as limited by the clip.
+  pub fn add_damage(&mut self, local:&Rect, paint:&Paint){
+    let footprint = |paint:&Paint, matrix:&Matrix| -> Option<Rect> {
+      let mut rect = *local;
+      if paint.style() != PaintStyle::Fill {
+        let miter = match paint.stroke_join() {
+          Join::Miter => paint.stroke_miter().max(SQRT_2),
+          _ => SQRT_2 // covers square caps
+        };
+        rect = rect.with_outset((paint.stroke_width().max(1.0) / 2.0 * miter, paint.stroke_width().max(1.0) / 2.0 * miter));
+      }
+      if let Some(filter) = paint.image_filter() {
+        match filter.can_compute_fast_bounds() {
+          true => rect = filter.compute_fast_bounds(rect),
+          false => return None
+        }
+      }
+      // leave room for antialiasing
+      Some(matrix.map_rect(rect).0.with_outset((1.0, 1.0)))
+    };
+
+    // these blend modes affect the whole canvas, and markers can extend past the stroke
+    let full_canvas = self.state.line_dash_marker.is_some() || matches!(self.state.global_composite_operation,
+      BlendMode::SrcIn | BlendMode::SrcOut | BlendMode::DstIn | BlendMode::DstOut | BlendMode::DstATop | BlendMode::Src
+    );
+    let mut area = match full_canvas {
+      true => None,
+      false => footprint(paint, &self.state.matrix)
+    };
+    if let (Some(rect), Some(shadow_paint)) = (area, self.paint_for_shadow(paint)){
+      let shadow_matrix = Matrix::concat(&Matrix::translate(self.state.shadow_offset), &self.state.matrix);
+      area = footprint(&shadow_paint, &shadow_matrix).map(|shadow| Rect::join2(rect, shadow));
+    }
+
+    let mut area = area.unwrap_or(self.bounds);
+    if let Some(clip) = &self.state.clip{
+      if !area.intersect(clip.bounds()){
+        return
+      }
+    }
+    if area.intersect(self.bounds){
+      self.damage = Some(match self.damage{
+        Some(damage) => Rect::join2(damage, area),
+        None => area
+      });
+    }
+  }
+
+  /// The damaged area in whole device pixels, or None if nothing has been drawn since the last
+  /// call to `take_damage`.
+  pub fn damage(&self) -> Option<IRect>{
+    self.damage.map(|rect| -> IRect { rect.round_out() })
+  }
+
+  pub fn take_damage(&mut self) -> Option<IRect>{
+    let damage = self.damage();
+    self.damage = None;
+    damage
+  }
 
   pub fn draw_path(&mut self, path:Option<Path>, style:PaintStyle, rule:Option<FillType>){
     let mut path = path.unwrap_or_else(|| {
@@ -319,6 +509,8 @@ impl Context2D{
     path.set_fill_type(rule.unwrap_or(FillType::Winding));
 
     let paint = self.paint_for_drawing(style);
+    self.add_damage(path.bounds(), &paint);
+    self.retain(path.clone(), &paint);
     let texture = self.state.texture(style);
     self.render_to_canvas(&paint, |canvas, paint| {
       if let Some(tile) = texture{
@@ -365,45 +557,50 @@ impl Context2D{
     let prev_rule = path.fill_type();
     path.set_fill_type(rule);
 
-    let is_in = match style{
-      PaintStyle::Stroke => {
-        let paint = self.paint_for_drawing(PaintStyle::Stroke);
-        let precision = 0.3; // this is what Chrome uses to compute this
-        match paint.get_fill_path(path, None, Some(precision)){
-          Some(traced_path) => traced_path.contains(point),
-          None => path.contains(point)
-        }
-      },
-      _ => path.contains(point)
-    };
+    let paint = self.paint_for_drawing(style);
+    let is_in = path_contains(path, point, &paint);
 
     path.set_fill_type(prev_rule);
     is_in
   }
 
   pub fn clear_rect(&mut self, rect:&Rect){
-    match self.state.matrix.map_rect(rect).0.contains(self.bounds){
+    // inside a layer only the layer's own contents can be erased
+    match self.layers.is_empty() && self.state.matrix.map_rect(rect).0.contains(self.bounds){
 
       // if rect fully encloses canvas, erase existing content (but preserve CTM, path, etc.)
-      true =>  self.with_recorder(|mut recorder|{
-        recorder.set_bounds(self.bounds);
-        recorder.set_matrix(self.state.matrix);
-        recorder.set_clip(&self.state.clip);
-      }),
+      true => {
+        if let Some(list) = &mut self.display_list {
+          list.clear();
+        }
+        self.damage = Some(self.bounds);
+        self.with_recorder(|mut recorder|{
+          recorder.set_bounds(self.bounds);
+          recorder.set_matrix(self.state.matrix);
+          recorder.set_clip(&self.state.clip);
+        })
+      },
 
       // otherwise, paint over the specified region but preserve overdrawn vectors
-      false => self.with_canvas(|canvas| {
+      false => {
         let mut paint = Paint::default();
         paint.set_anti_alias(true)
              .set_style(PaintStyle::Fill)
              .set_blend_mode(BlendMode::Clear);
-        canvas.draw_rect(&rect, &paint);
-      })
+        self.add_damage(rect, &paint);
+        self.with_canvas(|canvas| {
+          canvas.draw_rect(&rect, &paint);
+        })
+      }
     }
   }
 
   pub fn draw_picture(&mut self, picture:&Option<Picture>, src_rect:&Rect, dst_rect:&Rect){
     let paint = self.paint_for_image();
+    if picture.is_some(){
+      self.add_damage(dst_rect, &paint);
+      self.retain(Path::rect(dst_rect, None), &paint);
+    }
     let size = ISize::new(dst_rect.width() as i32, dst_rect.height() as i32);
     let mag = Point::new(dst_rect.width()/src_rect.width(), dst_rect.height()/src_rect.height());
     let mut matrix = Matrix::new_identity();
@@ -418,13 +615,21 @@ impl Context2D{
           (Some(BlendMode::SrcOver), 255, None) => None,
           _ => Some(paint)
         };
+        // keep parts of the picture outside of src_rect from spilling past dst_rect
+        canvas.save();
+        canvas.clip_rect(dst_rect, None, None);
         canvas.draw_picture(&picture, Some(&matrix), paint);
+        canvas.restore();
       });
     }
   }
 
   pub fn draw_image(&mut self, img: &Option<Image>, src_rect:&Rect, dst_rect:&Rect){
     let paint = self.paint_for_image();
+    if img.is_some(){
+      self.add_damage(dst_rect, &paint);
+      self.retain(Path::rect(dst_rect, None), &paint);
+    }
     if let Some(image) = &img {
       self.render_to_canvas(&paint, |canvas, paint| {
         let sampling = self.state.image_filter.sampling();
@@ -433,22 +638,43 @@ impl Context2D{
     }
   }
 
+  /// Choose the color space that snapshots, exports, and pixel access happen in. Drawing commands
+  /// are recorded independently of it, so contents are preserved.
+  pub fn set_color_space(&mut self, space: PredefinedColorSpace) {
+    self.color_space = space;
+    self.recorder.get_mut().color_space = space.to_color_space();
+  }
+
+  /// Choose how snapshots store their pixels. Float formats avoid banding in smooth gradients and
+  /// keep values outside of 0–1 for HDR content.
+  pub fn set_pixel_format(&mut self, format: PixelFormat) {
+    self.pixel_format = format;
+    self.recorder.get_mut().bit_depth = format.bit_depth();
+  }
+
+  /// Keep a raster backing store that drawing is flattened into at each read (see
+  /// `Recorder::set_raster_backed`), as hinted by the `willReadFrequently` context setting.
+  pub fn set_will_read_frequently(&mut self, will_read_frequently: bool) {
+    self.recorder.get_mut().set_raster_backed(will_read_frequently);
+  }
+
+  pub fn will_read_frequently(&self) -> bool {
+    self.recorder.borrow().is_raster_backed()
+  }
+
   pub fn get_image(&mut self, matte: Option<Color>) -> Option<Image> { 
     self.recorder.get_mut().get_image(matte)
   }
 
   pub fn get_picture(&mut self,  matte:Option<Color>) -> Option<Picture> {
-    if self.picture.is_none() {
-      let mut recorder = self.recorder.borrow_mut();
-      self.picture = recorder.get_picture(matte);
-    } 
-    self.picture.clone()
+    self.recorder.get_mut().get_picture(matte)
   }
 
-  pub fn get_pixels(&mut self, buffer: &mut [u8], origin: impl Into<IPoint>, size: impl Into<ISize>){
+  /// Read pixels as unpremultiplied RGBA laid out per `format`, converted into `space`.
+  pub fn get_pixels(&mut self, buffer: &mut [u8], origin: impl Into<IPoint>, size: impl Into<ISize>, space: PredefinedColorSpace, format: PixelFormat){
     let origin = origin.into();
     let size = size.into();
-    let info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Unpremul, None);
+    let info = ImageInfo::new(size, format.color_type(), AlphaType::Unpremul, Some(space.to_color_space()));
 
     if let Some(img) = self.get_image(None){
       img.read_pixels(&info, buffer, info.min_row_bytes(), origin, CachingHint::Allow);
@@ -460,6 +686,9 @@ impl Context2D{
     // without clips, transforms, alpha, blend, or shadows
     let data = Data::new_copy(buffer);
     if let Some(bitmap) = Image::from_raster_data(info, data, info.min_row_bytes()) {
+      if let Some(area) = Rect::intersect(dst_rect, &self.bounds){ // ignores the clip & matrix, like the blit
+        self.damage = Some(self.damage.map_or(area, |damage| Rect::join2(damage, area)));
+      }
       self.push(); // cache matrix & clip in self.state
       self.with_canvas(|canvas| {
         let paint = Paint::default();
@@ -478,6 +707,7 @@ impl Context2D{
     if let Some(new_style) = library.update_style(&self.state.char_style, &spec){
       self.state.font = spec.canonical;
       self.state.font_variant = spec.variant.to_string();
+      self.state.font_variant_caps = "normal".to_string();
       self.state.char_style = new_style;
     }
   }
@@ -489,10 +719,65 @@ impl Context2D{
     self.state.char_style = new_style;
   }
 
+  pub fn set_letter_spacing(&mut self, spacing:&str){
+    if let Some(spacing) = Spacing::parse(spacing){
+      self.state.letter_spacing = spacing;
+    }
+  }
+
+  pub fn set_word_spacing(&mut self, spacing:&str){
+    if let Some(spacing) = Spacing::parse(spacing){
+      self.state.word_spacing = spacing;
+    }
+  }
+
+  pub fn font_stretch(&self) -> String{
+    from_width(self.state.char_style.font_style().width())
+  }
+
+  pub fn set_font_stretch(&mut self, stretch:&str){
+    let width = to_width(stretch);
+    if from_width(width) == stretch{
+      let style = self.state.char_style.font_style();
+      self.state.char_style.set_font_style(FontStyle::new(style.weight(), width, style.slant()));
+    }
+  }
+
+  pub fn set_font_kerning(&mut self, kerning:&str){
+    if is_font_kerning(kerning){
+      self.state.font_kerning = kerning.to_string();
+    }
+  }
+
+  pub fn set_font_variant_caps(&mut self, caps:&str){
+    if to_caps_features(caps).is_some(){
+      self.state.font_variant_caps = caps.to_string();
+    }
+  }
+
+  pub fn set_text_rendering(&mut self, rendering:&str){
+    if is_text_rendering(rendering){
+      self.state.text_rendering = rendering.to_string();
+    }
+  }
+
 
   pub fn draw_text(&mut self, text: &str, x: f32, y: f32, width: Option<f32>, style:PaintStyle){
     let paint = self.paint_for_drawing(style);
     let mut typesetter = RefCell::new(Typesetter::new(&self.state, text, width));
+    if let Some(bounds) = typesetter.borrow_mut().bounds(){
+      self.add_damage(&bounds.with_offset((x, y)), &paint);
+    }
+    if self.display_list.is_some() && !self.state.element_id.is_empty() {
+      // text is hit by its line boxes rather than its glyph outlines
+      let mut boxes = Path::new();
+      for line in typesetter.borrow_mut().line_bounds() {
+        boxes.add_rect(line.with_offset((x, y)), None);
+      }
+      let mut fill = paint.clone();
+      fill.set_style(PaintStyle::Fill);
+      self.retain(boxes, &fill);
+    }
     self.render_to_canvas(&paint, |canvas, paint| {
       let point = Point::new(x, y);
       let (paragraph, offset) = typesetter.borrow_mut().layout(paint);
@@ -500,6 +785,43 @@ impl Context2D{
     });
   }
 
+  /// Paint a paragraph (and any images in its placeholders) with its top-left corner at (x, y).
+  /// Its runs bring their own fonts and colors, so only the context's alpha, compositing, filter
+  /// and shadow settings apply.
+  pub fn draw_rich_text(&mut self, text: &mut RichText, x: f32, y: f32){
+    let paint = self.paint_for_image();
+    if let Some(bounds) = text.bounds(){
+      self.add_damage(&bounds.with_offset((x, y)), &paint);
+    }
+    if self.display_list.is_some() && !self.state.element_id.is_empty() {
+      // like plain text, paragraphs are hit by their line boxes
+      let mut boxes = Path::new();
+      for line in text.line_bounds() {
+        boxes.add_rect(line.with_offset((x, y)), None);
+      }
+      self.retain(boxes, &paint);
+    }
+    let images = text.inline_images();
+    let paragraph = text.paragraph();
+    let sampling = self.state.image_filter.sampling();
+    self.render_to_canvas(&paint, |canvas, paint| {
+      // only composite through a layer when the paint would change the result
+      let layered = !matches!((paint.as_blend_mode(), paint.alpha(), paint.image_filter()),
+        (Some(BlendMode::SrcOver), 255, None)
+      );
+      if layered {
+        canvas.save_layer(&SaveLayerRec::default().paint(paint));
+      }
+      paragraph.paint(canvas, (x, y));
+      for (rect, image) in &images {
+        canvas.draw_image_rect_with_sampling_options(image, None, rect.with_offset((x, y)), sampling, &Paint::default());
+      }
+      if layered {
+        canvas.restore();
+      }
+    });
+  }
+
   pub fn measure_text(&mut self, text: &str, width:Option<f32>) -> Vec<Vec<f32>>{
     Typesetter::new(&self.state, text, width).metrics()
   }
@@ -572,7 +894,7 @@ impl Context2D{
 
 #[derive(Clone)]
 pub enum Dye{
-  Color(Color),
+  Color(Color4f), // extended sRGB, see css_to_color4f
   Gradient(CanvasGradient),
   Pattern(CanvasPattern),
   Texture(CanvasTexture)
@@ -582,7 +904,7 @@ impl Dye{
 
   pub fn value(&self) -> Result<String, ()> {
     match self{
-      Dye::Color(color) => color_to_css( color),
+      Dye::Color(color) => Ok(color4f_to_css(color)),
       _ => Ok("".to_owned()) // flag to the js context that it should use its cached pattern/gradient ref
     }
   }
@@ -590,9 +912,9 @@ impl Dye{
   pub fn mix_into(&self, paint: &mut Paint, alpha: f32, image_filter: ImageFilter){
     match self {
       Dye::Color(color) => {
-        let mut color:Color4f = (*color).into();
+        let mut color = *color;
         color.a *= alpha;
-        paint.set_color(color.to_color());
+        paint.set_color4f(color, None);
       },
       Dye::Gradient(gradient) =>{
         paint.set_shader(gradient.shader())
@@ -613,7 +935,7 @@ impl Dye{
 #[no_mangle]
 pub unsafe extern "C" fn new_dye_from_color(color: *mut c_char) -> *const Dye {
   let color_str = CStr::from_ptr(color).to_string_lossy().into_owned();
-  if let Some(c) = css_to_color(&color_str) {
+  if let Some(c) = css_to_color4f(&color_str) {
     Box::into_raw(Box::new(Dye::Color(c)))
   } else {
     null()
@@ -633,4 +955,11 @@ pub unsafe extern "C" fn new_dye_from_pattern(p: *mut CanvasPattern) -> *mut Dye
 #[no_mangle]
 pub unsafe extern "C" fn new_dye_from_texture(t: *mut CanvasTexture) -> *mut Dye {
   Box::into_raw(Box::new(Dye::Texture((*t).clone())))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_dye(dye: *mut Dye) {
+  if !dye.is_null() {
+    drop(Box::from_raw(dye));
+  }
 }
\ No newline at end of file
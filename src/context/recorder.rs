@@ -1,10 +1,25 @@
-use skia_safe::{Canvas as SkCanvas, ClipOp, Color, ColorSpace, Image as SkImage, Matrix, Path, Picture, PictureRecorder, Rect};
+use skia_safe::image::BitDepth;
+use skia_safe::{Canvas as SkCanvas, AlphaType, ClipOp, Color, ColorSpace, ColorType, Image as SkImage, ImageInfo,
+                Matrix, Paint, Path, Picture, PictureRecorder, Rect, Surface};
 
 pub struct Recorder {
   bounds: Rect,
   pub current: PictureRecorder,
   matrix: Matrix,
   clip: Option<Path>,
+  history: Option<Picture>, // everything drawn before `current` was last restarted
+  pub color_space: ColorSpace,
+  pub bit_depth: BitDepth,
+  raster_backed: bool,
+  raster: Option<Surface>,
+  open_layers: Vec<Layer>, // drawn into instead of `current` until they're ended
+}
+
+struct Layer {
+  recorder: PictureRecorder,
+  paint: Paint,
+  clip: Option<Path>, // the state to composite with once the layer ends
+  matrix: Matrix,
 }
 
 impl Recorder {
@@ -12,21 +27,69 @@ impl Recorder {
     let mut rec = PictureRecorder::new();
     rec.begin_recording(bounds, None);
     rec.recording_canvas().unwrap().save(); // start at depth 
-    Recorder { current: rec, bounds, matrix:Matrix::default(), clip:None }
+    Recorder { current: rec, bounds, matrix:Matrix::default(), clip:None, history: None, color_space: ColorSpace::new_srgb(), bit_depth: BitDepth::U8,
+               raster_backed: false, raster: None, open_layers: vec![] }
+  }
+
+  // the innermost open layer, or the main recording if there are none
+  fn canvas(&mut self) -> Option<&mut SkCanvas> {
+    match self.open_layers.last_mut() {
+      Some(layer) => layer.recorder.recording_canvas(),
+      None => self.current.recording_canvas()
+    }
   }
 
   pub fn append<F>(&mut self, f:F)
   where F:FnOnce(&mut SkCanvas)
   {
-    if let Some(canvas) = self.current.recording_canvas() {
+    if let Some(canvas) = self.canvas() {
       f(canvas);
     } else {
       panic!("no canvas");
     }
   }
 
+  /// Divert subsequent drawing into a layer that will be composited with `paint` once it's ended.
+  pub fn begin_layer(&mut self, paint:Paint){
+    let mut recorder = PictureRecorder::new();
+    recorder.begin_recording(self.bounds, None);
+    recorder.recording_canvas().unwrap().save(); // start at depth
+    self.open_layers.push(Layer{ recorder, paint, clip: self.clip.clone(), matrix: self.matrix });
+    self.restore();
+  }
+
+  /// Composite the innermost layer onto whatever it was begun on top of.
+  pub fn end_layer(&mut self) -> bool {
+    let mut layer = match self.open_layers.pop() {
+      Some(layer) => layer,
+      None => return false
+    };
+    // the parent may have been replaced by a fresh recording in the meantime (see merge_history
+    // and pending_layers)
+    self.clip = layer.clip;
+    self.matrix = layer.matrix;
+    self.restore();
+
+    if let (Some(pict), Some(canvas)) = (layer.recorder.finish_recording_as_picture(Some(&self.bounds)), self.canvas()) {
+      // the layer was recorded in device space
+      canvas.save();
+      canvas.reset_matrix();
+      canvas.draw_picture(&pict, None, Some(&layer.paint));
+      canvas.restore();
+    }
+    true
+  }
+
+  pub fn layer_count(&self) -> usize {
+    self.open_layers.len()
+  }
+
   pub fn set_bounds(&mut self, bounds:Rect) {
+    let (color_space, bit_depth, raster_backed) = (self.color_space.clone(), self.bit_depth, self.raster_backed);
     *self = Recorder::new(bounds);
+    self.color_space = color_space;
+    self.bit_depth = bit_depth;
+    self.raster_backed = raster_backed;
   }
 
   pub fn update_bounds(&mut self, bounds:Rect){
@@ -35,19 +98,23 @@ impl Recorder {
 
   pub fn set_matrix(&mut self, matrix:Matrix){
     self.matrix = matrix;
-    if let Some(canvas) = self.current.recording_canvas() {
+    if let Some(canvas) = self.canvas() {
       canvas.set_matrix(&matrix.into());
     }
   }
 
   pub fn restore(&mut self){
+    let (clip, matrix) = (self.clip.clone(), self.matrix);
+    if let Some(canvas) = self.canvas() {
+      reapply(canvas, &clip, &matrix);
+    }
+  }
+
+  // bring a new main recording up to date, even if layers are open (in which case it will be
+  // brought up to date again when the outermost one ends)
+  fn restore_current(&mut self){
     if let Some(canvas) = self.current.recording_canvas() {
-      canvas.restore_to_count(1);
-      canvas.save();
-      if let Some(clip) = &self.clip{
-        canvas.clip_path(&clip, ClipOp::Intersect, true /* antialias */);
-      }
-      canvas.set_matrix(&self.matrix.into());
+      reapply(canvas, &self.clip, &self.matrix);
     }
   }
 
@@ -56,25 +123,189 @@ impl Recorder {
     self.restore();
   }
 
-  pub fn get_image(&mut self, matte: Option<Color>) -> Option<SkImage>{
-    if let Some(pict) = self.get_picture(matte) {
-      let size = self.bounds.size().to_floor();
-      SkImage::from_picture(pict, size, None, None, skia_safe::image::BitDepth::U8, Some(ColorSpace::new_srgb()))
+  pub fn is_raster_backed(&self) -> bool {
+    self.raster_backed
+  }
+
+  /// Switch between keeping the full history of drawing commands and keeping a raster surface
+  /// that new commands are flattened into whenever the contents are read. The latter makes
+  /// repeated reads cost only the commands issued since the last one, at the price of vector
+  /// output (PDF/SVG exports contain a bitmap instead).
+  pub fn set_raster_backed(&mut self, raster_backed: bool) {
+    if raster_backed == self.raster_backed {
+      return
+    }
+    if raster_backed {
+      self.raster_backed = true;
+      self.flatten();
+    } else if let Some(image) = self.flatten() {
+      // the history is gone, so carry the flattened contents over as the bottom layer
+      let mut layer = PictureRecorder::new();
+      layer.begin_recording(self.bounds, None);
+      if let Some(canvas) = layer.recording_canvas() {
+        canvas.draw_image(&image, (0, 0), None);
+      }
+      self.history = layer.finish_recording_as_picture(Some(&self.bounds));
+      self.raster_backed = false;
+      self.raster = None;
     } else {
-      panic!("no picture")
+      self.raster_backed = false;
+    }
+  }
+
+  // play any pending commands onto the backing store (reallocating it if the size or pixel
+  // format has changed) and start a fresh recording on top of it
+  fn flatten(&mut self) -> Option<SkImage> {
+    let color_type = match self.bit_depth {
+      BitDepth::F16 => ColorType::RGBAF16,
+      _ => ColorType::N32,
+    };
+    let info = ImageInfo::new(self.bounds.size().to_floor(), color_type, AlphaType::Premul, Some(self.color_space.clone()));
+    let stale = match &self.raster {
+      Some(surface) => {
+        let current = surface.image_info();
+        current.dimensions() != info.dimensions() || current.color_type() != info.color_type()
+          || current.color_space() != info.color_space()
+      },
+      None => true
+    };
+    if stale {
+      let mut fresh = Surface::new_raster(&info, None, None)?;
+      if let Some(mut previous) = self.raster.take() {
+        fresh.canvas().draw_image(&previous.image_snapshot(), (0, 0), None);
+      }
+      self.raster = Some(fresh);
+    }
+
+    let surface = self.raster.as_mut()?;
+    let canvas = surface.canvas();
+    if let Some(pict) = self.history.take() {
+      pict.playback(canvas);
+    }
+    if let Some(pic) = self.current.finish_recording_as_picture(Some(&self.bounds)) {
+      pic.playback(canvas);
     }
+    let image = surface.image_snapshot();
+    self.current.begin_recording(self.bounds, None);
+    self.restore_current();
+    Some(image)
+  }
+
+  pub fn get_image(&mut self, matte: Option<Color>) -> Option<SkImage>{
+    if self.raster_backed {
+      let image = self.flatten()?;
+      return match (matte, self.pending_layers()) {
+        (None, None) => Some(image),
+        (matte, layers) => {
+          let mut surface = Surface::new_raster(&image.image_info(), None, None)?;
+          let canvas = surface.canvas();
+          matte.map(|c| canvas.clear(c));
+          canvas.draw_image(&image, (0, 0), None);
+          if let Some(pict) = layers {
+            pict.playback(canvas);
+          }
+          Some(surface.image_snapshot())
+        }
+      }
+    }
+    let pict = self.get_picture(matte)?;
+    let size = self.bounds.size().to_floor();
+    SkImage::from_picture(pict, size, None, None, self.bit_depth, Some(self.color_space.clone()))
   }
 
   pub fn get_picture(&mut self, matte: Option<Color>) -> Option<Picture> {
+    let flattened = match self.raster_backed {
+      true => Some(self.flatten()?),
+      false => None
+    };
+    let history = match flattened {
+      Some(_) => None,
+      None => self.merge_history()
+    };
+    let layers = self.pending_layers();
+
     let mut compositor = PictureRecorder::new();
     compositor.begin_recording(self.bounds, None);
     if let Some(output) = compositor.recording_canvas() {
       matte.map(|c| output.clear(c));
-      if let Some(pic) = self.current.finish_recording_as_picture(Some(&self.bounds)) {
-        pic.playback(output);
+      if let Some(image) = flattened {
+        output.draw_image(&image, (0, 0), None);
+      }
+      for pict in [history, layers].iter().flatten() {
+        pict.playback(output);
       }
     }
     compositor.finish_recording_as_picture(Some(&self.bounds))
   }
 
-}
\ No newline at end of file
+  // what the open layers would add to the canvas if they were all ended now, so reading the
+  // contents mid-layer matches what endLayer will produce. Each layer's recording is restarted
+  // with its contents so far, leaving the layers open.
+  fn pending_layers(&mut self) -> Option<Picture> {
+    let (bounds, clip, matrix) = (self.bounds, self.clip.clone(), self.matrix);
+    let mut composite: Option<Picture> = None;
+    for layer in self.open_layers.iter_mut().rev() {
+      let contents = layer.recorder.finish_recording_as_picture(Some(&bounds));
+      layer.recorder.begin_recording(bounds, None);
+      if let Some(canvas) = layer.recorder.recording_canvas() {
+        if let Some(pict) = &contents {
+          pict.playback(canvas);
+        }
+        // an outer layer's state is reapplied when the layer inside it ends
+        reapply(canvas, &clip, &matrix);
+      }
+
+      // the layer with any layers inside it already composited on top...
+      let mut group = PictureRecorder::new();
+      group.begin_recording(bounds, None);
+      if let Some(canvas) = group.recording_canvas() {
+        for pict in [contents, composite.take()].iter().flatten() {
+          pict.playback(canvas);
+        }
+      }
+      let group = group.finish_recording_as_picture(Some(&bounds))?;
+
+      // ...composited the way end_layer will, in device space within the layer's clip
+      let mut result = PictureRecorder::new();
+      result.begin_recording(bounds, None);
+      if let Some(canvas) = result.recording_canvas() {
+        if let Some(clip) = &layer.clip {
+          canvas.clip_path(clip, ClipOp::Intersect, true /* antialias */);
+        }
+        canvas.draw_picture(&group, None, Some(&layer.paint));
+      }
+      composite = result.finish_recording_as_picture(Some(&bounds));
+    }
+    composite
+  }
+
+  // fold the current recording into the history and start a fresh one on top of it, so reading
+  // the contents doesn't stop the context from drawing. The history stays a single flat picture
+  // so repeated reads don't pile up pictures that every later read would have to replay.
+  fn merge_history(&mut self) -> Option<Picture> {
+    let mut merged = PictureRecorder::new();
+    merged.begin_recording(self.bounds, None);
+    if let Some(canvas) = merged.recording_canvas() {
+      if let Some(pict) = self.history.take() {
+        pict.playback(canvas);
+      }
+      if let Some(pict) = self.current.finish_recording_as_picture(Some(&self.bounds)) {
+        pict.playback(canvas);
+      }
+    }
+    self.current.begin_recording(self.bounds, None);
+    self.restore_current();
+    self.history = merged.finish_recording_as_picture(Some(&self.bounds));
+    self.history.clone()
+  }
+
+}
+
+fn reapply(canvas:&mut SkCanvas, clip:&Option<Path>, matrix:&Matrix){
+  canvas.restore_to_count(1);
+  canvas.save();
+  if let Some(clip) = clip{
+    canvas.clip_path(clip, ClipOp::Intersect, true /* antialias */);
+  }
+  canvas.set_matrix(&(*matrix).into());
+}
@@ -0,0 +1,84 @@
+use std::ffi::c_char;
+
+use skia_safe::{Color, Document, Size};
+
+use crate::canvas::{add_pdf_page, pdf_document};
+use crate::context::jstypes::{JsBuffer, JsF32Array, JsStrMap};
+use crate::context::Context2D;
+use crate::error::{boxed, status, Error, ErrorCode};
+use crate::utils::*;
+
+/// A PDF that is built up one page at a time and only encoded when it is closed.
+pub struct PdfDocument {
+  doc: Option<Document>,
+}
+
+impl PdfDocument {
+  pub fn new(quality: f32, density: f32, info: Option<&JsStrMap>) -> Self {
+    PdfDocument { doc: Some(pdf_document(quality, density, info)) }
+  }
+
+  pub fn pages(&self) -> usize {
+    self.doc.as_ref().map_or(0, |doc| doc.pages())
+  }
+
+  pub fn add_page(&mut self, ctx: &mut Context2D, size: Option<Size>, matte: Option<Color>, background: Option<Color>, cutting: &[f32]) -> Result<(), Error> {
+    if self.doc.is_none() {
+      return Err(closed())
+    }
+    let pic = ctx.get_picture(matte)
+      .ok_or_else(|| Error::new(ErrorCode::InvalidState, "Could not record the canvas contents"))?;
+    if let Some(doc) = self.doc.take() {
+      self.doc = Some(add_pdf_page(doc, &pic, ctx.bounds, size, background, cutting));
+    }
+    Ok(())
+  }
+
+  pub fn close(&mut self) -> Result<Vec<u8>, Error> {
+    let doc = self.doc.take().ok_or_else(closed)?;
+    Ok(Vec::from(doc.close().as_bytes()))
+  }
+}
+
+fn closed() -> Error {
+  Error::new(ErrorCode::InvalidState, "The PDF document has already been closed")
+}
+
+//
+// Javascript Methods
+//
+
+/// `info` may be null; see [pdf_document] for the keys it understands.
+#[no_mangle]
+pub unsafe extern "C" fn new_pdf_document(quality: f32, density: f32, info: *mut JsStrMap) -> *mut PdfDocument {
+  Box::into_raw(Box::new(PdfDocument::new(quality, density, info.as_ref())))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_pdf_document(doc: *mut PdfDocument) {
+  if !doc.is_null() {
+    drop(Box::from_raw(doc));
+  }
+}
+
+/// Append the context's current contents as a page. A `width` or `height` of 0 sizes the page to
+/// the `cutting` target, or to the canvas when there is no cutting.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_document_add_page(doc: *mut PdfDocument, cx: *mut Context2D, width: f32, height: f32, matte: *mut c_char, background: *mut c_char, cutting: *mut JsF32Array) -> u32 {
+  let matte = css_to_color(&char_to_string(matte));
+  let background = css_to_color(&char_to_string(background));
+  let cutting = cutting.as_ref().map(|arr| arr.as_slice()).unwrap_or(&[]);
+  let size = (width > 0.0 && height > 0.0).then(|| Size::new(width, height));
+  status((*doc).add_page(&mut *cx, size, matte, background, cutting))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pdf_document_page_count(doc: *mut PdfDocument) -> u32 {
+  (*doc).pages() as u32
+}
+
+/// Finish the document, returning null (see `get_last_error`) if it was already closed.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_document_close(doc: *mut PdfDocument) -> *mut JsBuffer {
+  boxed((*doc).close())
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::ffi::c_char;
+use std::ptr::null_mut;
+
+use crate::utils::string_to_char;
+
+/// The kind of failure, numbered so JS can map it onto the matching `DOMException` name.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+  Ok = 0,
+  Type = 1,
+  IndexSize = 2,
+  InvalidState = 3,
+  NotSupported = 4,
+  Syntax = 5,
+  Encoding = 6,
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+  pub code: ErrorCode,
+  pub message: String,
+}
+
+impl Error {
+  pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+    Error { code, message: message.into() }
+  }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<Error>> = RefCell::new(None);
+}
+
+pub fn set_last_error(err: Error) {
+  LAST_ERROR.with(|last| *last.borrow_mut() = Some(err));
+}
+
+/// Record `err` and return null, for exports that hand back a boxed handle.
+pub fn fail<T>(err: Error) -> *mut T {
+  set_last_error(err);
+  null_mut()
+}
+
+/// Turn a result into the status code returned by exports that have no other return value.
+pub fn status(result: Result<()>) -> u32 {
+  match result {
+    Ok(()) => ErrorCode::Ok as u32,
+    Err(err) => {
+      let code = err.code as u32;
+      set_last_error(err);
+      code
+    }
+  }
+}
+
+/// Box a successful result, or record the error and return null.
+pub fn boxed<T>(result: Result<T>) -> *mut T {
+  match result {
+    Ok(value) => Box::into_raw(Box::new(value)),
+    Err(err) => fail(err),
+  }
+}
+
+//
+// Javascript Methods
+//
+
+/// Code of the most recent failure on this thread, or 0 if nothing has failed since the last
+/// `clear_last_error`.
+#[no_mangle]
+pub extern "C" fn get_last_error() -> u32 {
+  LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ErrorCode::Ok as u32, |err| err.code as u32))
+}
+
+/// Message of the most recent failure, or null if there is none.
+#[no_mangle]
+pub extern "C" fn get_last_error_message() -> *mut c_char {
+  LAST_ERROR.with(|last| match last.borrow().as_ref() {
+    Some(err) => string_to_char(err.message.clone()),
+    None => null_mut(),
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn clear_last_error() {
+  LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
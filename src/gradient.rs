@@ -7,6 +7,7 @@ use skia_safe::{gradient_shader, gradient_shader::GradientShaderColors::Colors};
 
 use crate::context::jstypes::JsF32Array;
 use crate::utils::{char_to_string, css_to_color, to_degrees};
+use crate::error::{fail, Error, ErrorCode};
 
 enum Gradient{
   Linear{
@@ -98,40 +99,47 @@ impl CanvasGradient{
 
 #[no_mangle]
 pub unsafe fn new_linear_gradient(arr: *mut JsF32Array) -> *mut CanvasGradient {
-  if let [x1, y1, x2, y2] = (*arr).as_slice()[0..4]{
+  if let Some(&[x1, y1, x2, y2]) = (*arr).as_slice().get(0..4){
     let start = Point::new(x1, y1);
     let end = Point::new(x2, y2);
     let ramp = Gradient::Linear{ start, end, stops:vec![], colors:vec![] };
     let canvas_gradient = Box::new(CanvasGradient{ gradient:Arc::new(Mutex::new(ramp)) });
     Box::into_raw(canvas_gradient)
   } else {
-    panic!("Expected 4 arguments (x1, y1, x2, y2)");
+    fail(Error::new(ErrorCode::Type, "Expected 4 arguments (x1, y1, x2, y2)"))
   }
 }
 
 #[no_mangle]
 pub unsafe fn new_radial_gradient(arr: *mut JsF32Array) -> *mut CanvasGradient {
-  if let [x1, y1, r1, x2, y2, r2] = (*arr).as_slice()[0..6] {
+  if let Some(&[x1, y1, r1, x2, y2, r2]) = (*arr).as_slice().get(0..6) {
     let start_point = Point::new(x1, y1);
     let end_point = Point::new(x2, y2);
     let bloom = Gradient::Radial{ start_point, start_radius: r1, end_point, end_radius: r2, stops:vec![], colors:vec![] };
     let canvas_gradient = Box::new(CanvasGradient{ gradient:Arc::new(Mutex::new(bloom)) });
     Box::into_raw(canvas_gradient)
   }else{
-    panic!("Expected 6 arguments (x1, y1, r1, x2, y2, r2)");
+    fail(Error::new(ErrorCode::Type, "Expected 6 arguments (x1, y1, r1, x2, y2, r2)"))
   }
 }
 
 #[no_mangle]
 pub unsafe fn new_conic_gradient(arr: *mut JsF32Array) -> *mut CanvasGradient {
-  if let [theta, x, y] = (*arr).as_slice()[0..3] {
+  if let Some(&[theta, x, y]) = (*arr).as_slice().get(0..3) {
     let center = Point::new(x, y);
     let angle = to_degrees(theta) - 90.0;
     let sweep = Gradient::Conic{ center, angle, stops:vec![], colors:vec![] };
     let canvas_gradient = Box::new(CanvasGradient{ gradient:Arc::new(Mutex::new(sweep)) });
     Box::into_raw(canvas_gradient)
   } else {
-    panic!("Expected 3 arguments (startAngle, x, y)");
+    fail(Error::new(ErrorCode::Type, "Expected 3 arguments (startAngle, x, y)"))
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_canvas_gradient(g: *mut CanvasGradient) {
+  if !g.is_null() {
+    drop(Box::from_raw(g));
   }
 }
 
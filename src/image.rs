@@ -4,26 +4,106 @@
 #![allow(dead_code)]
 use std::{cell::RefCell, ffi::c_char};
 // use neon::{prelude::*, types::buffer::TypedArray};
-use skia_safe::{Image as SkImage, ImageInfo, Size, ColorType, AlphaType, Data};
+use skia_safe::{Image as SkImage, ImageInfo, Size, ColorType, AlphaType, Data, Codec, EncodedImageFormat,
+                EncodedOrigin, ColorSpace, Color, IRect, Rect, Surface, Picture, PictureRecorder, image::BitDepth};
+use skia_safe::svg::Dom;
+use skia_safe::codec::{self, Options, ZeroInitialized};
+use skia_safe::image::CachingHint;
 
-use crate::{context::jstypes::JsBuffer, utils::*};
+use crate::{context::jstypes::{JsBuffer, JsF32Array, JsStrMap}, utils::*};
+use crate::animation::{png_chunks, write_chunk, PNG_SIGNATURE};
+use crate::error::{boxed, status, Error, ErrorCode, Result};
+use crate::filter::{FilterQuality, ImageFilter};
 
 pub type BoxedImage = RefCell<Image>;
 // impl Finalize for Image {}
 
 pub struct Image{
   // src: String,
-  pub image: Option<SkImage>
+  pub image: Option<SkImage>,
+  pub frames: Option<Frames>,
+  pub picture: Option<Picture>,
+  pub orientation: EncodedOrigin,
+  pub profile: String,
+  pub honor_orientation: bool,
+  source: Option<Data>,
 }
 
 impl Image{
-  pub fn info(width:f32, height:f32) -> ImageInfo {
+  pub fn new(image: Option<SkImage>) -> Self {
+    Image{
+      image, frames: None, picture: None, orientation: EncodedOrigin::TopLeft, profile: String::new(),
+      honor_orientation: true, source: None,
+    }
+  }
+
+  /// Decode `data`, rotating it upright per its EXIF orientation (unless `honor_orientation` is off).
+  /// The image stays tagged with any embedded color profile, so drawing it converts its colors to
+  /// the destination context's color space without first clipping them to sRGB.
+  pub fn load(&mut self, data: Data) -> bool {
+    self.picture = None;
+    if is_svg(data.as_bytes()) {
+      return self.load_svg(data)
+    }
+
+    let codec = Codec::from_data(data.clone());
+    self.orientation = codec.as_ref().map_or(EncodedOrigin::TopLeft, |codec| codec.origin());
+    self.profile = profile_name(data.as_bytes()).unwrap_or_default();
+    self.frames = Frames::new(&data);
+
+    let upright = self.orientation == EncodedOrigin::TopLeft || !self.honor_orientation;
+    self.image = match self.frames.is_none() && !upright {
+      true => {
+        let orientation = if self.honor_orientation { Orientation::FromImage } else { Orientation::None };
+        let opts = BitmapOptions { orientation, ..BitmapOptions::default() };
+        Image::bitmap_from_data(data.clone(), &opts).ok().and_then(|bitmap| bitmap.image)
+      },
+      // upright stills can stay lazily decoded
+      false => SkImage::from_encoded(data.clone())
+    };
+    self.source = Some(data);
+    self.image.is_some()
+  }
+
+  /// Parse an SVG document into a picture at its intrinsic size so it can be drawn at any scale.
+  /// A rasterized copy is kept in `image` for consumers that need pixels.
+  fn load_svg(&mut self, data: Data) -> bool {
+    self.frames = None;
+    self.orientation = EncodedOrigin::TopLeft;
+    self.profile = String::new();
+    self.picture = Dom::from_bytes(data.as_bytes()).ok().and_then(|mut dom| {
+      let size = svg_size(data.as_bytes());
+      dom.set_container_size(size);
+      let mut recorder = PictureRecorder::new();
+      dom.render(recorder.begin_recording(Rect::from_size(size), None));
+      recorder.finish_recording_as_picture(None)
+    });
+    self.image = self.picture.as_ref().and_then(|pict| {
+      let dims = pict.cull_rect().size().to_ceil();
+      SkImage::from_picture(pict.clone(), dims, None, None, BitDepth::U8, ColorSpace::new_srgb())
+    });
+    self.source = Some(data);
+    self.image.is_some()
+  }
+
+  /// Turn EXIF orientation handling on or off, re-decoding the current data if it matters.
+  pub fn set_honor_orientation(&mut self, honor: bool) {
+    let changed = self.honor_orientation != honor;
+    self.honor_orientation = honor;
+    if let (true, Some(data)) = (changed && self.orientation != EncodedOrigin::TopLeft, self.source.clone()) {
+      self.load(data);
+    }
+  }
+
+  pub fn info(width:f32, height:f32, space:PredefinedColorSpace, format:PixelFormat) -> ImageInfo {
     let dims = (width as i32, height as i32);
-    ImageInfo::new(dims, ColorType::RGBA8888, AlphaType::Unpremul, None)
+    ImageInfo::new(dims, format.color_type(), AlphaType::Unpremul, Some(space.to_color_space()))
   }
 
   pub fn size(&self) -> Size{
-    if let Some(img) = &self.image {
+    if let Some(pict) = &self.picture {
+      pict.cull_rect().size()
+    }else if let Some(img) = &self.image {
       let width = &img.width();
       let height = &img.height();
       Size::new(*width as f32, *height as f32)
@@ -31,19 +111,648 @@ impl Image{
       Size::new(0.0, 0.0)
     }
   }
+
+  /// Show frame `idx` of an animated image; still images only have frame 0.
+  pub fn select_frame(&mut self, idx: usize) -> Result<()> {
+    match &mut self.frames {
+      Some(frames) => {
+        self.image = Some(frames.frame(idx)?);
+        Ok(())
+      },
+      None if idx == 0 && self.image.is_some() => Ok(()),
+      None => Err(Error::new(ErrorCode::IndexSize, format!(
+        "Frame {} is out of range (the image has {} frames)", idx, self.image.is_some() as u32
+      )))
+    }
+  }
+}
+
+/// The frames of an animated GIF, WebP or APNG, composited on demand and kept once decoded.
+pub struct Frames {
+  source: FrameSource,
+  info: ImageInfo,
+  durations: Vec<f32>,
+  loops: Option<usize>,
+  decoded: Vec<SkImage>,
+  current: usize,
+}
+
+// Skia's PNG codec ignores APNG chunks, so those frames are reassembled and composited here
+enum FrameSource {
+  Codec(Codec),
+  Apng(Apng),
+}
+
+impl Frames {
+  fn new(data: &Data) -> Option<Self> {
+    let mut codec = Codec::from_data(data.clone())?;
+    let info = ImageInfo::new_n32_premul(codec.dimensions(), codec.info().color_space());
+    if codec.encoded_format() == EncodedImageFormat::PNG {
+      let apng = Apng::new(data.as_bytes())?;
+      let durations = apng.frames.iter().map(|frame| frame.delay).collect();
+      let loops = apng.plays.checked_sub(1).map(|loops| loops as usize); // 0 plays loops forever
+      return Some(Frames { source: FrameSource::Apng(apng), info, durations, loops, decoded: vec![], current: 0 })
+    }
+
+    let count = codec.get_frame_count();
+    if count < 2 {
+      return None
+    }
+    let mut durations = match codec.encoded_format() {
+      EncodedImageFormat::GIF => gif_durations(data.as_bytes()),
+      EncodedImageFormat::WEBP => webp_durations(data.as_bytes()),
+      _ => vec![],
+    };
+    durations.resize(count, 0.0);
+    let loops = codec.get_repetition_count();
+    Some(Frames { source: FrameSource::Codec(codec), info, durations, loops, decoded: vec![], current: 0 })
+  }
+
+  pub fn len(&self) -> usize {
+    self.durations.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.durations.is_empty()
+  }
+
+  /// Display time of frame `idx` in milliseconds.
+  pub fn duration(&self, idx: usize) -> Option<f32> {
+    self.durations.get(idx).copied()
+  }
+
+  /// How many times the animation repeats after its first run, or `None` if it loops forever.
+  pub fn loops(&self) -> Option<usize> {
+    self.loops
+  }
+
+  pub fn current(&self) -> usize {
+    self.current
+  }
+
+  /// The fully composited image for frame `idx`, decoding it (and any frames before it) as needed.
+  pub fn frame(&mut self, idx: usize) -> Result<SkImage> {
+    if idx >= self.len() {
+      return Err(Error::new(ErrorCode::IndexSize, format!(
+        "Frame {} is out of range (the image has {} frames)", idx, self.len()
+      )))
+    }
+    while self.decoded.len() <= idx {
+      let next = self.decoded.len();
+      let image = match &mut self.source {
+        FrameSource::Codec(codec) => decode_frame(codec, &self.info, &self.decoded, next)?,
+        FrameSource::Apng(apng) => apng.decode(&self.info, &self.decoded, next)?,
+      };
+      self.decoded.push(image);
+    }
+    self.current = idx;
+    Ok(self.decoded[idx].clone())
+  }
+}
+
+fn decode_frame(codec: &mut Codec, info: &ImageInfo, decoded: &[SkImage], idx: usize) -> Result<SkImage> {
+  let row_bytes = info.min_row_bytes();
+  let mut pixels = vec![0; info.compute_byte_size(row_bytes)];
+  let failed = |result| Error::new(ErrorCode::Encoding, format!(
+    "Could not decode frame {} ({})", idx, codec::result_to_string(result)
+  ));
+
+  // frames that only update part of the canvas are drawn over an earlier frame, but the codec
+  // refuses priors it can't build on (e.g. ones that restore their predecessor when disposed)
+  let mut result = codec::Result::InvalidParameters;
+  if idx == 0 {
+    let options = Options { zero_initialized: ZeroInitialized::Yes, subset: None, frame_index: 0, prior_frame: 0 };
+    result = codec.get_pixels_with_options(info, &mut pixels, row_bytes, Some(&options));
+  } else {
+    for prior in (0..idx).rev() {
+      if !decoded[prior].read_pixels(info, &mut pixels, row_bytes, (0, 0), CachingHint::Disallow) {
+        continue
+      }
+      let options = Options { zero_initialized: ZeroInitialized::No, subset: None, frame_index: idx, prior_frame: prior };
+      result = codec.get_pixels_with_options(info, &mut pixels, row_bytes, Some(&options));
+      if result != codec::Result::InvalidParameters {
+        break
+      }
+    }
+  }
+
+  match result {
+    codec::Result::Success | codec::Result::IncompleteInput | codec::Result::ErrorInInput => {
+      SkImage::from_raster_data(info, Data::new_copy(&pixels), row_bytes)
+        .ok_or_else(|| failed(result))
+    },
+    _ => Err(failed(result))
+  }
+}
+
+/// The frames of an APNG, each kept as the chunks needed to rebuild it as a standalone PNG.
+struct Apng {
+  header: Vec<([u8; 4], Vec<u8>)>, // IHDR and the ancillary chunks every frame shares
+  frames: Vec<ApngFrame>,
+  plays: u32,
+  backdrop: Option<SkImage>, // what the last decoded frame was drawn over
+}
+
+struct ApngFrame {
+  bounds: IRect,
+  delay: f32, // milliseconds
+  dispose: u8,
+  blend: u8,
+  data: Vec<Vec<u8>>,
+}
+
+impl Apng {
+  /// Split `bytes` into frames, or `None` for a plain PNG (or one with fewer than two frames).
+  fn new(bytes: &[u8]) -> Option<Self> {
+    let be_u32 = |data: &[u8], at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let be_u16 = |data: &[u8], at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+
+    let mut header = vec![];
+    let mut frames: Vec<ApngFrame> = vec![];
+    let mut plays = None;
+    let mut in_data = false;
+    for (kind, data) in png_chunks(bytes).ok()? {
+      match &kind {
+        b"acTL" if data.len() >= 8 => plays = Some(be_u32(data, 4)),
+        b"fcTL" if data.len() >= 26 => {
+          let (num, den) = (be_u16(data, 20) as f32, be_u16(data, 22) as f32);
+          frames.push(ApngFrame {
+            bounds: IRect::from_xywh(be_u32(data, 12) as i32, be_u32(data, 16) as i32, be_u32(data, 4) as i32, be_u32(data, 8) as i32),
+            delay: num * 1000.0 / if den == 0.0 { 100.0 } else { den }, // a zero denominator means 1/100s
+            dispose: data[24],
+            blend: data[25],
+            data: vec![],
+          })
+        },
+        // the default image is only a frame if an fcTL precedes it
+        b"IDAT" => {
+          in_data = true;
+          if let Some(frame) = frames.last_mut() {
+            frame.data.push(data.to_vec())
+          }
+        },
+        b"fdAT" if data.len() >= 4 => {
+          if let Some(frame) = frames.last_mut() {
+            frame.data.push(data[4..].to_vec()) // drop the sequence number
+          }
+        },
+        b"IEND" => break,
+        b"IHDR" if data.len() < 13 => return None,
+        _ if !in_data => header.push((kind, data.to_vec())),
+        _ => {}
+      }
+    }
+
+    match (plays, frames.len()) {
+      (Some(plays), count) if count > 1 => Some(Apng { header, frames, plays, backdrop: None }),
+      _ => None
+    }
+  }
+
+  /// Composite frame `idx` over the previous ones (which must already be in `decoded`).
+  fn decode(&mut self, info: &ImageInfo, decoded: &[SkImage], idx: usize) -> Result<SkImage> {
+    let failed = || Error::new(ErrorCode::Encoding, format!("Could not decode frame {}", idx));
+    let frame = &self.frames[idx];
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    for (kind, data) in &self.header {
+      match kind {
+        b"IHDR" => {
+          let mut ihdr = data.clone();
+          ihdr[0..4].copy_from_slice(&(frame.bounds.width() as u32).to_be_bytes());
+          ihdr[4..8].copy_from_slice(&(frame.bounds.height() as u32).to_be_bytes());
+          write_chunk(&mut png, kind, &ihdr)
+        },
+        _ => write_chunk(&mut png, kind, data)
+      }
+    }
+    for data in &frame.data {
+      write_chunk(&mut png, b"IDAT", data);
+    }
+    write_chunk(&mut png, b"IEND", &[]);
+    let image = SkImage::from_encoded(Data::new_copy(&png)).ok_or_else(failed)?;
+
+    // start from the previous frame, cleared or restored according to its dispose op
+    let mut surface = Surface::new_raster(info, None, None).ok_or_else(failed)?;
+    let canvas = surface.canvas();
+    if let Some(prev) = idx.checked_sub(1) {
+      match self.frames[prev].dispose {
+        2 => if let Some(backdrop) = &self.backdrop {
+          canvas.draw_image(backdrop, (0, 0), None);
+        },
+        dispose => {
+          canvas.draw_image(&decoded[prev], (0, 0), None);
+          if dispose == 1 {
+            canvas.save();
+            canvas.clip_rect(Rect::from(self.frames[prev].bounds), None, None);
+            canvas.clear(Color::TRANSPARENT);
+            canvas.restore();
+          }
+        }
+      }
+    }
+    self.backdrop = Some(surface.image_snapshot());
+
+    let canvas = surface.canvas();
+    let bounds = Rect::from(frame.bounds);
+    if frame.blend == 0 { // source: replace what's under the frame rather than drawing over it
+      canvas.save();
+      canvas.clip_rect(bounds, None, None);
+      canvas.clear(Color::TRANSPARENT);
+      canvas.restore();
+    }
+    canvas.draw_image(&image, (bounds.left, bounds.top), None);
+    Ok(surface.image_snapshot())
+  }
+}
+
+/// Per-frame delays from each image descriptor's preceding Graphic Control Extension.
+fn gif_durations(bytes: &[u8]) -> Vec<f32> {
+  let color_table = |packed: u8| -> usize { if packed & 0x80 != 0 { 3 << ((packed & 0x07) + 1) } else { 0 } };
+  let mut durations = vec![];
+  if bytes.len() < 13 || !bytes.starts_with(b"GIF") {
+    return durations
+  }
+  let mut delay = 0.0;
+  let mut at = 13 + color_table(bytes[10]);
+  while let Some(&block) = bytes.get(at) {
+    match block {
+      0x21 => { // extension
+        if bytes.get(at + 1) == Some(&0xF9) && at + 5 < bytes.len() {
+          delay = u16::from_le_bytes([bytes[at + 4], bytes[at + 5]]) as f32 * 10.0; // centiseconds
+        }
+        at = skip_sub_blocks(bytes, at + 2);
+      },
+      0x2C => { // image descriptor
+        durations.push(delay);
+        delay = 0.0;
+        let packed = match bytes.get(at + 9) { Some(packed) => *packed, None => break };
+        at = skip_sub_blocks(bytes, at + 11 + color_table(packed)); // skip the LZW code size too
+      },
+      _ => break // trailer
+    }
+  }
+  durations
+}
+
+fn skip_sub_blocks(bytes: &[u8], mut at: usize) -> usize {
+  while let Some(&len) = bytes.get(at) {
+    at += 1 + len as usize;
+    if len == 0 {
+      break
+    }
+  }
+  at
+}
+
+/// Per-frame durations from the ANMF chunks of an extended-format WebP.
+fn webp_durations(bytes: &[u8]) -> Vec<f32> {
+  let mut durations = vec![];
+  let mut at = 12;
+  while at + 8 <= bytes.len() {
+    let len = u32::from_le_bytes([bytes[at + 4], bytes[at + 5], bytes[at + 6], bytes[at + 7]]) as usize;
+    if &bytes[at..at + 4] == b"ANMF" {
+      if let Some(ms) = bytes.get(at + 20..at + 23) {
+        durations.push(u32::from_le_bytes([ms[0], ms[1], ms[2], 0]) as f32);
+      }
+    }
+    at += 8 + len + (len & 1);
+  }
+  durations
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+  FromImage, FlipY, None
+}
+
+/// The `ImageBitmapOptions` accepted by `createImageBitmap`, plus its optional source rectangle.
+#[derive(Copy, Clone)]
+pub struct BitmapOptions {
+  pub crop: Option<Rect>,
+  pub resize_width: Option<f32>,
+  pub resize_height: Option<f32>,
+  pub resize_quality: ImageFilter,
+  pub premultiply: bool,
+  pub orientation: Orientation,
+  pub convert_colors: bool,
+}
+
+impl Default for BitmapOptions {
+  fn default() -> Self {
+    BitmapOptions {
+      crop: None,
+      resize_width: None,
+      resize_height: None,
+      resize_quality: ImageFilter { smoothing: true, quality: FilterQuality::Low },
+      premultiply: true,
+      orientation: Orientation::FromImage,
+      convert_colors: true,
+    }
+  }
+}
+
+impl BitmapOptions {
+  /// Read the options from their JS names, rejecting enum values the spec doesn't allow.
+  pub fn new(crop: &[f32], options: Option<&JsStrMap>) -> Result<Self> {
+    let mut opts = BitmapOptions::default();
+    match crop {
+      [] => {},
+      [_, _, w, h] if *w == 0.0 || *h == 0.0 => {
+        return Err(Error::new(ErrorCode::IndexSize, "The crop rectangle must have a non-zero width and height"))
+      },
+      [x, y, w, h] => opts.crop = Some(Rect::from_xywh(*x, *y, *w, *h).sorted()),
+      _ => return Err(Error::new(ErrorCode::Type, format!("Expected 0 or 4 crop coordinates (got {})", crop.len())))
+    }
+
+    let options = match options {
+      Some(options) => options,
+      None => return Ok(opts)
+    };
+    let invalid = |key: &str, value: &str| Error::new(ErrorCode::Type, format!("'{}' is not a valid value for {}", value, key));
+    for (key, value) in options.iter() {
+      match key.as_str() {
+        "resizeWidth" | "resizeHeight" => {
+          let size = value.parse::<f32>().ok().filter(|n| n.is_finite() && *n >= 0.0)
+            .ok_or_else(|| invalid(key, value))?;
+          if size < 1.0 {
+            return Err(Error::new(ErrorCode::InvalidState, format!("{} must be at least 1", key)))
+          }
+          match key.as_str() {
+            "resizeWidth" => opts.resize_width = Some(size.floor()),
+            _ => opts.resize_height = Some(size.floor()),
+          }
+        },
+        "resizeQuality" => opts.resize_quality = match value.as_str() {
+          "pixelated" => ImageFilter { smoothing: false, quality: FilterQuality::None },
+          _ => ImageFilter { smoothing: true, quality: to_filter_quality(value).ok_or_else(|| invalid(key, value))? },
+        },
+        "premultiplyAlpha" => opts.premultiply = match value.as_str() {
+          "default" | "premultiply" => true,
+          "none" => false,
+          _ => return Err(invalid(key, value))
+        },
+        "imageOrientation" => opts.orientation = match value.as_str() {
+          "from-image" => Orientation::FromImage,
+          "flipY" => Orientation::FlipY,
+          "none" => Orientation::None,
+          _ => return Err(invalid(key, value))
+        },
+        "colorSpaceConversion" => opts.convert_colors = match value.as_str() {
+          "default" => true,
+          "none" => false,
+          _ => return Err(invalid(key, value))
+        },
+        _ => {}
+      }
+    }
+    Ok(opts)
+  }
+}
+
+impl Image {
+  /// Fully decode an encoded image into a raster bitmap so that drawing it never has to wait on the codec.
+  pub fn bitmap_from_data(data: Data, opts: &BitmapOptions) -> Result<Self> {
+    let undecodable = || Error::new(ErrorCode::InvalidState, "The source image could not be decoded");
+    let mut codec = Codec::from_data(data).ok_or_else(undecodable)?;
+    let origin = match opts.orientation {
+      Orientation::None => EncodedOrigin::TopLeft,
+      _ => codec.origin(),
+    };
+    // keep the embedded profile so the colors are converted when drawn, or decode without one to
+    // leave the pixel values as they were encoded
+    let color_space = match opts.convert_colors {
+      true => codec.info().color_space(),
+      false => None
+    };
+    let alpha = match codec.info().alpha_type() {
+      AlphaType::Opaque => AlphaType::Opaque,
+      _ => AlphaType::Premul
+    };
+    let info = ImageInfo::new(codec.dimensions(), ColorType::N32, alpha, color_space);
+    let image = codec.get_image(info, None).map_err(|_| undecodable())?;
+    Ok(Image::new(Some(bitmap(&image, origin, opts)?)))
+  }
+
+  /// Snapshot the current frame of an already loaded image as a bitmap.
+  pub fn bitmap_from_image(&self, opts: &BitmapOptions) -> Result<Self> {
+    let image = self.image.as_ref()
+      .ok_or_else(|| Error::new(ErrorCode::InvalidState, "Cannot create a bitmap from an incomplete image"))?;
+    let image = image.to_raster_image(CachingHint::Allow)
+      .ok_or_else(|| Error::new(ErrorCode::InvalidState, "The source image could not be decoded"))?;
+    Ok(Image::new(Some(bitmap(&image, EncodedOrigin::TopLeft, opts)?)))
+  }
+}
+
+/// Orient, crop, resize, and flip a decoded raster image as `createImageBitmap` would.
+fn bitmap(image: &SkImage, origin: EncodedOrigin, opts: &BitmapOptions) -> Result<SkImage> {
+  let dims = image.dimensions();
+  let oriented = match origin.swaps_width_height() {
+    true => Size::new(dims.height as f32, dims.width as f32),
+    false => Size::new(dims.width as f32, dims.height as f32),
+  };
+  let crop = opts.crop.unwrap_or_else(|| Rect::from_size(oriented));
+  let (width, height) = match (opts.resize_width, opts.resize_height) {
+    (Some(w), Some(h)) => (w, h),
+    (Some(w), None) => (w, (w * crop.height() / crop.width()).ceil()),
+    (None, Some(h)) => ((h * crop.width() / crop.height()).ceil(), h),
+    (None, None) => (crop.width(), crop.height()),
+  };
+
+  let untouched = origin == EncodedOrigin::TopLeft && opts.orientation != Orientation::FlipY
+    && crop == Rect::from_size(oriented) && (width, height) == (crop.width(), crop.height());
+  let image = match untouched {
+    true => image.clone(),
+    false => {
+      let info = ImageInfo::new_n32_premul((width as i32, height as i32), image.image_info().color_space());
+      let mut surface = Surface::new_raster(&info, None, None)
+        .ok_or_else(|| Error::new(ErrorCode::InvalidState, format!("Cannot allocate a {}×{} bitmap", width, height)))?;
+      let canvas = surface.canvas();
+      if opts.orientation == Orientation::FlipY {
+        canvas.translate((0.0, height));
+        canvas.scale((1.0, -1.0));
+      }
+      canvas.scale((width / crop.width(), height / crop.height()));
+      canvas.translate((-crop.left, -crop.top));
+      canvas.concat(&origin.to_matrix(dims));
+      canvas.draw_image_with_sampling_options(image, (0, 0), opts.resize_quality.sampling(), None);
+      surface.image_snapshot()
+    }
+  };
+
+  match opts.premultiply {
+    true => Ok(image),
+    false => {
+      let info = ImageInfo::new(image.dimensions(), ColorType::N32, AlphaType::Unpremul, image.image_info().color_space());
+      let row_bytes = info.min_row_bytes();
+      let mut pixels = vec![0u8; info.compute_byte_size(row_bytes)];
+      match image.read_pixels(&info, &mut pixels, row_bytes, (0, 0), CachingHint::Disallow) {
+        true => SkImage::from_raster_data(&info, Data::new_copy(&pixels), row_bytes),
+        false => None
+      }.ok_or_else(|| Error::new(ErrorCode::InvalidState, "Could not unpremultiply the bitmap"))
+    }
+  }
+}
+
+/// Whether the data looks like an SVG document rather than one of the binary image formats.
+fn is_svg(bytes: &[u8]) -> bool {
+  let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+  let head = head.trim_start_matches('\u{FEFF}').trim_start();
+  head.starts_with('<') && head.contains("<svg")
+}
+
+/// The intrinsic size of an SVG from its root element's `width`, `height`, and `viewBox`, falling
+/// back to the 300×150 that browsers use for replaced elements of unknown size.
+fn svg_size(bytes: &[u8]) -> Size {
+  let text = String::from_utf8_lossy(bytes);
+  let root = text.find("<svg").map(|start| {
+    let tag = &text[start + 4..];
+    &tag[..tag.find('>').unwrap_or(tag.len())]
+  }).unwrap_or_default();
+
+  let width = svg_attribute(root, "width").and_then(svg_length);
+  let height = svg_attribute(root, "height").and_then(svg_length);
+  let view_box = svg_attribute(root, "viewBox").and_then(|vb| {
+    let nums: Vec<f32> = vb.split(|c: char| c == ',' || c.is_whitespace())
+      .filter(|n| !n.is_empty()).filter_map(|n| n.parse().ok()).collect();
+    match nums[..] {
+      [_, _, w, h] if w > 0.0 && h > 0.0 => Some(Size::new(w, h)),
+      _ => None
+    }
+  });
+
+  match (width, height, view_box) {
+    (Some(w), Some(h), _) => Size::new(w, h),
+    (Some(w), None, Some(vb)) => Size::new(w, w * vb.height / vb.width),
+    (None, Some(h), Some(vb)) => Size::new(h * vb.width / vb.height, h),
+    (None, None, Some(vb)) => vb,
+    (w, h, None) => Size::new(w.unwrap_or(300.0), h.unwrap_or(150.0)),
+  }
+}
+
+fn svg_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+  let mut rest = tag;
+  while let Some(eq) = rest.find('=') {
+    let key = rest[..eq].trim_end().rsplit(|c: char| c.is_whitespace()).next().unwrap_or_default();
+    let value = rest[eq + 1..].trim_start();
+    let quote = value.chars().next()?;
+    if quote != '"' && quote != '\'' {
+      return None
+    }
+    let end = value[1..].find(quote)? + 1;
+    if key == name {
+      return Some(value[1..end].trim())
+    }
+    rest = &value[end + 1..];
+  }
+  None
+}
+
+/// An absolute SVG length in px; percentages and font-relative units don't give an intrinsic size.
+fn svg_length(length: &str) -> Option<f32> {
+  let split = length.find(|c: char| c.is_ascii_alphabetic() || c == '%').unwrap_or(length.len());
+  let (num, unit) = length.split_at(split);
+  let scale = match unit.trim() {
+    "" | "px" => 1.0,
+    "pt" => 4.0 / 3.0,
+    "pc" => 16.0,
+    "in" => 96.0,
+    "cm" => 96.0 / 2.54,
+    "mm" => 96.0 / 25.4,
+    _ => return None
+  };
+  num.trim().parse::<f32>().ok().map(|n| n * scale).filter(|n| *n > 0.0)
+}
+
+/// Name of the ICC profile embedded in a JPEG, PNG, or WebP file, read from the profile's
+/// description tag (PNGs label the profile themselves, so their label is used instead).
+fn profile_name(bytes: &[u8]) -> Option<String> {
+  if bytes.starts_with(&[0xFF, 0xD8]) {
+    // large profiles are split across several APP2 segments
+    let mut icc = vec![];
+    let mut at = 2;
+    while at + 4 <= bytes.len() && bytes[at] == 0xFF && !matches!(bytes[at + 1], 0xDA | 0xD9) {
+      let len = u16::from_be_bytes([bytes[at + 2], bytes[at + 3]]) as usize;
+      let segment = bytes.get(at + 4..at + 2 + len)?;
+      if bytes[at + 1] == 0xE2 && segment.starts_with(b"ICC_PROFILE\0") {
+        icc.extend(segment.get(14..)?);
+      }
+      at += 2 + len;
+    }
+    icc_description(&icc)
+  } else if bytes.starts_with(b"\x89PNG") {
+    let mut at = 8;
+    while at + 12 <= bytes.len() {
+      let len = u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize;
+      if &bytes[at + 4..at + 8] == b"iCCP" {
+        let data = bytes.get(at + 8..at + 8 + len)?;
+        let name = data.split(|b| *b == 0).next()?;
+        return Some(name.iter().map(|b| *b as char).collect()) // Latin-1
+      }
+      at += 12 + len;
+    }
+    None
+  } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+      let len = u32::from_le_bytes([bytes[at + 4], bytes[at + 5], bytes[at + 6], bytes[at + 7]]) as usize;
+      if &bytes[at..at + 4] == b"ICCP" {
+        return icc_description(bytes.get(at + 8..at + 8 + len)?)
+      }
+      at += 8 + len + (len & 1);
+    }
+    None
+  } else {
+    None
+  }
 }
 
+/// The text of an ICC profile's `desc` tag, in either the v2 `desc` or v4 `mluc` encoding.
+fn icc_description(icc: &[u8]) -> Option<String> {
+  let be32 = |bytes: &[u8], at: usize| bytes.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+  let count = be32(icc, 128)?;
+  let entry = (0..count.min(256)).map(|i| 132 + i * 12).find(|at| icc.get(*at..at + 4) == Some(b"desc"))?;
+  let (offset, size) = (be32(icc, entry + 4)?, be32(icc, entry + 8)?);
+  let tag = icc.get(offset..offset + size)?;
+
+  let text = match tag.get(0..4)? {
+    b"desc" => {
+      let len = be32(tag, 8)?;
+      String::from_utf8_lossy(tag.get(12..12 + len)?).into_owned()
+    },
+    b"mluc" => {
+      // use the first localization
+      let (len, start) = (be32(tag, 20)?, be32(tag, 24)?);
+      let units: Vec<u16> = tag.get(start..start + len)?.chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+      String::from_utf16_lossy(&units)
+    },
+    _ => return None
+  };
+  Some(text.trim_end_matches('\0').to_string())
+}
+
+//
+// Javascript Methods
+//
+
 #[no_mangle]
 pub extern "C" fn new_image() -> *mut Image {
-  Box::into_raw(Box::new(Image{ image:None }))
+  Box::into_raw(Box::new(Image::new(None)))
+}
+
+#[no_mangle]
+pub extern "C" fn drop_image(image: *mut Image) {
+  if !image.is_null() {
+    unsafe { drop(Box::from_raw(image)) }
+  }
 }
 
 #[no_mangle]
 pub extern "C" fn image_set_data(image: *mut Image, buffer: *mut JsBuffer) -> u32 {
   unsafe {
     let data = Data::new_copy((*buffer).as_slice());
-    (*image).image = SkImage::from_encoded(data);
-    if (*image).image.is_some() {
+    if (*image).load(data) {
       1
     } else {
       0
@@ -63,4 +772,93 @@ pub extern "C" fn image_get_height(image: *mut Image) -> f32 {
   unsafe {
     (*image).size().height
   }
-}
\ No newline at end of file
+}
+
+/// The EXIF orientation found in the image data (1–8, where 1 means upright), whether or not it
+/// has been applied.
+#[no_mangle]
+pub extern "C" fn image_get_orientation(image: *mut Image) -> u32 {
+  unsafe {
+    (*image).orientation as u32
+  }
+}
+
+/// Name of the embedded color profile, or an empty string if the image doesn't have one.
+#[no_mangle]
+pub extern "C" fn image_get_color_profile(image: *mut Image) -> *mut c_char {
+  unsafe {
+    string_to_char((*image).profile.clone())
+  }
+}
+
+/// Whether decoding should rotate the image upright per its EXIF orientation (the default).
+#[no_mangle]
+pub extern "C" fn image_set_honor_orientation(image: *mut Image, honor: u32) {
+  unsafe {
+    (*image).set_honor_orientation(honor != 0)
+  }
+}
+
+/// Number of frames in the image: 1 for stills, 0 if nothing has loaded.
+#[no_mangle]
+pub extern "C" fn image_get_frame_count(image: *mut Image) -> u32 {
+  unsafe {
+    match &(*image).frames {
+      Some(frames) => frames.len() as u32,
+      None => (*image).image.is_some() as u32
+    }
+  }
+}
+
+/// How long frame `idx` is shown for, in milliseconds (0 for stills or out-of-range frames).
+#[no_mangle]
+pub extern "C" fn image_get_frame_duration(image: *mut Image, idx: u32) -> f32 {
+  unsafe {
+    (*image).frames.as_ref().and_then(|frames| frames.duration(idx as usize)).unwrap_or(0.0)
+  }
+}
+
+/// Times an animation repeats after first playing through, or -1 if it loops forever.
+#[no_mangle]
+pub extern "C" fn image_get_loop_count(image: *mut Image) -> i32 {
+  unsafe {
+    match (*image).frames.as_ref().map(|frames| frames.loops()) {
+      Some(None) => -1,
+      Some(Some(loops)) => loops.min(i32::MAX as usize) as i32,
+      None => 0
+    }
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn image_get_frame(image: *mut Image) -> u32 {
+  unsafe {
+    (*image).frames.as_ref().map_or(0, |frames| frames.current() as u32)
+  }
+}
+
+/// Choose the frame that `drawImage` and patterns will use from now on.
+#[no_mangle]
+pub extern "C" fn image_set_frame(image: *mut Image, idx: u32) -> u32 {
+  unsafe {
+    status((*image).select_frame(idx as usize))
+  }
+}
+
+/// Eagerly decode `buffer` into a bitmap, following `createImageBitmap`. `crop` holds either no
+/// numbers or the source rectangle's x, y, width and height; `options` may be null. Returns null
+/// (see `get_last_error`) if the options are invalid or the data can't be decoded.
+#[no_mangle]
+pub unsafe extern "C" fn new_image_bitmap(buffer: *mut JsBuffer, crop: *mut JsF32Array, options: *mut JsStrMap) -> *mut Image {
+  let crop = crop.as_ref().map(|arr| arr.as_slice()).unwrap_or(&[]);
+  boxed(BitmapOptions::new(crop, options.as_ref()).and_then(|opts| {
+    Image::bitmap_from_data(Data::new_copy((*buffer).as_slice()), &opts)
+  }))
+}
+
+/// Like `new_image_bitmap`, but starting from the current frame of an image that has already loaded.
+#[no_mangle]
+pub unsafe extern "C" fn image_bitmap_from_image(image: *mut Image, crop: *mut JsF32Array, options: *mut JsStrMap) -> *mut Image {
+  let crop = crop.as_ref().map(|arr| arr.as_slice()).unwrap_or(&[]);
+  boxed(BitmapOptions::new(crop, options.as_ref()).and_then(|opts| (*image).bitmap_from_image(&opts)))
+}
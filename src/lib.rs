@@ -2,17 +2,21 @@
 use once_cell::sync::Lazy;
 use std::sync::{Mutex};
 
-mod typography;
-mod utils;
-mod filter;
-mod path;
-mod context;
-mod gradient;
-mod image;
-mod pattern;
-mod texture;
-mod canvas;
-mod surface;
+pub mod typography;
+pub mod rich_text;
+pub mod utils;
+pub mod filter;
+pub mod path;
+pub mod context;
+pub mod gradient;
+pub mod image;
+pub mod pattern;
+pub mod texture;
+pub mod canvas;
+pub mod surface;
+pub mod error;
+pub mod document;
+pub mod animation;
 
 use typography::FontLibrary;
 
@@ -12,11 +12,15 @@ mod pattern;
 mod texture;
 mod canvas;
 mod surface;
+mod error;
+mod document;
+mod animation;
 
 use typography::FontLibrary;
 
 pub static FONT_LIBRARY: Lazy<Mutex<FontLibrary>> = Lazy::new(|| FontLibrary::shared() );
 
+#[cfg(all(feature = "gpu", target_os = "emscripten"))]
 extern "C" {
   pub fn emscripten_GetProcAddress(
     name: *const ::std::os::raw::c_char,
@@ -26,6 +30,7 @@ extern "C" {
 /// Load GL functions pointers from JavaScript so we can call OpenGL functions from Rust.
 ///
 /// This only needs to be done once.
+#[cfg(all(feature = "gpu", target_os = "emscripten"))]
 fn init_gl() {
     unsafe {
         gl::load_with(|addr| {
@@ -37,5 +42,6 @@ fn init_gl() {
 
 /// The main function is called by emscripten when the WASM object is created.
 fn main() {
+    #[cfg(all(feature = "gpu", target_os = "emscripten"))]
     init_gl();
 }
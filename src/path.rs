@@ -12,6 +12,7 @@ use skia_safe::path::{self, AddPathMode, Verb, FillType};
 
 use crate::context::jstypes::{js_f32_array_push, new_js_f32_array, JsF32Array};
 use crate::utils::*;
+use crate::error::{fail, status, Error, ErrorCode};
 
 pub struct Path2D {
   pub path:Path
@@ -101,15 +102,22 @@ pub extern "C" fn new_path2d_form_svg(svg_path: *mut c_char) -> *mut Path2D {
 #[no_mangle]
 pub extern "C" fn new_path2d_from_path(path: *mut Path2D) -> *mut Path2D {
   unsafe {
-    let path = Box::from_raw(path).path.clone();
+    let path = (*path).path.clone();
     Box::into_raw(Box::new(Path2D { path: path }))
   }
 }
 
+#[no_mangle]
+pub extern "C" fn drop_path2d(path: *mut Path2D) {
+  if !path.is_null() {
+    unsafe { drop(Box::from_raw(path)) }
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn path2d_add_path(path: *mut Path2D, other: *mut Path2D, transform: *mut Matrix) {
   unsafe {
-    let transform = if transform.is_null() { Matrix::new_identity() } else { *Box::from_raw(transform) };
+    let transform = if transform.is_null() { Matrix::new_identity() } else { *transform };
 
     // make a copy if adding a path to itself, otherwise use a ref
     if path == other {
@@ -234,10 +242,10 @@ pub extern "C" fn path2d_op(path: *mut Path2D, other_path: *mut Path2D, op: *mut
     if let Some(path_op) = to_path_op(&op_name){
       match (*path).path.op(&(*other_path).path, path_op) {
         Some(path) => Box::into_raw(Box::new(Path2D{ path })),
-        None => panic!("path operation failed")
+        None => fail(Error::new(ErrorCode::InvalidState, "path operation failed"))
       }
     }else{
-      panic!("pathOp must be Difference, Intersect, Union, XOR, or Complement")
+      fail(Error::new(ErrorCode::Syntax, "pathOp must be Difference, Intersect, Union, XOR, or Complement"))
     }
   }
 }
@@ -248,7 +256,7 @@ pub extern "C" fn path2d_interpolate(path: *mut Path2D, other_path: *mut Path2D,
     if let Some(path) = (*other_path).path.interpolate(&(*path).path, weight){
       Box::into_raw(Box::new(Path2D{ path }))
     }else{
-      panic!("Can only interpolate between two Path2D objects with the same number of points and control points")
+      fail(Error::new(ErrorCode::Type, "Can only interpolate between two Path2D objects with the same number of points and control points"))
     }
   }
 }
@@ -372,8 +380,7 @@ pub extern "C" fn path2d_bounds(path: *mut Path2D) -> *mut JsF32Array {
 #[no_mangle]
 pub extern "C" fn path2d_contains(path: *mut Path2D, x: f32, y: f32) -> i32 {
   unsafe {
-    let mut path = Box::from_raw(path);
-    if path.path.contains((x,y)) {
+    if (*path).path.contains((x,y)) {
       1
     } else {
       0
@@ -384,21 +391,21 @@ pub extern "C" fn path2d_contains(path: *mut Path2D, x: f32, y: f32) -> i32 {
 #[no_mangle]
 pub extern "C" fn path2d_get_d(path: *mut Path2D) -> *mut c_char {
   unsafe {
-    let mut path = Box::from_raw(path);
-    string_to_char(path.path.to_svg())
+    string_to_char((*path).path.to_svg())
   }
 }
 
 #[no_mangle]
-pub extern "C" fn path2d_set_d(path: *mut Path2D, svg_path: *mut c_char) {
+pub extern "C" fn path2d_set_d(path: *mut Path2D, svg_path: *mut c_char) -> u32 {
   unsafe {
-    let mut p = Box::from_raw(path);
+    let p = &mut *path;
     let svg_string = char_to_string(svg_path);
     if let Some(path) = Path::from_svg(svg_string){
       p.path.rewind();
       p.path.add_path(&path, (0,0), None);
+      status(Ok(()))
     }else{
-      panic!("Expected a valid SVG path string")
+      status(Err(Error::new(ErrorCode::Syntax, "Expected a valid SVG path string")))
     }
   }
 }
\ No newline at end of file
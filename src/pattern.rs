@@ -14,6 +14,7 @@ use crate::context::Context2D;
 use crate::utils::*;
 use crate::image::{BoxedImage, Image};
 use crate::filter::ImageFilter;
+use crate::error::{fail, Error, ErrorCode};
 
 pub struct Stamp{
   image:Option<SkImage>,
@@ -51,9 +52,11 @@ pub unsafe extern "C" fn new_canvas_pattern_from_image(image: *mut Image, repeti
   let repetition = char_to_string(repetition);
   if let Some(repeat) = to_repeat_mode(&repetition) {
     let  dims = (*image).size();
+    // vector images tile their picture so they stay sharp when the pattern is scaled
+    let pict = (*image).picture.clone();
     let stamp = Stamp{
-      image: (*image).image.clone(),
-      pict:None,
+      image: if pict.is_some() { None } else { (*image).image.clone() },
+      pict,
       dims,
       repeat,
       matrix:Matrix::new_identity()
@@ -61,7 +64,7 @@ pub unsafe extern "C" fn new_canvas_pattern_from_image(image: *mut Image, repeti
     let stamp = Arc::new(Mutex::new(stamp));
     Box::into_raw(Box::new(CanvasPattern{stamp}))
   } else {
-    panic!("Unknown pattern repeat style")
+    fail(Error::new(ErrorCode::Syntax, format!("Unknown pattern repeat style: {}", repetition)))
   }
 }
 
@@ -80,7 +83,14 @@ pub unsafe extern "C" fn new_canvas_pattern_from_canvas(cx: *mut Context2D, repe
     let stamp = Arc::new(Mutex::new(stamp));
     Box::into_raw(Box::new(CanvasPattern{stamp}))
   } else {
-    panic!("Unknown pattern repeat style")
+    fail(Error::new(ErrorCode::Syntax, format!("Unknown pattern repeat style: {}", repetition)))
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_canvas_pattern(cp: *mut CanvasPattern) {
+  if !cp.is_null() {
+    drop(Box::from_raw(cp));
   }
 }
 
@@ -0,0 +1,504 @@
+use std::os::raw::c_char;
+
+use skia_safe::{Color, Image as SkImage, Paint, Point, Rect};
+use skia_safe::font_style::{FontStyle, Weight, Width};
+use skia_safe::textlayout::{FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle,
+                            TextDecoration, TextDecorationStyle, PlaceholderStyle, PlaceholderAlignment,
+                            TextBaseline, TextBox, TextDirection, RectHeightStyle, RectWidthStyle,
+                            Affinity};
+use crate::context::jstypes::{JsF32Array, JsStrMap};
+use crate::error::{status, Error, ErrorCode, Result};
+use crate::image::Image;
+use crate::typography::{to_slant, to_text_align, Spacing, GALLEY};
+use crate::utils::*;
+use crate::FONT_LIBRARY;
+
+//
+// Multi-style paragraphs
+//
+
+enum Run{
+  Text{ text: String, style: TextStyle },
+  // a box that flows with the text, optionally filled with an image when drawn
+  Placeholder{ style: PlaceholderStyle, image: Option<SkImage> },
+}
+
+/// A paragraph assembled from differently styled runs of text, laid out independently of any
+/// context's font and text settings.
+pub struct RichText{
+  runs: Vec<Run>,
+  graf_style: ParagraphStyle,
+  width: Option<f32>,
+  paragraph: Option<Paragraph>, // laid out on demand and discarded whenever the text changes
+}
+
+impl Default for RichText {
+  fn default() -> Self {
+    let mut graf_style = ParagraphStyle::new();
+    graf_style.set_text_style(&default_style());
+    RichText{ runs: vec![], graf_style, width: None, paragraph: None }
+  }
+}
+
+impl RichText{
+  pub fn add_run(&mut self, text: &str, style: TextStyle){
+    self.runs.push(Run::Text{ text: text.to_string(), style });
+    self.paragraph = None;
+  }
+
+  pub fn add_placeholder(&mut self, style: PlaceholderStyle, image: Option<SkImage>){
+    self.runs.push(Run::Placeholder{ style, image });
+    self.paragraph = None;
+  }
+
+  /// Update the layout width (unwrapped if None), line limit, ellipsis and alignment.
+  pub fn set_options(&mut self, width: Option<f32>, graf_style: ParagraphStyle){
+    self.width = width;
+    self.graf_style = graf_style;
+    self.paragraph = None;
+  }
+
+  pub fn paragraph(&mut self) -> &Paragraph {
+    if self.paragraph.is_none() {
+      self.paragraph = Some(self.layout());
+    }
+    self.paragraph.as_ref().unwrap()
+  }
+
+  fn layout(&self) -> Paragraph {
+    let mut typefaces = self.typefaces();
+    // see Typesetter::layout for why the cache is bypassed
+    typefaces.paragraph_cache_mut().turn_on(false);
+    let mut builder = ParagraphBuilder::new(&self.graf_style, &typefaces);
+    for run in &self.runs {
+      match run {
+        Run::Text{ text, style } => {
+          builder.push_style(style);
+          builder.add_text(text);
+          builder.pop();
+        },
+        Run::Placeholder{ style, .. } => {
+          builder.add_placeholder(style);
+        }
+      }
+    }
+    let mut paragraph = builder.build();
+    match self.width {
+      Some(width) => paragraph.layout(width),
+      None => {
+        // without a width, size the paragraph to fit its longest line
+        paragraph.layout(GALLEY);
+        paragraph.layout(paragraph.max_intrinsic_width().ceil());
+      }
+    }
+    typefaces.paragraph_cache_mut().turn_on(true);
+    paragraph
+  }
+
+  fn text_styles(&self) -> impl Iterator<Item=&TextStyle> {
+    self.runs.iter().filter_map(|run| match run {
+      Run::Text{ style, .. } => Some(style),
+      Run::Placeholder{ .. } => None
+    })
+  }
+
+  // variable fonts need a collection of their own (see FontLibrary::collect_fonts), which is only
+  // possible when every run is set in the same face
+  fn typefaces(&self) -> FontCollection {
+    let mut library = FONT_LIBRARY.lock().unwrap();
+    match self.text_styles().next() {
+      Some(first) if self.text_styles().all(|style| same_face(style, first)) =>
+        library.collect_fonts(first),
+      _ => library.collection.clone()
+    }
+  }
+
+  /// The laid-out box of each placeholder, relative to the paragraph's top-left. Placeholders
+  /// cut off by `maxLines` are left out.
+  pub fn placeholder_rects(&mut self) -> Vec<Rect> {
+    self.paragraph().get_rects_for_placeholders().iter().map(|text_box| text_box.rect).collect()
+  }
+
+  /// The images to draw into placeholder slots, paired with their laid-out boxes.
+  pub fn inline_images(&mut self) -> Vec<(Rect, SkImage)> {
+    let rects = self.placeholder_rects();
+    let images = self.runs.iter().filter_map(|run| match run {
+      Run::Placeholder{ image, .. } => Some(image),
+      Run::Text{ .. } => None
+    });
+    rects.into_iter().zip(images)
+      .filter_map(|(rect, image)| image.as_ref().map(|image| (rect, image.clone())))
+      .collect()
+  }
+
+  /// The box of each laid-out line (from ascent to descent), relative to the paragraph's top-left.
+  pub fn line_bounds(&mut self) -> Vec<Rect> {
+    self.paragraph().get_line_metrics().iter().map(|line|
+      Rect::new(line.left as f32, (line.baseline - line.ascent) as f32,
+                (line.left + line.width) as f32, (line.baseline + line.descent) as f32)
+    ).collect()
+  }
+
+  /// A conservative box around the laid-out glyphs, relative to the paragraph's top-left.
+  pub fn bounds(&mut self) -> Option<Rect> {
+    let bounds = self.line_bounds().iter().fold(Rect::new_empty(), |union, line| Rect::join2(union, line));
+    // leave room for glyphs that overhang their advance (italics, swashes, etc.)
+    let overhang = self.text_styles().map(|style| style.font_size()).fold(0.0, f32::max) / 2.0;
+    match bounds.is_empty() {
+      true => None,
+      false => Some(bounds.with_outset((overhang, overhang)))
+    }
+  }
+
+  // positions are counted in UTF-16 code units (like JS strings), with each placeholder taking one
+  fn len(&self) -> usize {
+    self.runs.iter().map(|run| match run {
+      Run::Text{ text, .. } => text.encode_utf16().count(),
+      Run::Placeholder{ .. } => 1
+    }).sum()
+  }
+
+  /// The text position nearest to a point relative to the paragraph's top-left, and whether it
+  /// belongs with the character after it (downstream) rather than the one before.
+  pub fn position_at(&mut self, point: impl Into<Point>) -> (usize, bool) {
+    let found = self.paragraph().get_glyph_position_at_coordinate(point);
+    (found.position.max(0) as usize, found.affinity == Affinity::Downstream)
+  }
+
+  /// Selection boxes covering a range of text, one per line and direction run. Each spans the
+  /// full height of its line.
+  pub fn rects_for_range(&mut self, start: usize, end: usize) -> Vec<TextBox> {
+    match start < end {
+      true => self.paragraph().get_rects_for_range(start..end, RectHeightStyle::Max, RectWidthStyle::Tight),
+      false => vec![]
+    }
+  }
+
+  pub fn word_boundary(&mut self, index: usize) -> (usize, usize) {
+    let range = self.paragraph().get_word_boundary(index as u32);
+    (range.start, range.end)
+  }
+
+  /// The top of a caret placed before the character at `index`, and its height. Past the end
+  /// of a line (and in empty ones) the caret follows the last character on it, and at a soft
+  /// wrap it begins the next line.
+  pub fn caret(&mut self, index: usize) -> (Point, f32) {
+    let index = index.min(self.len());
+    let line = self.paragraph().get_line_metrics().into_iter().rev()
+      .find(|line| line.start_index <= index)
+      .map(|line| (line.start_index, line.end_index, line.left as f32,
+                   (line.baseline - line.ascent) as f32, (line.ascent + line.descent) as f32));
+    let (start, end, left, top, height) = match line {
+      Some(line) => line,
+      None => return (Point::default(), self.paragraph().height())
+    };
+
+    // use the leading edge of the character after the caret or the trailing edge of the one
+    // before it, whose sides depend on their direction
+    let after = match index < end {
+      true => self.rects_for_range(index, index + 1).first().map(|b| match b.direct {
+        TextDirection::LTR => b.rect.left,
+        TextDirection::RTL => b.rect.right
+      }),
+      false => None
+    };
+    let before = || match index > start {
+      true => self.rects_for_range(index - 1, index).last().map(|b| match b.direct {
+        TextDirection::LTR => b.rect.right,
+        TextDirection::RTL => b.rect.left
+      }),
+      false => None
+    };
+    let x = after.or_else(before).unwrap_or(left);
+    (Point::new(x, top), height)
+  }
+
+  /// [width, height, minIntrinsicWidth, maxIntrinsicWidth, alphabeticBaseline,
+  /// ideographicBaseline, longestLine, lineCount, didExceedMaxLines]
+  pub fn metrics(&mut self) -> Vec<f32> {
+    let paragraph = self.paragraph();
+    vec![
+      paragraph.max_width(), paragraph.height(),
+      paragraph.min_intrinsic_width(), paragraph.max_intrinsic_width(),
+      paragraph.alphabetic_baseline(), paragraph.ideographic_baseline(),
+      paragraph.longest_line(), paragraph.line_number() as f32,
+      if paragraph.did_exceed_max_lines() { 1.0 } else { 0.0 },
+    ]
+  }
+}
+
+fn same_face(a: &TextStyle, b: &TextStyle) -> bool {
+  a.font_style() == b.font_style() && a.font_families().iter().eq(b.font_families().iter())
+}
+
+// match the canvas' default font of 10px sans-serif in black
+fn default_style() -> TextStyle {
+  let mut style = TextStyle::new();
+  style.set_color(Color::BLACK);
+  style.set_font_size(10.0);
+  style.set_font_families(&["sans-serif"]);
+  style
+}
+
+fn parse_number(key: &str, value: &str) -> Result<f32> {
+  match value.trim().trim_end_matches("px").parse::<f32>() {
+    Ok(num) if num.is_finite() => Ok(num),
+    _ => Err(Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value)))
+  }
+}
+
+// a CSS length like the context's letterSpacing, or a bare number of pixels
+fn parse_spacing(key: &str, value: &str) -> Result<Spacing> {
+  let spacing = match value.trim().parse::<f32>() {
+    Ok(px) => Spacing::parse(&format!("{}px", px)),
+    Err(_) => Spacing::parse(value),
+  };
+  spacing.ok_or_else(|| Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value)))
+}
+
+fn parse_color(key: &str, value: &str) -> Result<Color> {
+  css_to_color(value).ok_or_else(|| Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value)))
+}
+
+/// Build the style for a run from its `fontFamily`, `fontSize`, `fontWeight`, `fontStyle`,
+/// `color`, `background`, `decoration`, `decorationColor`, `decorationStyle`, `letterSpacing`,
+/// `wordSpacing` and `lineHeight` entries. Anything left out keeps its default.
+pub fn to_text_style(map: &JsStrMap) -> Result<TextStyle> {
+  let mut style = default_style();
+  let mut font_style = style.font_style();
+  let mut spacing = (Spacing::default(), Spacing::default());
+  for (key, value) in map.iter() {
+    let invalid = || Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value));
+    match key.as_str() {
+      "fontFamily" => {
+        let families:Vec<String> = value.split(',')
+          .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+          .filter(|name| !name.is_empty())
+          .collect();
+        if families.is_empty() {
+          return Err(invalid())
+        }
+        style.set_font_families(&families);
+      },
+      "fontSize" => match parse_number(key, value)? {
+        size if size > 0.0 => { style.set_font_size(size); },
+        _ => return Err(invalid())
+      },
+      "fontWeight" => {
+        let weight = match value.as_str() {
+          "normal" => 400,
+          "bold" => 700,
+          _ => match value.parse::<i32>() {
+            Ok(weight) if (1..=1000).contains(&weight) => weight,
+            _ => return Err(invalid())
+          }
+        };
+        font_style = FontStyle::new(Weight::from(weight), Width::NORMAL, font_style.slant());
+      },
+      "fontStyle" => match value.as_str() {
+        "normal" | "italic" | "oblique" =>
+          font_style = FontStyle::new(font_style.weight(), Width::NORMAL, to_slant(value)),
+        _ => return Err(invalid())
+      },
+      "color" => { style.set_color(parse_color(key, value)?); },
+      "background" => {
+        let mut paint = Paint::default();
+        paint.set_color(parse_color(key, value)?);
+        style.set_background_color(&paint);
+      },
+      "decoration" => {
+        let mut lines = TextDecoration::NO_DECORATION;
+        for name in value.split_whitespace() {
+          lines |= match name {
+            "none" => TextDecoration::NO_DECORATION,
+            "underline" => TextDecoration::UNDERLINE,
+            "overline" => TextDecoration::OVERLINE,
+            "line-through" => TextDecoration::LINE_THROUGH,
+            _ => return Err(invalid())
+          };
+        }
+        style.decoration_mut().ty = lines;
+      },
+      "decorationColor" => { style.decoration_mut().color = parse_color(key, value)?; },
+      "decorationStyle" => {
+        style.decoration_mut().style = match value.as_str() {
+          "solid" => TextDecorationStyle::Solid,
+          "double" => TextDecorationStyle::Double,
+          "dotted" => TextDecorationStyle::Dotted,
+          "dashed" => TextDecorationStyle::Dashed,
+          "wavy" => TextDecorationStyle::Wavy,
+          _ => return Err(invalid())
+        };
+      },
+      "letterSpacing" => spacing.0 = parse_spacing(key, value)?,
+      "wordSpacing" => spacing.1 = parse_spacing(key, value)?,
+      "lineHeight" => match parse_number(key, value)? {
+        height if height > 0.0 => { style.set_height(height).set_height_override(true); },
+        _ => return Err(invalid())
+      },
+      _ => {}
+    }
+  }
+  // em lengths depend on the run's font size, which may come later in the map
+  let size = style.font_size();
+  style.set_letter_spacing(spacing.0.to_px(size));
+  style.set_word_spacing(spacing.1.to_px(size));
+  style.set_font_style(font_style);
+  Ok(style)
+}
+
+/// Read the `width`, `maxLines`, `ellipsis` and `align` entries of an options map.
+pub fn to_paragraph_options(map: &JsStrMap) -> Result<(Option<f32>, ParagraphStyle)> {
+  let mut graf_style = ParagraphStyle::new();
+  graf_style.set_text_style(&default_style());
+  let mut width = None;
+  for (key, value) in map.iter() {
+    let invalid = || Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value));
+    match key.as_str() {
+      "width" => match parse_number(key, value)? {
+        w if w >= 0.0 => width = Some(w),
+        _ => return Err(invalid())
+      },
+      "maxLines" => match value.parse::<usize>() {
+        Ok(0) => {}, // unlimited, as by default
+        Ok(lines) => { graf_style.set_max_lines(lines); },
+        Err(_) => return Err(invalid())
+      },
+      "ellipsis" => { graf_style.set_ellipsis(value); },
+      "align" => match to_text_align(value) {
+        Some(align) => { graf_style.set_text_align(align); },
+        None => return Err(invalid())
+      },
+      _ => {}
+    }
+  }
+  Ok((width, graf_style))
+}
+
+/// Build a placeholder `width`×`height` in size from the `alignment` ("baseline",
+/// "above-baseline", "below-baseline", "top", "bottom" or "middle"), `baseline` ("alphabetic" or
+/// "ideographic") and `baselineOffset` entries of an options map. By default its bottom edge sits
+/// on the baseline, like an inline image.
+pub fn to_placeholder_style(width: f32, height: f32, map: &JsStrMap) -> Result<PlaceholderStyle> {
+  if !(width.is_finite() && height.is_finite() && width >= 0.0 && height >= 0.0) {
+    return Err(Error::new(ErrorCode::Type, format!("Invalid placeholder size: {}×{}", width, height)))
+  }
+  let mut style = PlaceholderStyle::new(width, height, PlaceholderAlignment::Baseline, TextBaseline::Alphabetic, height);
+  for (key, value) in map.iter() {
+    let invalid = || Error::new(ErrorCode::Type, format!("Invalid {}: {}", key, value));
+    match key.as_str() {
+      "alignment" => {
+        style.alignment = match value.as_str() {
+          "baseline" => PlaceholderAlignment::Baseline,
+          "above-baseline" => PlaceholderAlignment::AboveBaseline,
+          "below-baseline" => PlaceholderAlignment::BelowBaseline,
+          "top" => PlaceholderAlignment::Top,
+          "bottom" => PlaceholderAlignment::Bottom,
+          "middle" => PlaceholderAlignment::Middle,
+          _ => return Err(invalid())
+        };
+      },
+      "baseline" => {
+        style.baseline = match value.as_str() {
+          "alphabetic" => TextBaseline::Alphabetic,
+          "ideographic" => TextBaseline::Ideographic,
+          _ => return Err(invalid())
+        };
+      },
+      "baselineOffset" => { style.baseline_offset = parse_number(key, value)?; },
+      _ => {}
+    }
+  }
+  Ok(style)
+}
+
+//
+// Javascript Methods
+//
+
+#[no_mangle]
+pub extern "C" fn new_rich_text() -> *mut RichText {
+  Box::into_raw(Box::new(RichText::default()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_rich_text(text: *mut RichText) {
+  if !text.is_null() {
+    drop(Box::from_raw(text));
+  }
+}
+
+/// Append a run of text styled by the entries in `style` (see `to_text_style`). Unrecognized
+/// values are a Type error and leave the paragraph unchanged.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_add_run(text: *mut RichText, run: *mut c_char, style: *mut JsStrMap) -> u32 {
+  status(to_text_style(&*style).map(|style| (*text).add_run(&char_to_string(run), style)))
+}
+
+/// Append an inline placeholder (see `to_placeholder_style`). If `image` isn't null it is drawn
+/// into the placeholder's box, so it must have finished loading.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_add_placeholder(text: *mut RichText, width: f32, height: f32, options: *mut JsStrMap, image: *mut Image) -> u32 {
+  let image = match image.as_ref() {
+    Some(image) if image.image.is_none() =>
+      return status(Err(Error::new(ErrorCode::InvalidState, "Cannot draw incomplete image (has it finished loading?)"))),
+    Some(image) => image.image.clone(),
+    None => None
+  };
+  status(to_placeholder_style(width, height, &*options).map(|style| (*text).add_placeholder(style, image)))
+}
+
+/// The laid-out box of each placeholder as a flat list of [x, y, width, height] values.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_placeholder_rects(text: *mut RichText) -> *mut JsF32Array {
+  let rects = (*text).placeholder_rects();
+  let nums = rects.iter().flat_map(|r| [r.left, r.top, r.width(), r.height()]).collect();
+  Box::into_raw(Box::new(nums))
+}
+
+/// Replace the layout options with those in `options` (see `to_paragraph_options`).
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_set_options(text: *mut RichText, options: *mut JsStrMap) -> u32 {
+  status(to_paragraph_options(&*options).map(|(width, graf_style)| (*text).set_options(width, graf_style)))
+}
+
+/// The paragraph's layout metrics, in the order listed by `RichText::metrics`.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_metrics(text: *mut RichText) -> *mut JsF32Array {
+  Box::into_raw(Box::new((*text).metrics()))
+}
+
+/// The text position nearest to (x, y) as [index, downstream], where `downstream` is 1 if the
+/// position belongs with the character after it. Positions count UTF-16 code units, with each
+/// placeholder taking one.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_position_at(text: *mut RichText, x: f32, y: f32) -> *mut JsF32Array {
+  let (index, downstream) = (*text).position_at((x, y));
+  Box::into_raw(Box::new(vec![index as f32, if downstream { 1.0 } else { 0.0 }]))
+}
+
+/// The selection boxes for the text between `start` and `end` as a flat list of
+/// [x, y, width, height, rtl] values, where `rtl` is 1 for right-to-left runs.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_rects_for_range(text: *mut RichText, start: u32, end: u32) -> *mut JsF32Array {
+  let boxes = (*text).rects_for_range(start as usize, end as usize);
+  let nums = boxes.iter().flat_map(|b| {
+    let rtl = if b.direct == TextDirection::RTL { 1.0 } else { 0.0 };
+    [b.rect.left, b.rect.top, b.rect.width(), b.rect.height(), rtl]
+  }).collect();
+  Box::into_raw(Box::new(nums))
+}
+
+/// The [start, end] of the word containing `index`.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_word_boundary(text: *mut RichText, index: u32) -> *mut JsF32Array {
+  let (start, end) = (*text).word_boundary(index as usize);
+  Box::into_raw(Box::new(vec![start as f32, end as f32]))
+}
+
+/// The caret for `index` as [x, y, height], with (x, y) at its top.
+#[no_mangle]
+pub unsafe extern "C" fn rich_text_get_caret(text: *mut RichText, index: u32) -> *mut JsF32Array {
+  let (top, height) = (*text).caret(index as usize);
+  Box::into_raw(Box::new(vec![top.x, top.y, height]))
+}
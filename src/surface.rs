@@ -1,35 +1,61 @@
-use skia_safe::{gpu::{gl::FramebufferInfo, BackendRenderTarget, DirectContext}, Point, Surface};
+#[cfg(feature = "gpu")]
+use skia_safe::gpu::{gl::FramebufferInfo, BackendRenderTarget, DirectContext};
+use skia_safe::{BlendMode, ColorSpace, IRect, Paint, Rect, Surface};
+use skia_safe::canvas::SrcRectConstraint::Strict;
 
-use crate::context::{api::reset, Context2D};
+use crate::context::{api::reset, jstypes::JsF32Array, Context2D};
+use crate::error::{status, Error, ErrorCode};
 
+#[cfg(feature = "gpu")]
 pub struct GpuState {
   context: DirectContext,
   framebuffer_info: FramebufferInfo,
 }
 
+/// Where a SurfaceState's pixels live.
+///
+/// `Gpu` renders into the framebuffer of the WebGL context that was current when [init_surface]
+/// was called. `Raster` renders into CPU memory and needs no GL context at all, so it can be used
+/// from Web Workers and native builds.
+pub enum Backend {
+  #[cfg(feature = "gpu")]
+  Gpu(GpuState),
+  Raster,
+}
+
 /// This struct holds the state of the Rust application between JS calls.
 ///
 /// It is created by [init] and passed to the other exported functions. Note that rust-skia data
 /// structures are not thread safe, so a state must not be shared between different Web Workers.
 pub struct SurfaceState {
-  gpu_state: GpuState,
+  backend: Backend,
   surface: Surface,
+  presented: Option<IRect>, // the area redrawn by the last call to [render_to_surface]
 }
 
 impl SurfaceState {
-  fn new(gpu_state: GpuState, surface: Surface) -> Self {
-    SurfaceState { gpu_state, surface }
+  fn new(backend: Backend, surface: Surface) -> Self {
+    SurfaceState { backend, surface, presented: None }
   }
 
   pub fn set_surface(&mut self, surface: Surface) {
       self.surface = surface;
   }
+
+  pub fn surface(&mut self) -> &mut Surface {
+    &mut self.surface
+  }
+
+  pub fn is_raster(&self) -> bool {
+    matches!(self.backend, Backend::Raster)
+  }
 }
 
 
 /// Create the GPU state from the JavaScript WebGL context.
 ///
 /// This needs to be done once per WebGL context.
+#[cfg(feature = "gpu")]
 fn create_gpu_state() -> GpuState {
   let interface = skia_safe::gpu::gl::Interface::new_native().unwrap();
   let context = skia_safe::gpu::DirectContext::new_gl(interface, None).unwrap();
@@ -52,7 +78,8 @@ fn create_gpu_state() -> GpuState {
 
 
 /// Create the Skia surface that will be used for rendering.
-fn create_surface(gpu_state: &mut GpuState, width: i32, height: i32) -> Surface {
+#[cfg(feature = "gpu")]
+fn create_gpu_surface(gpu_state: &mut GpuState, width: i32, height: i32) -> Surface {
   let backend_render_target =
       BackendRenderTarget::new_gl((width, height), 1, 8, gpu_state.framebuffer_info);
 
@@ -67,15 +94,54 @@ fn create_surface(gpu_state: &mut GpuState, width: i32, height: i32) -> Surface
   .unwrap()
 }
 
+/// Create a CPU-backed surface, clamping to a single pixel so a zero-sized canvas stays valid.
+fn create_raster_surface(width: i32, height: i32) -> Surface {
+  Surface::new_raster_n32_premul((width.max(1), height.max(1))).unwrap()
+}
+
+fn create_surface(backend: &mut Backend, width: i32, height: i32) -> Surface {
+  match backend {
+    #[cfg(feature = "gpu")]
+    Backend::Gpu(gpu_state) => create_gpu_surface(gpu_state, width, height),
+    Backend::Raster => create_raster_surface(width, height),
+  }
+}
+
 /// Initialize the renderer.
 ///
-/// This is called from JS after the WebGL context has been created.
+/// This is called from JS after the WebGL context has been created. Builds without the `gpu`
+/// feature fall back to a raster surface.
 #[no_mangle]
 pub extern "C" fn init_surface(width: i32, height: i32) -> *mut SurfaceState {
-    let mut gpu_state = create_gpu_state();
-    let surface = create_surface(&mut gpu_state, width, height);
-    let state = SurfaceState::new(gpu_state, surface);
-    Box::into_raw(Box::new(state))
+  #[cfg(feature = "gpu")]
+  let mut backend = Backend::Gpu(create_gpu_state());
+  #[cfg(not(feature = "gpu"))]
+  let mut backend = Backend::Raster;
+
+  let surface = create_surface(&mut backend, width, height);
+  let state = SurfaceState::new(backend, surface);
+  Box::into_raw(Box::new(state))
+}
+
+/// Initialize a renderer that draws on the CPU.
+///
+/// Unlike [init_surface] this doesn't require a current WebGL context.
+#[no_mangle]
+pub extern "C" fn init_raster_surface(width: i32, height: i32) -> *mut SurfaceState {
+  let mut backend = Backend::Raster;
+  let surface = create_surface(&mut backend, width, height);
+  let state = SurfaceState::new(backend, surface);
+  Box::into_raw(Box::new(state))
+}
+
+#[no_mangle]
+pub extern "C" fn surface_is_raster(state: *mut SurfaceState) -> u32 {
+  let state = unsafe { state.as_ref() }.expect("got an invalid state pointer");
+  if state.is_raster() {
+    1
+  } else {
+    0
+  }
 }
 
 /// Resize the Skia surface
@@ -84,20 +150,58 @@ pub extern "C" fn init_surface(width: i32, height: i32) -> *mut SurfaceState {
 #[no_mangle]
 pub extern "C" fn resize_surface(state: *mut SurfaceState, width: i32, height: i32) {
   let state = unsafe { state.as_mut() }.expect("got an invalid state pointer");
-  let surface = create_surface(&mut state.gpu_state, width, height);
+  let surface = create_surface(&mut state.backend, width, height);
   state.set_surface(surface);
 }
 
 
+/// Present the context's contents, returning a non-zero error code if they couldn't be drawn.
+///
+/// Only the area drawn to since the last present is redrawn (see [surface_get_presented_rect]);
+/// if nothing was drawn the surface is left untouched.
 #[no_mangle]
-pub extern "C" fn render_to_surface(state: *mut SurfaceState, cx: *mut Context2D) {
+pub extern "C" fn render_to_surface(state: *mut SurfaceState, cx: *mut Context2D) -> u32 {
   unsafe {
-    if let Some(picture) = (*cx).get_image(None) {
-      (*state).surface.canvas().draw_image(picture, Point{x: 0.0, y: 0.0}, None);
-      (*state).surface.flush();
-      reset(cx);
-    } else {
-      panic!("no image")
-    }
+    // the damage is only cleared once it has been redrawn, so a failed present is retried in full
+    let damage = match (*cx).damage() {
+      Some(damage) => Rect::from(damage),
+      None => {
+        (*state).presented = None;
+        reset(cx);
+        return status(Ok(()))
+      }
+    };
+
+    // the display surface isn't color managed, so wide-gamut contexts are converted to sRGB first
+    let image = (*cx).get_image(None).and_then(|image| image.new_color_space(ColorSpace::new_srgb()));
+    status(match image {
+      Some(image) => {
+        let canvas = (*state).surface.canvas();
+        canvas.save();
+        canvas.clip_rect(damage, None, None);
+        // replace rather than blend, so cleared pixels erase what was presented before
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::Src);
+        canvas.draw_image_rect(image, Some((&damage, Strict)), damage, &paint);
+        canvas.restore();
+        (*state).surface.flush();
+        (*state).presented = (*cx).take_damage();
+        reset(cx);
+        Ok(())
+      },
+      None => Err(Error::new(ErrorCode::InvalidState, "Could not render an empty canvas"))
+    })
   }
-}
\ No newline at end of file
+}
+
+/// The area redrawn by the last present as `[x, y, width, height]` in surface pixels (from the
+/// top left), or an empty array if nothing needed to be redrawn.
+#[no_mangle]
+pub extern "C" fn surface_get_presented_rect(state: *mut SurfaceState) -> *mut JsF32Array {
+  let state = unsafe { state.as_ref() }.expect("got an invalid state pointer");
+  let rect = match state.presented {
+    Some(r) => vec![r.left as f32, r.top as f32, r.width() as f32, r.height() as f32],
+    None => vec![]
+  };
+  Box::into_raw(Box::new(rect))
+}
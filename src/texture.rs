@@ -104,4 +104,11 @@ pub unsafe extern "C" fn new_canvas_texture(path: *mut Path2D, color: *mut c_cha
   };
   let canvas_texture = Box::new(CanvasTexture{ texture:Arc::new(Mutex::new(texture)) });
   Box::into_raw(canvas_texture)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn drop_canvas_texture(t: *mut CanvasTexture) {
+  if !t.is_null() {
+    drop(Box::from_raw(t));
+  }
 }
\ No newline at end of file
@@ -16,17 +16,19 @@ use skia_safe::{Font, FontMgr, FontMetrics, FontArguments, Typeface, Data, Paint
 use skia_safe::font_style::{FontStyle, Weight, Width, Slant};
 use skia_safe::font_arguments::{VariationPosition, variation_position::{Coordinate}};
 use skia_safe::textlayout::{FontCollection, TypefaceFontProvider, TextStyle, TextAlign,
-                            TextDirection, ParagraphStyle, Paragraph, ParagraphBuilder};
+                            TextDirection, ParagraphStyle, Paragraph, ParagraphBuilder,
+                            RectHeightStyle, RectWidthStyle};
 use crate::context::jstypes::{JsAnyArray, JsBuffer, JsStrMap};
 use crate::FONT_LIBRARY;
 use crate::utils::*;
 use crate::context::CanvasState;
+use crate::error::{status, Error, ErrorCode};
 
 //
 // Text layout and metrics
 //
 
-const GALLEY:f32 = 100_000.0;
+pub const GALLEY:f32 = 100_000.0;
 // const GALLEY:f32 = INFINITY;
 
 pub struct Typesetter{
@@ -92,6 +94,13 @@ impl Typesetter{
     (paragraph, offset.into())
   }
 
+  /// Measure the text the way `CanvasRenderingContext2D.measureText` does. The first entry holds
+  /// [width, actualBoundingBoxLeft, actualBoundingBoxRight, fontBoundingBoxAscent,
+  /// fontBoundingBoxDescent, actualBoundingBoxAscent, actualBoundingBoxDescent, emHeightAscent,
+  /// emHeightDescent, hangingBaseline, alphabeticBaseline, ideographicBaseline], all measured from
+  /// the point the text is drawn at, with ascents and baselines positive upward (and boxes'
+  /// left sides positive leftward). The rest hold [left, top, width, height, baseline, start, end]
+  /// for each line.
   pub fn metrics(&mut self) -> Vec<Vec<f32>>{
     let (paragraph, _) = self.layout(&Paint::default());
     let font_metrics = self.char_style.font_metrics();
@@ -99,12 +108,18 @@ impl Typesetter{
     let hang = get_baseline_offset(&font_metrics, Baseline::Hanging) - offset;
     let norm = get_baseline_offset(&font_metrics, Baseline::Alphabetic) - offset;
     let ideo = get_baseline_offset(&font_metrics, Baseline::Ideographic) - offset;
-    let ascent = norm - font_metrics.ascent;
-    let descent = font_metrics.descent - norm;
     let alignment = get_alignment_factor(&self.graf_style) * self.width;
 
+    // the font and em boxes come from the face actually being drawn with, like in browsers
+    let font = self.font();
+    let face_metrics = font.as_ref().map_or(font_metrics, |font| font.metrics().1);
+    let ascent = norm - face_metrics.ascent;
+    let descent = face_metrics.descent - norm;
+    let (em_ascent, em_descent) = get_em_height(&face_metrics, self.char_style.font_size());
+    let (em_ascent, em_descent) = (norm + em_ascent, em_descent - norm);
+
     if paragraph.line_number() == 0 {
-      return vec![vec![0.0, 0.0, 0.0, 0.0, 0.0, ascent, descent, ascent, descent, hang, norm, ideo]]
+      return vec![vec![0.0, 0.0, 0.0, ascent, descent, 0.0, 0.0, em_ascent, em_descent, hang, norm, ideo]]
     }
 
     // find the bounds and text-range for each individual line
@@ -120,15 +135,18 @@ impl Typesetter{
     }).collect();
 
     // take their union to find the bounds for the whole text run
-    let (bounds, chars) = line_rects.iter().fold((Rect::new_empty(), 0), |(union, indices), (rect, range, _)|
-      (Rect::join2(union, rect), range.end)
-    );
+    let bounds = line_rects.iter().fold(Rect::new_empty(), |union, (rect, _, _)| Rect::join2(union, rect));
+
+    let ink = match self.ink_bounds(&paragraph) {
+      ink if ink.is_empty() => ink,
+      ink => ink.with_offset((alignment, offset - origin as f32))
+    };
 
     // return a list-of-lists whose first entry is the whole-run font metrics and subsequent entries are
     // line-rect/range values (with the js side responsible for restructuring the whole bundle)
     let mut results = vec![vec![
-      bounds.width(), bounds.left, bounds.right, -bounds.top, bounds.bottom,
-      ascent, descent, ascent, descent, hang, norm, ideo
+      bounds.width(), -ink.left, ink.right, ascent, descent,
+      -ink.top, ink.bottom, em_ascent, em_descent, hang, norm, ideo
     ]];
     line_rects.iter().for_each(|(rect, range, baseline)|{
       results.push(vec![rect.left, rect.top, rect.width(), rect.height(),
@@ -137,11 +155,72 @@ impl Typesetter{
     results
   }
 
-  pub fn path(&mut self) -> Option<SkPath> {
+  // The glyphs' inked area in paragraph coordinates. Each character is measured in the face that
+  // covers it (falling back like the paragraph does) and placed where the layout put it, so
+  // spacing, kerning and fallback fonts are reflected; ligatures and other substitutions are
+  // measured as the characters they replace.
+  fn ink_bounds(&mut self, paragraph: &Paragraph) -> Rect {
+    let families:Vec<String> = self.char_style.font_families().iter().map(|fam| fam.to_string()).collect();
+    let (style, size) = (self.char_style.font_style(), self.char_style.font_size());
+    let faces = self.typefaces.find_typefaces(&families, style);
+    let lines = paragraph.get_line_metrics();
+
+    let mut ink = Rect::new_empty();
+    let mut idx = 0; // the paragraph counts positions in UTF-16 code units
+    for c in self.text.chars() {
+      let range = idx..idx + c.len_utf16();
+      idx = range.end;
+      let face = match faces.iter().find(|face| face.unichar_to_glyph(c as i32) != 0) {
+        Some(face) => face.clone(),
+        None => match self.typefaces.default_fallback_char(c as i32, style, "") {
+          Some(face) => face,
+          None => continue
+        }
+      };
+      let (_, glyph) = Font::from_typeface(face, size).measure_str(c.encode_utf8(&mut [0; 4]), None);
+      if glyph.is_empty() {
+        continue
+      }
+      for text_box in paragraph.get_rects_for_range(range, RectHeightStyle::Tight, RectWidthStyle::Tight) {
+        let middle = text_box.rect.center_y() as f64;
+        if let Some(line) = lines.iter().find(|line| middle < line.baseline + line.descent) {
+          ink = Rect::join2(ink, glyph.with_offset((text_box.rect.left, line.baseline as f32)));
+        }
+      }
+    }
+    ink
+  }
+
+  /// The box of each laid-out line (from ascent to descent), relative to the point the text is
+  /// drawn at.
+  pub fn line_bounds(&mut self) -> Vec<Rect> {
+    let (paragraph, offset) = self.layout(&Paint::default());
+    paragraph.get_line_metrics().iter().map(|line| {
+      Rect::new(line.left as f32, (line.baseline - line.ascent) as f32,
+                (line.left + line.width) as f32, (line.baseline + line.descent) as f32).with_offset(offset)
+    }).collect()
+  }
+
+  /// A conservative box around the laid-out glyphs, relative to the point the text is drawn at.
+  pub fn bounds(&mut self) -> Option<Rect> {
+    let bounds = self.line_bounds().iter().fold(Rect::new_empty(), |union, line| Rect::join2(union, line));
+    // leave room for glyphs that overhang their advance (italics, swashes, etc.)
+    let overhang = self.char_style.font_size() / 2.0;
+    match bounds.is_empty() {
+      true => None,
+      false => Some(bounds.with_outset((overhang, overhang)))
+    }
+  }
+
+  // the first typeface matching the font's families and style
+  fn font(&self) -> Option<Font> {
     let families:Vec<String> = self.char_style.font_families().iter().map(|fam| fam.to_string()).collect();
     let matches = self.typefaces.find_typefaces(&families, self.char_style.font_style());
-    if let Some(typeface) = matches.first(){
-      let font = Font::from_typeface(typeface, self.char_style.font_size());
+    matches.first().map(|typeface| Font::from_typeface(typeface, self.char_style.font_size()))
+  }
+
+  pub fn path(&mut self) -> Option<SkPath> {
+    if let Some(font) = self.font(){
       let (leading, metrics) = font.metrics();
       let (width, bounds) = font.measure_str(&self.text, None);
       let offset = (
@@ -184,6 +263,13 @@ pub extern "C" fn new_font_spec() -> *mut FontSpec {
   Box::into_raw(spec)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn drop_font_spec(spec: *mut FontSpec) {
+  if !spec.is_null() {
+    drop(Box::from_raw(spec));
+  }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn font_spec_set_families(spec: *mut FontSpec, arr: *mut JsAnyArray) {
   let families = (*arr).iter().map(|v| {
@@ -331,6 +417,75 @@ pub fn from_width(width:Width) -> String{
   }.to_string()
 }
 
+/// A CSS length for `letterSpacing` or `wordSpacing`. Font-relative lengths keep their unit so
+/// they can be resolved against whatever the font size is when text is laid out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spacing{
+  pub css: String,
+  value: f32,
+  em: bool,
+}
+
+impl Default for Spacing{
+  fn default() -> Self {
+    Spacing{ css: "0px".to_string(), value: 0.0, em: false }
+  }
+}
+
+impl Spacing{
+  /// Parse an absolute or `em` length (percentages aren't allowed).
+  pub fn parse(css:&str) -> Option<Self>{
+    let css = css.trim().to_lowercase();
+    // split off the unit from the end, since the number itself may contain an exponent
+    let (num, unit) = css.split_at(css.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len());
+    let value = num.parse::<f32>().ok().filter(|n| n.is_finite())?;
+    let scale = match unit {
+      "px" => 1.0,
+      "pt" => 4.0 / 3.0,
+      "pc" => 16.0,
+      "in" => 96.0,
+      "cm" => 96.0 / 2.54,
+      "mm" => 96.0 / 25.4,
+      "q" => 96.0 / 101.6,
+      "em" => 1.0,
+      // the unitless zero is the only bare number CSS accepts
+      "" if value == 0.0 => 1.0,
+      _ => return None
+    };
+    Some(Spacing{ value: value * scale, em: unit == "em", css })
+  }
+
+  pub fn to_px(&self, font_size:f32) -> f32{
+    match self.em {
+      true => self.value * font_size,
+      false => self.value
+    }
+  }
+}
+
+/// The OpenType features that `fontVariantCaps` turns on.
+pub fn to_caps_features(caps_name:&str) -> Option<&'static [&'static str]>{
+  let features:&[&str] = match caps_name{
+    "normal" => &[],
+    "small-caps" => &["smcp"],
+    "all-small-caps" => &["c2sc", "smcp"],
+    "petite-caps" => &["pcap"],
+    "all-petite-caps" => &["c2pc", "pcap"],
+    "unicase" => &["unic"],
+    "titling-caps" => &["titl"],
+    _ => return None
+  };
+  Some(features)
+}
+
+pub fn is_font_kerning(mode_name:&str) -> bool{
+  matches!(mode_name, "auto" | "normal" | "none")
+}
+
+pub fn is_text_rendering(mode_name:&str) -> bool{
+  matches!(mode_name, "auto" | "optimizeSpeed" | "optimizeLegibility" | "geometricPrecision")
+}
+
 pub fn to_text_align(mode_name:&str) -> Option<TextAlign>{
   let mode = match mode_name.to_lowercase().as_str(){
     "left" => TextAlign::Left,
@@ -410,6 +565,16 @@ pub fn get_baseline_offset(metrics: &FontMetrics, mode:Baseline) -> f32 {
   }
 }
 
+/// The font's ascent and descent (both positive) scaled so that together they span exactly one
+/// em, which is how browsers derive `emHeightAscent` and `emHeightDescent`.
+pub fn get_em_height(metrics: &FontMetrics, size: f32) -> (f32, f32) {
+  let height = metrics.descent - metrics.ascent;
+  match height > 0.0 {
+    true => (-metrics.ascent / height * size, metrics.descent / height * size),
+    false => (size, 0.0)
+  }
+}
+
 #[derive(PartialEq, Eq, Hash)]
 struct CollectionKey{ families:String, weight:i32, slant:Slant }
 
@@ -620,7 +785,7 @@ impl FontLibrary{
 //
 
 #[no_mangle]
-pub unsafe extern "C" fn add_font_family(fontBuf: *mut JsBuffer, alias: *mut c_char) {
+pub unsafe extern "C" fn add_font_family(fontBuf: *mut JsBuffer, alias: *mut c_char) -> u32 {
   let alias = if alias.is_null() { 
     None
   } else {
@@ -632,9 +797,10 @@ pub unsafe extern "C" fn add_font_family(fontBuf: *mut JsBuffer, alias: *mut c_c
       // register the typeface
       let mut library = FONT_LIBRARY.lock().unwrap();
       library.add_typeface(font, alias);
+      status(Ok(()))
     },
     None => {
-      panic!("Could not decode font data")
+      status(Err(Error::new(ErrorCode::Encoding, "Could not decode font data")))
     }
   }
 } 
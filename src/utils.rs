@@ -7,12 +7,17 @@ use std::{cmp, os::raw::c_char};
 use std::f32::consts::PI;
 use core::ops::Range;
 use css_color::Rgba;
+use once_cell::sync::Lazy;
 use skia_safe::{
-  Path, Matrix, Point, Color, Color4f, RGB, Rect, FontArguments,
+  Path, Matrix, Point, Color, Color4f, ColorSpace, ColorType, Data, Paint, RGB, Rect, FontArguments,
+  image::BitDepth,
   font_style::{FontStyle, Weight, Width, Slant},
-  font_arguments::{VariationPosition, variation_position::{Coordinate}}
+  font_arguments::{VariationPosition, variation_position::{Coordinate}},
+  DateTime
 };
 
+use crate::error::{Error, ErrorCode};
+
 
 //
 // meta-helpers
@@ -52,6 +57,35 @@ pub fn string_idx_range(text: &str, start_idx: usize, end_idx: usize) -> Range<u
 }
 
 
+/// Convert a JS timestamp (milliseconds since the epoch) to a UTC calendar date
+pub fn to_date_time(ms: f64) -> DateTime {
+  let secs = (ms / 1000.0).floor() as i64;
+  let days = secs.div_euclid(86400);
+  let time = secs.rem_euclid(86400);
+
+  // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  DateTime{
+    time_zone_minutes: 0,
+    year: year as u16,
+    month: month as u8,
+    day_of_week: (days + 4).rem_euclid(7) as u8, // 1970-01-01 was a Thursday
+    day: day as u8,
+    hour: (time / 3600) as u8,
+    minute: (time / 60 % 60) as u8,
+    second: (time % 60) as u8,
+  }
+}
+
 //
 // Colors
 //
@@ -63,7 +97,93 @@ pub fn css_to_color<'a>(css:&str) -> Option<Color> {
       (green*255.0).round() as u8,
       (blue*255.0).round() as u8,
     )
-  )
+  ).or_else(|| css_to_color4f(css).map(|color| color.to_color()))
+}
+
+/// Parse a CSS color into extended sRGB. Unlike [css_to_color] this keeps `color(display-p3 …)`
+/// values that lie outside of sRGB, so they can still be reproduced on a display-p3 canvas.
+pub fn css_to_color4f(css:&str) -> Option<Color4f> {
+  if let Ok(Rgba{red, green, blue, alpha}) = css.parse::<Rgba>() {
+    let color:Color4f = Color::from_argb(
+      (alpha*255.0).round() as u8, (red*255.0).round() as u8, (green*255.0).round() as u8, (blue*255.0).round() as u8
+    ).into();
+    return Some(color)
+  }
+
+  // color(<space> r g b [/ alpha]), with components given as numbers or percentages
+  let css = css.trim().to_lowercase();
+  let args = css.strip_prefix("color(")?.strip_suffix(')')?;
+  let (channels, alpha) = match args.split_once('/') {
+    Some((channels, alpha)) => (channels, Some(alpha)),
+    None => (args, None)
+  };
+  let component = |arg: &str| match arg.strip_suffix('%') {
+    Some(pct) => pct.trim().parse::<f32>().ok().map(|n| n / 100.0),
+    None => arg.trim().parse::<f32>().ok(),
+  };
+  let mut words = channels.split_whitespace();
+  let space = words.next()?;
+  let rgb = words.map(component).collect::<Option<Vec<f32>>>()?;
+  let alpha = match alpha {
+    Some(alpha) => component(alpha)?.clamp(0.0, 1.0),
+    None => 1.0
+  };
+  match (space, rgb.as_slice()) {
+    ("srgb", &[r, g, b]) => Some(Color4f::new(r, g, b, alpha)),
+    ("display-p3", &[r, g, b]) => {
+      let [r, g, b] = convert_gamut([r, g, b], &P3_TO_SRGB);
+      Some(Color4f::new(r, g, b, alpha))
+    },
+    _ => None
+  }
+}
+
+/// Serialize an extended-sRGB color, switching to `color(display-p3 …)` syntax when it can't be
+/// expressed in sRGB.
+pub fn color4f_to_css(color:&Color4f) -> String {
+  let Color4f{r, g, b, a} = *color;
+  let in_srgb = [r, g, b].iter().all(|c| (-0.0005..=1.0005).contains(c));
+  match in_srgb {
+    true => color_to_css(&color.to_color()).unwrap_or_default(),
+    false => {
+      let fmt = |n: f32| {
+        let n = format!("{:.4}", n);
+        n.trim_end_matches('0').trim_end_matches('.').to_string()
+      };
+      let [r, g, b] = convert_gamut([r, g, b], &SRGB_TO_P3);
+      match a < 1.0 {
+        true => format!("color(display-p3 {} {} {} / {})", fmt(r), fmt(g), fmt(b), fmt(a)),
+        false => format!("color(display-p3 {} {} {})", fmt(r), fmt(g), fmt(b)),
+      }
+    }
+  }
+}
+
+// linear-light conversions between the sRGB and Display P3 primaries (both D65)
+const P3_TO_SRGB: [[f32; 3]; 3] = [
+  [ 1.2249401, -0.2249404,  0.0000000],
+  [-0.0420569,  1.0420571,  0.0000000],
+  [-0.0196376, -0.0786361,  1.0982735],
+];
+const SRGB_TO_P3: [[f32; 3]; 3] = [
+  [0.8224621, 0.1775380, 0.0000000],
+  [0.0331941, 0.9668058, 0.0000000],
+  [0.0170827, 0.0723974, 0.9105199],
+];
+
+/// Move a color between two gamuts that share sRGB's transfer function (as Display P3 does),
+/// extending the curve symmetrically to negative values.
+fn convert_gamut(rgb: [f32; 3], matrix: &[[f32; 3]; 3]) -> [f32; 3] {
+  let to_linear = |c: f32| match c.abs() <= 0.04045 {
+    true => c / 12.92,
+    false => c.signum() * ((c.abs() + 0.055) / 1.055).powf(2.4)
+  };
+  let from_linear = |c: f32| match c.abs() <= 0.0031308 {
+    true => c * 12.92,
+    false => c.signum() * (1.055 * c.abs().powf(1.0 / 2.4) - 0.055)
+  };
+  let lin = rgb.map(to_linear);
+  matrix.map(|row| from_linear(row[0] * lin[0] + row[1] * lin[1] + row[2] * lin[2]))
 }
 
 pub fn color_to_css(color:&Color) -> Result<String, ()> {
@@ -128,6 +248,136 @@ pub fn from_filter_quality(mode:FilterQuality) -> String{
 //
 
 use skia_safe::{TileMode, TileMode::{Decal, Repeat}};
+
+/// The `PredefinedColorSpace` values a context or `ImageData` can use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PredefinedColorSpace{
+  Srgb, DisplayP3
+}
+
+// Display P3's primaries, as the matrix Skia uses to convert them to XYZ D50
+const DISPLAY_P3_TO_XYZD50: [f32; 9] = [
+  0.515102, 0.291965, 0.157153,
+  0.241182, 0.692236, 0.0665819,
+  -0.00104941, 0.0418818, 0.784378,
+];
+
+// skia-safe doesn't bind SkColorSpace::MakeRGB, so start from sRGB's serialized form (a header,
+// the transfer function, then the gamut) and swap in the P3 gamut. That layout is private to
+// Skia, so the result is checked against a known P3 color and left out if it doesn't match.
+static DISPLAY_P3: Lazy<Option<ColorSpace>> = Lazy::new(|| {
+  let mut bytes = ColorSpace::new_srgb().serialize().as_bytes().to_vec();
+  let gamut = bytes.len().checked_sub(DISPLAY_P3_TO_XYZD50.len() * 4)?;
+  for (i, value) in DISPLAY_P3_TO_XYZD50.iter().enumerate() {
+    bytes[gamut + i * 4..gamut + i * 4 + 4].copy_from_slice(&value.to_ne_bytes());
+  }
+  let p3 = ColorSpace::deserialize(Data::new_copy(&bytes));
+
+  // P3's red lies outside sRGB, at roughly (1.0931, -0.2267, -0.1501) in extended sRGB
+  let mut paint = Paint::default();
+  paint.set_color4f(Color4f::new(1.0, 0.0, 0.0, 1.0), Some(&p3));
+  let red = paint.color4f();
+  let expected = [1.0931, -0.2267, -0.1501];
+  let matches = [red.r, red.g, red.b].iter().zip(expected).all(|(a, b)| (a - b).abs() < 0.01);
+  match !p3.is_srgb() && matches {
+    true => Some(p3),
+    false => None
+  }
+});
+
+impl PredefinedColorSpace{
+  /// The Skia color space, or `None` if this build can't construct it (see `DISPLAY_P3`).
+  pub fn try_color_space(&self) -> Option<ColorSpace> {
+    match self {
+      PredefinedColorSpace::Srgb => Some(ColorSpace::new_srgb()),
+      PredefinedColorSpace::DisplayP3 => DISPLAY_P3.clone(),
+    }
+  }
+
+  /// Like `try_color_space`, but falling back to sRGB. Spaces chosen through
+  /// [to_supported_color_space] are always available.
+  pub fn to_color_space(&self) -> ColorSpace {
+    self.try_color_space().unwrap_or_else(ColorSpace::new_srgb)
+  }
+}
+
+/// How a context stores its pixels, and the layout of an `ImageData` buffer. Float formats
+/// render at half-float precision; `Float32` only widens the values on readback.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelFormat{
+  Unorm8, Float16, Float32
+}
+
+impl PixelFormat{
+  pub fn color_type(&self) -> ColorType {
+    match self {
+      PixelFormat::Unorm8 => ColorType::RGBA8888,
+      PixelFormat::Float16 => ColorType::RGBAF16,
+      PixelFormat::Float32 => ColorType::RGBAF32,
+    }
+  }
+
+  pub fn bit_depth(&self) -> BitDepth {
+    match self {
+      PixelFormat::Unorm8 => BitDepth::U8,
+      _ => BitDepth::F16,
+    }
+  }
+
+  pub fn bytes_per_pixel(&self) -> usize {
+    match self {
+      PixelFormat::Unorm8 => 4,
+      PixelFormat::Float16 => 8,
+      PixelFormat::Float32 => 16,
+    }
+  }
+}
+
+/// Accepts the `ImageData` pixel formats ("rgba-unorm8", "rgba-float16", "rgba-float32").
+pub fn to_pixel_format(name:&str) -> Option<PixelFormat>{
+  let format = match name {
+    "rgba-unorm8" => PixelFormat::Unorm8,
+    "rgba-float16" => PixelFormat::Float16,
+    "rgba-float32" => PixelFormat::Float32,
+    _ => return None
+  };
+  Some(format)
+}
+
+pub fn from_pixel_format(format:PixelFormat) -> String{
+  match format {
+    PixelFormat::Unorm8 => "rgba-unorm8",
+    PixelFormat::Float16 => "rgba-float16",
+    PixelFormat::Float32 => "rgba-float32",
+  }.to_string()
+}
+
+pub fn to_color_space_name(name:&str) -> Option<PredefinedColorSpace>{
+  let space = match name {
+    "srgb" => PredefinedColorSpace::Srgb,
+    "display-p3" => PredefinedColorSpace::DisplayP3,
+    _ => return None
+  };
+  Some(space)
+}
+
+/// Look up a color space by name for a context or `ImageData`: unknown names are a `Type` error
+/// and spaces this build can't construct are `NotSupported`.
+pub fn to_supported_color_space(name:&str) -> Result<PredefinedColorSpace, Error>{
+  let space = to_color_space_name(name)
+    .ok_or_else(|| Error::new(ErrorCode::Type, format!("Unknown color space: {}", name)))?;
+  match space.try_color_space() {
+    Some(_) => Ok(space),
+    None => Err(Error::new(ErrorCode::NotSupported, format!("The {} color space is not available", name)))
+  }
+}
+
+pub fn from_color_space_name(space:PredefinedColorSpace) -> String{
+  match space {
+    PredefinedColorSpace::Srgb => "srgb",
+    PredefinedColorSpace::DisplayP3 => "display-p3",
+  }.to_string()
+}
 pub fn to_repeat_mode(repeat:&str) -> Option<(TileMode, TileMode)> {
   let mode = match repeat.to_lowercase().as_str() {
     "repeat" | "" => (Repeat, Repeat),
@@ -0,0 +1,188 @@
+//
+// Frame collection, animated APNG / GIF encoding, and decoding animated images frame by frame.
+//
+use canvas_wasm::animation::*;
+use canvas_wasm::context::api::*;
+use canvas_wasm::error::*;
+use canvas_wasm::image::*;
+
+mod common;
+use common::*;
+
+fn spinner(format: &str, loops: u32) -> Vec<u8> {
+  let cx = new_ctx(16, 16);
+  let fc = new_frame_collector();
+  unsafe {
+    for (i, color) in ["#f00", "#0f0", "#00f"].iter().enumerate() {
+      reset(cx);
+      set_fill(cx, color);
+      fillRect(cx, f32s(&[i as f32 * 4.0, 0.0, 4.0, 16.0]));
+      assert_eq!(frame_collector_add_frame(fc, cx, 100.0, c_str("")), 0);
+    }
+    assert_eq!(frame_collector_frame_count(fc), 3);
+    let buf = frame_collector_encode(fc, c_str(format), loops, 0.8);
+    drop_frame_collector(fc);
+    *Box::from_raw(buf)
+  }
+}
+
+fn png_chunk_types(bytes: &[u8]) -> Vec<String> {
+  let mut kinds = vec![];
+  let mut at = 8;
+  while at + 12 <= bytes.len() {
+    let len = u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+    kinds.push(String::from_utf8_lossy(&bytes[at + 4..at + 8]).into_owned());
+    at += 12 + len;
+  }
+  kinds
+}
+
+#[test]
+fn apng_has_one_control_chunk_per_frame() {
+  let apng = spinner("png", 0);
+  let kinds = png_chunk_types(&apng);
+  assert_eq!(&kinds[0..2], ["IHDR", "acTL"]);
+  assert_eq!(kinds.iter().filter(|k| *k == "fcTL").count(), 3);
+  assert!(kinds.contains(&"IDAT".to_string()));
+  assert!(kinds.contains(&"fdAT".to_string()));
+  assert_eq!(kinds.last().map(String::as_str), Some("IEND"));
+
+  // acTL: 3 frames, loop forever
+  let actl = &apng[8 + 25 + 8..8 + 25 + 16];
+  assert_eq!(actl, [0, 0, 0, 3, 0, 0, 0, 0]);
+}
+
+#[test]
+fn apng_is_a_valid_png_for_non_animated_viewers() {
+  let apng = spinner("apng", 2);
+  assert_eq!(&apng[1..4], b"PNG");
+  // the first frame doubles as the default image, so IDAT precedes any fdAT
+  let kinds = png_chunk_types(&apng);
+  let idat = kinds.iter().position(|k| k == "IDAT").unwrap();
+  let fdat = kinds.iter().position(|k| k == "fdAT").unwrap();
+  assert!(idat < fdat);
+}
+
+#[test]
+fn gif_has_a_frame_per_capture() {
+  let gif = spinner("gif", 0);
+  assert_eq!(&gif[0..6], b"GIF89a");
+  assert_eq!(u16::from_le_bytes([gif[6], gif[7]]), 16);
+  // NETSCAPE2.0 extension marks the animation as looping
+  assert!(gif.windows(11).any(|w| w == b"NETSCAPE2.0"));
+  // each frame starts with a graphic control extension
+  assert!(gif.windows(2).filter(|w| *w == [0x21, 0xF9]).count() >= 3);
+}
+
+#[test]
+fn long_gif_delays_are_rounded_to_centiseconds() {
+  let cx = new_ctx(4, 4);
+  let fc = new_frame_collector();
+  let gif = unsafe {
+    assert_eq!(frame_collector_add_frame(fc, cx, 70000.0, c_str("")), 0);
+    assert_eq!(frame_collector_add_frame(fc, cx, 15.0, c_str("")), 0);
+    *Box::from_raw(frame_collector_encode(fc, c_str("gif"), 0, 1.0))
+  };
+  // graphic control extensions: 21 F9 04 <flags> <delay lo> <delay hi>
+  let delays: Vec<u16> = gif.windows(6)
+    .filter(|w| w[..3] == [0x21, 0xF9, 0x04])
+    .map(|w| u16::from_le_bytes([w[4], w[5]]))
+    .collect();
+  // delays are capped at u16::MAX ms, which is 6554 cs once rounded
+  assert_eq!(delays, [6554, 2]);
+}
+
+#[test]
+fn frames_must_share_a_size() {
+  let fc = new_frame_collector();
+  unsafe {
+    assert_eq!(frame_collector_add_frame(fc, new_ctx(10, 10), 50.0, c_str("")), 0);
+    let status = frame_collector_add_frame(fc, new_ctx(20, 10), 50.0, c_str(""));
+    assert_eq!(status, ErrorCode::IndexSize as u32);
+    assert_eq!(frame_collector_frame_count(fc), 1);
+    drop_frame_collector(fc);
+  }
+}
+
+#[test]
+fn empty_animation_is_an_error() {
+  let fc = new_frame_collector();
+  unsafe {
+    assert!(frame_collector_encode(fc, c_str("gif"), 0, 1.0).is_null());
+    assert_eq!(get_last_error(), ErrorCode::InvalidState as u32);
+    drop_frame_collector(fc);
+  }
+}
+
+fn load_image(bytes: Vec<u8>) -> *mut Image {
+  let image = new_image();
+  assert_eq!(image_set_data(image, Box::into_raw(Box::new(bytes))), 1);
+  image
+}
+
+#[test]
+fn animated_gif_exposes_its_frames() {
+  let image = load_image(spinner("gif", 2));
+  assert_eq!(image_get_frame_count(image), 3);
+  assert_eq!(image_get_loop_count(image), 2);
+  for idx in 0..3 {
+    assert_eq!(image_get_frame_duration(image, idx), 100.0);
+  }
+  assert_eq!(image_get_frame_duration(image, 3), 0.0);
+
+  let forever = load_image(spinner("gif", 0));
+  assert_eq!(image_get_loop_count(forever), -1);
+}
+
+#[test]
+fn draw_image_uses_the_selected_frame() {
+  let image = load_image(spinner("gif", 0));
+  assert_eq!(image_get_frame(image), 0);
+  assert_eq!(image_set_frame(image, 2), 0);
+  assert_eq!(image_get_frame(image), 2);
+
+  let cx = new_ctx(16, 16);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+  assert_eq!(pixel(cx, 9, 8), [0, 0, 255, 255]);
+  assert_eq!(pixel(cx, 1, 8)[3], 0); // the red strip from frame 0 was cleared
+
+  // earlier frames can be revisited once later ones have been decoded
+  assert_eq!(image_set_frame(image, 1), 0);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+  assert_eq!(pixel(cx, 5, 8), [0, 255, 0, 255]);
+}
+
+#[test]
+fn apng_exposes_its_frames() {
+  // APNG counts plays rather than repeats, so 3 plays loop twice after the first
+  let image = load_image(spinner("png", 3));
+  assert_eq!(image_get_frame_count(image), 3);
+  assert_eq!(image_get_loop_count(image), 2);
+  for idx in 0..3 {
+    assert_eq!(image_get_frame_duration(image, idx), 100.0);
+  }
+  assert_eq!(image_get_loop_count(load_image(spinner("png", 0))), -1);
+
+  let cx = new_ctx(16, 16);
+  assert_eq!(image_set_frame(image, 2), 0);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+  assert_eq!(pixel(cx, 9, 8), [0, 0, 255, 255]);
+  assert_eq!(pixel(cx, 1, 8)[3], 0); // each frame replaces the one before
+
+  assert_eq!(image_set_frame(image, 0), 0);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+  assert_eq!(pixel(cx, 1, 8), [255, 0, 0, 255]);
+}
+
+#[test]
+fn out_of_range_frames_are_index_size_errors() {
+  let image = load_image(spinner("gif", 0));
+  assert_eq!(image_set_frame(image, 3), ErrorCode::IndexSize as u32);
+  assert_eq!(image_get_frame(image), 0);
+
+  let cx = new_ctx(4, 4);
+  let still = get_image(cx);
+  assert_eq!(image_get_frame_count(still), 1);
+  assert_eq!(image_set_frame(still, 0), 0);
+  assert_eq!(image_set_frame(still, 1), ErrorCode::IndexSize as u32);
+}
@@ -0,0 +1,90 @@
+//
+// Contexts created with `willReadFrequently` flatten their drawing into a raster backing store.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::{canvas_save_as, new_canvas};
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use canvas_wasm::surface::init_raster_surface;
+
+mod common;
+use common::*;
+
+fn new_raster_ctx(width: i32, height: i32) -> *mut Context2D {
+  unsafe {
+    let canvas = new_canvas(init_raster_surface(width, height), width as f32, height as f32);
+    let settings = new_js_str_map();
+    js_str_map_insert(settings, c_str("willReadFrequently"), c_str("true"));
+    new_context(canvas as *mut _, settings)
+  }
+}
+
+#[test]
+fn drawing_accumulates_across_reads() {
+  let cx = new_raster_ctx(8, 8);
+  assert_eq!(get_willReadFrequently(cx), 1);
+  assert_eq!(get_willReadFrequently(new_ctx(1, 1)), 0);
+
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 4.0, 8.0]));
+  assert_eq!(pixel(cx, 1, 1), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 6, 1), [0, 0, 0, 0]);
+
+  // later commands land on top of the flattened contents
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[2.0, 0.0, 6.0, 8.0]));
+  assert_eq!(pixel(cx, 1, 1), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 6, 1), [0, 0, 255, 255]);
+  assert_eq!(pixel(cx, 3, 1), [0, 0, 255, 255]);
+}
+
+#[test]
+fn state_survives_a_checkpoint() {
+  let cx = new_raster_ctx(8, 8);
+  save(cx);
+  translate(cx, f32s(&[4.0, 0.0]));
+  beginPath(cx);
+  rect(cx, f32s(&[0.0, 0.0, 2.0, 8.0]));
+  clip(cx, null_mut(), 0);
+  assert_eq!(pixel(cx, 0, 0), [0, 0, 0, 0]);
+
+  // the matrix and clip still apply to commands issued after the read
+  set_fill(cx, "#0f0");
+  fillRect(cx, f32s(&[-4.0, 0.0, 16.0, 8.0]));
+  assert_eq!(pixel(cx, 1, 1), [0, 0, 0, 0]);
+  assert_eq!(pixel(cx, 5, 1), [0, 255, 0, 255]);
+  assert_eq!(pixel(cx, 7, 1), [0, 0, 0, 0]);
+  restore(cx);
+}
+
+#[test]
+fn clearing_and_reset_erase_the_backing_store() {
+  let cx = new_raster_ctx(4, 4);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 4.0, 4.0]));
+  assert_eq!(pixel(cx, 2, 2), [255, 0, 0, 255]);
+
+  clearRect(cx, f32s(&[0.0, 0.0, 2.0, 4.0]));
+  assert_eq!(pixel(cx, 1, 2), [0, 0, 0, 0]);
+  assert_eq!(pixel(cx, 3, 2), [255, 0, 0, 255]);
+
+  reset(cx);
+  assert_eq!(pixel(cx, 3, 2), [0, 0, 0, 0]);
+  assert_eq!(get_willReadFrequently(cx), 1);
+}
+
+#[test]
+fn exports_match_a_vector_context() {
+  let draw = |cx: *mut Context2D| unsafe {
+    set_fill(cx, "#f00");
+    fillRect(cx, f32s(&[0.0, 0.0, 2.0, 4.0]));
+    let _ = pixel(cx, 0, 0);
+    set_fill(cx, "#00f");
+    fillRect(cx, f32s(&[2.0, 0.0, 2.0, 4.0]));
+    let png = *Box::from_raw(canvas_save_as(&mut *(*cx).canvas, c_str("png"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut()));
+    (png.len() > 0, pixels(cx, 0, 0, 4, 4))
+  };
+  assert_eq!(draw(new_raster_ctx(4, 4)), draw(new_ctx(4, 4)));
+}
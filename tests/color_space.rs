@@ -0,0 +1,146 @@
+//
+// Display-p3 contexts, ImageData color spaces and wide-gamut CSS colors.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::{canvas_save_as, new_canvas};
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use canvas_wasm::error::*;
+use canvas_wasm::surface::init_raster_surface;
+use canvas_wasm::utils::*;
+use skia_safe::{Color4f, ColorSpace, Paint};
+
+mod common;
+use common::*;
+
+fn image_data(cx: *mut Context2D, color_space: &str) -> (Vec<u8>, String) {
+  unsafe {
+    let image_data = getImageData(cx, 0, 0, 1, 1, c_str(color_space), c_str(""));
+    let space = char_to_string(image_data_get_color_space(image_data));
+    (*Box::from_raw((*image_data).data), space)
+  }
+}
+
+fn near(actual: &[u8], expected: [u8; 4]) -> bool {
+  actual.iter().zip(expected).all(|(a, e)| (*a as i32 - e as i32).abs() <= 2)
+}
+
+fn has_chunk(png: &[u8], kind: &[u8]) -> bool {
+  let mut at = 8;
+  while at + 12 <= png.len() {
+    let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+    if &png[at + 4..at + 8] == kind {
+      return true
+    }
+    at += 12 + len;
+  }
+  false
+}
+
+#[test]
+fn image_data_defaults_to_the_context_color_space() {
+  let cx = new_ctx_in(1, 1, "display-p3");
+  assert_eq!(char_to_string(get_colorSpace(cx)), "display-p3");
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 1.0, 1.0]));
+
+  let (p3, space) = image_data(cx, "");
+  assert_eq!(space, "display-p3");
+  assert!(near(&p3, [234, 51, 35, 255]), "{:?}", p3);
+
+  let (srgb, space) = image_data(cx, "srgb");
+  assert_eq!(space, "srgb");
+  assert!(near(&srgb, [255, 0, 0, 255]), "{:?}", srgb);
+}
+
+#[test]
+fn display_p3_colors_keep_their_gamut() {
+  let cx = new_ctx_in(1, 1, "display-p3");
+  set_fill(cx, "color(display-p3 1 0 0)");
+  fillRect(cx, f32s(&[0.0, 0.0, 1.0, 1.0]));
+  let (p3, _) = image_data(cx, "");
+  assert!(near(&p3, [255, 0, 0, 255]), "{:?}", p3);
+
+  // an sRGB context can only show the nearest sRGB color
+  let cx = new_ctx(1, 1);
+  set_fill(cx, "color(display-p3 1 0 0)");
+  fillRect(cx, f32s(&[0.0, 0.0, 1.0, 1.0]));
+  assert_eq!(pixel(cx, 0, 0), [255, 0, 0, 255]);
+}
+
+#[test]
+fn css_colors_round_trip() {
+  let p3_red = css_to_color4f("color(display-p3 1 0 0)").unwrap();
+  assert!(p3_red.r > 1.0 && p3_red.g < 0.0);
+  assert_eq!(color4f_to_css(&p3_red), "color(display-p3 1 0 0)");
+
+  let half = css_to_color4f("color(display-p3 100% 0% 0% / 50%)").unwrap();
+  assert_eq!(half.a, 0.5);
+  assert_eq!(color4f_to_css(&css_to_color4f("color(srgb 1 0 0)").unwrap()), "#ff0000");
+  assert_eq!(css_to_color("color(display-p3 0 1 0)").map(|c| c.a()), Some(255));
+  assert!(css_to_color4f("color(rec2020 1 0 0)").is_none());
+}
+
+#[test]
+fn put_image_data_converts_between_color_spaces() {
+  let cx = new_ctx(1, 1);
+  unsafe {
+    let pixels = Box::into_raw(Box::new(vec![234u8, 51, 35, 255]));
+    let data = new_image_data(pixels, 1.0, 1.0, c_str("display-p3"), c_str(""));
+    assert_eq!(putImageData(cx, data, f32s(&[0.0, 0.0])), 0);
+  }
+  assert!(near(&pixel(cx, 0, 0), [255, 0, 0, 255]));
+}
+
+#[test]
+fn png_export_embeds_the_color_profile() {
+  let save = |cx: *mut Context2D| unsafe {
+    let buf = canvas_save_as(&mut *(*cx).canvas, c_str("png"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut());
+    *Box::from_raw(buf)
+  };
+  assert!(has_chunk(&save(new_ctx_in(4, 4, "display-p3")), b"iCCP"));
+  assert!(!has_chunk(&save(new_ctx(4, 4)), b"iCCP"));
+}
+
+#[test]
+fn unknown_color_spaces_are_type_errors() {
+  unsafe {
+    let canvas = new_canvas(init_raster_surface(1, 1), 1.0, 1.0);
+    let settings = new_js_str_map();
+    js_str_map_insert(settings, c_str("colorSpace"), c_str("rec2020"));
+    assert!(new_context(canvas as *mut _, settings).is_null());
+    assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+    assert!(new_image_data(Box::into_raw(Box::new(vec![0; 4])), 1.0, 1.0, c_str("cmyk"), c_str("")).is_null());
+    assert_eq!(get_last_error(), ErrorCode::Type as u32);
+  }
+}
+
+#[test]
+fn display_p3_space_matches_the_css_conversion() {
+  // None here means Skia's serialization changed and display-p3 contexts are being refused
+  let p3 = PredefinedColorSpace::DisplayP3.try_color_space().expect("display-p3 is unavailable");
+  assert!(!p3.is_srgb());
+  assert!(p3 != ColorSpace::new_srgb());
+
+  // skia's conversion out of the P3 space agrees with the one used to parse CSS colors
+  for rgb in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.2, 0.4, 0.6]] {
+    let css = format!("color(display-p3 {} {} {})", rgb[0], rgb[1], rgb[2]);
+    let parsed = css_to_color4f(&css).unwrap();
+    let mut paint = Paint::default();
+    paint.set_color4f(Color4f::new(rgb[0], rgb[1], rgb[2], 1.0), Some(&p3));
+    let converted = paint.color4f();
+    let pairs = [(parsed.r, converted.r), (parsed.g, converted.g), (parsed.b, converted.b)];
+    assert!(pairs.iter().all(|(a, b)| (a - b).abs() < 0.005), "{}: {:?}", css, pairs);
+  }
+  assert_eq!(color4f_to_css(&css_to_color4f("color(display-p3 1 0 0)").unwrap()), "color(display-p3 1 0 0)");
+
+  // and a P3 fill reads back unchanged from a display-p3 context
+  let cx = new_ctx_in(1, 1, "display-p3");
+  set_fill(cx, "color(display-p3 1 0 0)");
+  fillRect(cx, f32s(&[0.0, 0.0, 1.0, 1.0]));
+  let (pixel, _) = image_data(cx, "display-p3");
+  assert!(near(&pixel, [255, 0, 0, 255]), "{:?}", pixel);
+}
@@ -0,0 +1,147 @@
+//
+// Reference-image comparisons
+//
+// Each scene is rendered into a fresh context and compared against `tests/goldens/<name>.png`.
+// Set UPDATE_GOLDENS=1 to (re)write the stored images from the current output; otherwise a
+// missing golden is a failure. On failure the actual output and a diff image are written to the
+// cargo target tmpdir so they can be inspected.
+//
+use std::{env, fs, path::PathBuf};
+
+use skia_safe::{AlphaType, ColorType, Data, EncodedImageFormat, IPoint, Image, ImageInfo, image::CachingHint};
+
+use canvas_wasm::context::Context2D;
+
+use super::{new_ctx, pixels};
+
+pub struct Tolerance {
+  // largest per-channel difference that still counts as a match
+  pub channel: u8,
+  // fraction of pixels allowed to mismatch (after forgiving anti-aliased edges)
+  pub mismatch: f32,
+}
+
+impl Default for Tolerance {
+  fn default() -> Self {
+    Tolerance{ channel: 2, mismatch: 0.0 }
+  }
+}
+
+#[derive(Default, Debug)]
+pub struct Diff {
+  pub mismatched: usize,
+  pub antialiased: usize,
+  pub total: usize,
+}
+
+pub fn check_scene<F>(name: &str, width: i32, height: i32, tolerance: Tolerance, draw: F)
+  where F: FnOnce(*mut Context2D)
+{
+  let cx = new_ctx(width, height);
+  draw(cx);
+  let actual = pixels(cx, 0, 0, width, height);
+
+  let golden_path = goldens_dir().join(format!("{}.png", name));
+  if env::var("UPDATE_GOLDENS").is_ok() {
+    fs::write(&golden_path, encode_png(&actual, width, height)).unwrap();
+    return
+  }
+
+  let bytes = fs::read(&golden_path).unwrap_or_else(|_| panic!(
+    "no golden for scene '{}' at {}; run with UPDATE_GOLDENS=1 to record it", name, golden_path.display()
+  ));
+  let expected = decode_png(&bytes, width, height)
+    .unwrap_or_else(|| panic!("{} could not be decoded at {}×{}", golden_path.display(), width, height));
+  let (diff, diff_img) = compare(&actual, &expected, width, height, tolerance.channel);
+
+  if diff.mismatched as f32 > tolerance.mismatch * diff.total as f32 {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("goldens");
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{}.actual.png", name));
+    let diff_path = out_dir.join(format!("{}.diff.png", name));
+    fs::write(&actual_path, encode_png(&actual, width, height)).unwrap();
+    fs::write(&diff_path, encode_png(&diff_img, width, height)).unwrap();
+    panic!(
+      "scene '{}' differs from its golden in {} of {} pixels ({} more forgiven as anti-aliasing)\n  actual: {}\n  diff:   {}",
+      name, diff.mismatched, diff.total, diff.antialiased, actual_path.display(), diff_path.display()
+    );
+  }
+}
+
+/// Compare two unpremultiplied RGBA buffers, returning the mismatch counts and a diff image.
+///
+/// A pixel that is out of tolerance is treated as an anti-aliasing difference (rather than a
+/// mismatch) when it matches one of its immediate neighbors in the other image, which is what
+/// happens when an edge moves by a fraction of a pixel. In the diff image mismatches are red,
+/// forgiven edges are yellow, and everything else is a faded copy of the expected image.
+pub fn compare(actual: &[u8], expected: &[u8], width: i32, height: i32, channel: u8) -> (Diff, Vec<u8>) {
+  let mut diff = Diff{ total: (width * height) as usize, ..Diff::default() };
+  let mut img = vec![0u8; actual.len()];
+
+  for y in 0..height {
+    for x in 0..width {
+      let idx = ((y * width + x) * 4) as usize;
+      let marker = if matches(&actual[idx..idx+4], &expected[idx..idx+4], channel) {
+        let gray = 255 - luma(&expected[idx..idx+4]) / 4;
+        [gray, gray, gray, 255]
+      } else if has_neighbor(&actual[idx..idx+4], expected, x, y, width, height, channel)
+             && has_neighbor(&expected[idx..idx+4], actual, x, y, width, height, channel) {
+        diff.antialiased += 1;
+        [255, 255, 0, 255]
+      } else {
+        diff.mismatched += 1;
+        [255, 0, 0, 255]
+      };
+      img[idx..idx+4].copy_from_slice(&marker);
+    }
+  }
+
+  (diff, img)
+}
+
+fn matches(a: &[u8], b: &[u8], channel: u8) -> bool {
+  a.iter().zip(b).all(|(a, b)| a.abs_diff(*b) <= channel)
+}
+
+fn luma(px: &[u8]) -> u8 {
+  let rgb = 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+  (rgb * px[3] as f32 / 255.0) as u8
+}
+
+fn has_neighbor(px: &[u8], other: &[u8], x: i32, y: i32, width: i32, height: i32, channel: u8) -> bool {
+  (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+    .filter(|(nx, ny)| (0..width).contains(nx) && (0..height).contains(ny))
+    .any(|(nx, ny)| {
+      let idx = ((ny * width + nx) * 4) as usize;
+      matches(px, &other[idx..idx+4], channel)
+    })
+}
+
+fn goldens_dir() -> PathBuf {
+  let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("goldens");
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+fn rgba_info(width: i32, height: i32) -> ImageInfo {
+  ImageInfo::new((width, height), ColorType::RGBA8888, AlphaType::Unpremul, None)
+}
+
+fn encode_png(rgba: &[u8], width: i32, height: i32) -> Vec<u8> {
+  let info = rgba_info(width, height);
+  let img = Image::from_raster_data(&info, Data::new_copy(rgba), info.min_row_bytes()).unwrap();
+  img.encode_to_data(EncodedImageFormat::PNG).unwrap().as_bytes().to_vec()
+}
+
+fn decode_png(bytes: &[u8], width: i32, height: i32) -> Option<Vec<u8>> {
+  let img = Image::from_encoded(Data::new_copy(bytes))?;
+  if img.width() != width || img.height() != height {
+    return None
+  }
+  let info = rgba_info(width, height);
+  let mut rgba = vec![0u8; (width * height * 4) as usize];
+  match img.read_pixels(&info, rgba.as_mut_slice(), info.min_row_bytes(), IPoint::new(0, 0), CachingHint::Allow) {
+    true => Some(rgba),
+    false => None
+  }
+}
@@ -0,0 +1,107 @@
+//
+// Helpers shared by the integration tests. They call the same C entry points the JS bindings use.
+//
+#![allow(dead_code)]
+use std::ffi::{c_char, c_void, CString};
+
+use canvas_wasm::canvas::new_canvas;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use canvas_wasm::context::{new_dye_from_color, Context2D};
+use canvas_wasm::surface::init_raster_surface;
+use canvas_wasm::typography::*;
+
+pub mod golden;
+
+pub const ROBOTO: &[u8] = include_bytes!("../../web/Roboto-Regular.ttf");
+pub const MONACO: &[u8] = include_bytes!("../../web/monaco.ttf");
+
+pub fn new_ctx(width: i32, height: i32) -> *mut Context2D {
+  new_ctx_in(width, height, "srgb")
+}
+
+pub fn new_ctx_in(width: i32, height: i32, color_space: &str) -> *mut Context2D {
+  unsafe {
+    let surface = init_raster_surface(width, height);
+    let canvas = new_canvas(surface, width as f32, height as f32);
+    let settings = new_js_str_map();
+    js_str_map_insert(settings, c_str("colorSpace"), c_str(color_space));
+    new_context(canvas as *mut _, settings)
+  }
+}
+
+pub fn f32s(nums: &[f32]) -> *mut JsF32Array {
+  Box::into_raw(Box::new(nums.to_vec()))
+}
+
+pub fn c_str(s: &str) -> *mut c_char {
+  CString::new(s).unwrap().into_raw()
+}
+
+pub fn set_fill(cx: *mut Context2D, css: &str) {
+  unsafe {
+    let dye = new_dye_from_color(c_str(css));
+    set_fillStyle(cx, dye as *mut _);
+  }
+}
+
+pub fn set_stroke(cx: *mut Context2D, css: &str) {
+  unsafe {
+    let dye = new_dye_from_color(c_str(css));
+    set_strokeStyle(cx, dye as *mut _);
+  }
+}
+
+pub fn str_map(entries: &[(&str, &str)]) -> *mut JsStrMap {
+  let map = new_js_str_map();
+  for (k, v) in entries {
+    unsafe { js_str_map_insert(map, c_str(k), c_str(v)) }
+  }
+  map
+}
+
+pub fn add_roboto() {
+  unsafe {
+    add_font_family(Box::into_raw(Box::new(ROBOTO.to_vec())), c_str("Roboto"));
+  }
+}
+
+pub fn add_monaco() {
+  unsafe {
+    add_font_family(Box::into_raw(Box::new(MONACO.to_vec())), c_str("Monaco"));
+  }
+}
+
+pub fn set_roboto(cx: *mut Context2D, size: f32) {
+  add_roboto();
+  set_families(cx, &["Roboto"], size);
+}
+
+/// Set the font to a list of already registered families, in order of preference.
+pub fn set_families(cx: *mut Context2D, names: &[&str], size: f32) {
+  unsafe {
+    let spec = new_font_spec();
+    let families = new_js_any_array(names.len() as i32);
+    for name in names {
+      js_any_array_push(families, c_str(name) as *mut c_void);
+    }
+    font_spec_set_families(spec, families);
+    font_spec_set_size(spec, size);
+    font_spec_set_leading(spec, size * 1.2);
+    font_spec_set_style(spec, 400, c_str("normal"), c_str("normal"));
+    font_spec_set_canonical(spec, c_str(&format!("{}px {}", size, names.join(", "))));
+    set_font(cx, spec);
+  }
+}
+
+pub fn pixels(cx: *mut Context2D, x: i32, y: i32, width: i32, height: i32) -> Vec<u8> {
+  unsafe {
+    let image_data = Box::from_raw(getImageData(cx, x, y, width, height, c_str(""), c_str("")));
+    *Box::from_raw(image_data.data)
+  }
+}
+
+pub fn pixel(cx: *mut Context2D, x: i32, y: i32) -> [u8; 4] {
+  let px = pixels(cx, x, y, 1, 1);
+  [px[0], px[1], px[2], px[3]]
+}
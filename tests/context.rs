@@ -0,0 +1,171 @@
+//
+// Drives Context2D through the same C entry points the JS bindings use and checks the
+// resulting pixels. Run natively with `cargo test --no-default-features`.
+//
+#![allow(non_snake_case)]
+use std::f32::consts::PI;
+use std::ptr::null_mut;
+
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn blank_canvas_is_transparent() {
+  let cx = new_ctx(20, 20);
+  assert!(pixels(cx, 0, 0, 20, 20).iter().all(|v| *v == 0));
+}
+
+#[test]
+fn get_image_data_has_one_rgba_quad_per_pixel() {
+  let cx = new_ctx(20, 20);
+  assert_eq!(pixels(cx, 0, 0, 7, 3).len(), 7 * 3 * 4);
+}
+
+#[test]
+fn fill_rect_uses_fill_style() {
+  let cx = new_ctx(100, 100);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[10.0, 10.0, 20.0, 20.0]));
+
+  assert_eq!(pixel(cx, 15, 15), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 29, 29), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 30, 30), [0, 0, 0, 0]);
+  assert_eq!(pixel(cx, 5, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn fill_rect_honors_transform_and_alpha() {
+  let cx = new_ctx(100, 100);
+  set_fill(cx, "#00f");
+  set_globalAlpha(cx, 0.5);
+  translate(cx, f32s(&[50.0, 50.0]));
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+
+  let [r, g, b, a] = pixel(cx, 55, 55);
+  assert!(r == 0 && g == 0 && b >= 254, "color was {:?}", (r, g, b)); // unpremultiplying may round
+  assert!((a as i32 - 128).abs() <= 1, "alpha was {}", a);
+  assert_eq!(pixel(cx, 5, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn save_and_restore_reset_the_transform() {
+  let cx = new_ctx(100, 100);
+  set_fill(cx, "#0f0");
+  save(cx);
+  translate(cx, f32s(&[50.0, 0.0]));
+  restore(cx);
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+
+  assert_eq!(pixel(cx, 5, 5), [0, 255, 0, 255]);
+  assert_eq!(pixel(cx, 55, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn arc_fills_a_circle() {
+  let cx = new_ctx(100, 100);
+  set_fill(cx, "#000");
+  beginPath(cx);
+  arc(cx, f32s(&[50.0, 50.0, 20.0, 0.0, 2.0 * PI, 0.0]));
+  fill(cx, null_mut(), 0);
+
+  assert_eq!(pixel(cx, 50, 50), [0, 0, 0, 255]);
+  assert_eq!(pixel(cx, 50, 32), [0, 0, 0, 255]);
+  assert_eq!(pixel(cx, 35, 35), [0, 0, 0, 0]); // outside the circle, inside its bounds
+  assert_eq!(pixel(cx, 50, 75), [0, 0, 0, 0]);
+}
+
+#[test]
+fn clear_rect_erases_content() {
+  let cx = new_ctx(100, 100);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 100.0, 100.0]));
+  clearRect(cx, f32s(&[10.0, 10.0, 10.0, 10.0]));
+
+  assert_eq!(pixel(cx, 15, 15), [0, 0, 0, 0]);
+  assert_eq!(pixel(cx, 25, 25), [255, 0, 0, 255]);
+}
+
+#[test]
+fn fill_text_draws_glyphs_above_the_baseline() {
+  let cx = new_ctx(200, 100);
+  set_roboto(cx, 40.0);
+  set_fill(cx, "#000");
+  fillText(cx, c_str("Hello"), f32s(&[10.0, 60.0]));
+
+  let ink = |x, y, w, h| pixels(cx, x, y, w, h).chunks(4).filter(|px| px[3] > 0).count();
+  assert!(ink(10, 20, 190, 40) > 100, "expected glyphs above the baseline");
+  assert_eq!(ink(0, 70, 200, 30), 0, "'Hello' has no descenders");
+}
+
+#[test]
+fn measure_text_reports_width() {
+  let cx = new_ctx(200, 100);
+  set_roboto(cx, 40.0);
+  unsafe {
+    let short = *Box::from_raw(measureText(cx, c_str("Hi"), f32s(&[])));
+    let long = *Box::from_raw(measureText(cx, c_str("Hi there"), f32s(&[])));
+    let width = |metrics: &JsAnyArray| (*(metrics[0] as *mut JsF32Array))[0];
+    assert!(width(&short) > 0.0);
+    assert!(width(&long) > width(&short));
+  }
+}
+
+#[test]
+fn copied_paths_outlive_their_source() {
+  use canvas_wasm::path::*;
+
+  let path = new_path2d();
+  path2d_move_to(path, 0.0, 0.0);
+  path2d_line_to(path, 10.0, 10.0);
+  let copy = new_path2d_from_path(path);
+  drop_path2d(path);
+  drop_path2d(copy);
+}
+
+#[test]
+fn drop_context_releases_canvas_and_surface() {
+  let cx = new_ctx(20, 20);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  drop_context(cx);
+}
+
+#[test]
+fn drawing_continues_after_reading_pixels() {
+  let cx = new_ctx(20, 20);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  assert_eq!(pixel(cx, 5, 5), [255, 0, 0, 255]);
+
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[10.0, 10.0, 10.0, 10.0]));
+  assert_eq!(pixel(cx, 5, 5), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 15, 15), [0, 0, 255, 255]);
+}
+
+#[test]
+fn repeated_reads_dont_grow_the_recording() {
+  let cx = new_ctx(20, 20);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  let ops = unsafe { (*cx).get_picture(None) }.unwrap().approximate_op_count_nested(true);
+  for _ in 0..20 {
+    pixels(cx, 0, 0, 20, 20);
+  }
+  let after = unsafe { (*cx).get_picture(None) }.unwrap().approximate_op_count_nested(true);
+  assert!(after <= ops + 4, "{} ops before, {} after", ops, after);
+  assert_eq!(pixel(cx, 5, 5), [255, 0, 0, 255]);
+}
+
+#[test]
+fn create_projection_reads_every_point() {
+  let cx = new_ctx(20, 20);
+  let projection = |dst: &[f32], src: &[f32]| unsafe { *Box::from_raw(createProjection(cx, f32s(dst), f32s(src))) };
+
+  // maps the basis rect onto the output rect, so each corner has to be read separately
+  assert_eq!(projection(&[0.0, 0.0, 20.0, 40.0], &[0.0, 0.0, 10.0, 10.0]), [2.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0, 1.0]);
+  assert_eq!(projection(&[0.0, 0.0, 10.0, 10.0], &[10.0, 10.0, 20.0, 20.0]), [1.0, 0.0, -10.0, 0.0, 1.0, -10.0, 0.0, 0.0, 1.0]);
+}
@@ -0,0 +1,154 @@
+//
+// Dirty-rectangle tracking and partial presents.
+//
+use canvas_wasm::canvas::new_canvas;
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::image::*;
+use canvas_wasm::surface::*;
+use skia_safe::{AlphaType, ColorType, ImageInfo};
+
+mod common;
+use common::*;
+
+fn dirty_rect(cx: *mut Context2D) -> Vec<f32> {
+  unsafe { *Box::from_raw(get_dirtyRect(cx)) }
+}
+
+fn presented_rect(state: *mut SurfaceState) -> Vec<f32> {
+  unsafe { *Box::from_raw(surface_get_presented_rect(state)) }
+}
+
+// [x, y, width, height] a contains [x, y, width, height] b
+fn contains(a: &[f32], b: [f32; 4]) -> bool {
+  a[0] <= b[0] && a[1] <= b[1] && a[0] + a[2] >= b[0] + b[2] && a[1] + a[3] >= b[1] + b[3]
+}
+
+#[test]
+fn fills_mark_their_device_bounds() {
+  let cx = new_ctx(100, 100);
+  assert_eq!(dirty_rect(cx), Vec::<f32>::new());
+
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[10.0, 10.0, 10.0, 10.0]));
+  // a pixel of slack for antialiasing
+  assert_eq!(dirty_rect(cx), [9.0, 9.0, 12.0, 12.0]);
+
+  translate(cx, f32s(&[50.0, 0.0]));
+  fillRect(cx, f32s(&[10.0, 40.0, 10.0, 10.0]));
+  assert_eq!(dirty_rect(cx), [9.0, 9.0, 62.0, 42.0]);
+}
+
+#[test]
+fn strokes_shadows_and_clips_adjust_the_bounds() {
+  let cx = new_ctx(100, 100);
+  set_stroke(cx, "#000");
+  set_lineWidth(cx, 4.0);
+  strokeRect(cx, f32s(&[40.0, 40.0, 20.0, 20.0]));
+  let stroked = dirty_rect(cx);
+  assert!(contains(&stroked, [38.0, 38.0, 24.0, 24.0]), "{:?}", stroked);
+
+  let cx = new_ctx(100, 100);
+  set_shadowColor(cx, c_str("#000"));
+  set_shadowOffsetX(cx, 30.0);
+  fillRect(cx, f32s(&[10.0, 10.0, 10.0, 10.0]));
+  let shadowed = dirty_rect(cx);
+  assert!(contains(&shadowed, [10.0, 10.0, 40.0, 10.0]), "{:?}", shadowed);
+
+  let cx = new_ctx(100, 100);
+  beginPath(cx);
+  rect(cx, f32s(&[0.0, 0.0, 50.0, 50.0]));
+  clip(cx, std::ptr::null_mut(), 0);
+  fillRect(cx, f32s(&[40.0, 40.0, 40.0, 40.0]));
+  assert_eq!(dirty_rect(cx), [39.0, 39.0, 11.0, 11.0]);
+
+  // nothing visible, nothing dirty
+  fillRect(cx, f32s(&[60.0, 60.0, 10.0, 10.0]));
+  assert_eq!(dirty_rect(cx), [39.0, 39.0, 11.0, 11.0]);
+}
+
+#[test]
+fn text_and_images_are_tracked() {
+  let cx = new_ctx(200, 100);
+  set_roboto(cx, 20.0);
+  fillText(cx, c_str("Hello"), f32s(&[50.0, 50.0]));
+  let text = dirty_rect(cx);
+  assert!(contains(&text, [50.0, 35.0, 40.0, 15.0]), "{:?}", text);
+  assert!(text[2] < 200.0, "{:?}", text);
+
+  let cx = new_ctx(100, 100);
+  let image = get_image(new_ctx(10, 10));
+  assert_eq!(drawImage(cx, image, f32s(&[20.0, 30.0])), 0);
+  assert_eq!(dirty_rect(cx), [19.0, 29.0, 12.0, 12.0]);
+}
+
+#[test]
+fn canvas_wide_blend_modes_dirty_everything() {
+  let cx = new_ctx(100, 50);
+  set_globalCompositeOperation(cx, c_str("destination-in"));
+  fillRect(cx, f32s(&[10.0, 10.0, 10.0, 10.0]));
+  assert_eq!(dirty_rect(cx), [0.0, 0.0, 100.0, 50.0]);
+}
+
+#[test]
+fn presenting_redraws_only_the_dirty_area() {
+  unsafe {
+    let state = init_raster_surface(100, 100);
+    let cx = new_context(new_canvas(state, 100.0, 100.0) as *mut _, std::ptr::null_mut());
+
+    // nothing drawn yet, so nothing to redraw
+    assert_eq!(render_to_surface(state, cx), 0);
+    assert_eq!(presented_rect(state), Vec::<f32>::new());
+
+    set_fill(cx, "#f00");
+    fillRect(cx, f32s(&[10.0, 20.0, 30.0, 40.0]));
+    assert_eq!(render_to_surface(state, cx), 0);
+    assert_eq!(presented_rect(state), [9.0, 19.0, 32.0, 42.0]);
+    assert_eq!(dirty_rect(cx), Vec::<f32>::new());
+  }
+}
+
+#[test]
+fn clearing_marks_the_cleared_area() {
+  let cx = new_ctx(100, 100);
+  clearRect(cx, f32s(&[10.0, 20.0, 30.0, 40.0]));
+  let cleared = dirty_rect(cx);
+  assert!(contains(&cleared, [10.0, 20.0, 30.0, 40.0]), "{:?}", cleared);
+  assert!(cleared[2] < 100.0, "{:?}", cleared);
+
+  // erasing the whole canvas dirties all of it
+  let cx = new_ctx(100, 50);
+  clearRect(cx, f32s(&[0.0, 0.0, 100.0, 50.0]));
+  assert_eq!(dirty_rect(cx), [0.0, 0.0, 100.0, 50.0]);
+
+  unsafe {
+    let state = init_raster_surface(100, 100);
+    let cx = new_context(new_canvas(state, 100.0, 100.0) as *mut _, std::ptr::null_mut());
+    clearRect(cx, f32s(&[50.0, 50.0, 10.0, 10.0]));
+    assert_eq!(render_to_surface(state, cx), 0);
+    assert!(contains(&presented_rect(state), [50.0, 50.0, 10.0, 10.0]), "{:?}", presented_rect(state));
+  }
+}
+
+#[test]
+fn presenting_a_clear_erases_the_surface() {
+  unsafe {
+    let state = init_raster_surface(100, 100);
+    let cx = new_context(new_canvas(state, 100.0, 100.0) as *mut _, std::ptr::null_mut());
+    set_fill(cx, "#f00");
+    fillRect(cx, f32s(&[0.0, 0.0, 100.0, 100.0]));
+    assert_eq!(render_to_surface(state, cx), 0);
+
+    clearRect(cx, f32s(&[50.0, 50.0, 10.0, 10.0]));
+    assert_eq!(render_to_surface(state, cx), 0);
+
+    let surface_pixel = |x: i32, y: i32| {
+      let info = ImageInfo::new((1, 1), ColorType::RGBA8888, AlphaType::Unpremul, None);
+      let mut rgba = [0u8; 4];
+      assert!((*state).surface().read_pixels(&info, &mut rgba, 4, (x, y)));
+      rgba
+    };
+    assert_eq!(surface_pixel(55, 55), [0, 0, 0, 0]);
+    assert_eq!(surface_pixel(20, 20), [255, 0, 0, 255]);
+  }
+}
@@ -0,0 +1,104 @@
+//
+// Fallible exports report failures through get_last_error instead of panicking, since a panic
+// aborts the whole wasm module.
+//
+use std::ffi::CStr;
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::canvas_save_as;
+use canvas_wasm::context::api::*;
+use canvas_wasm::error::*;
+use canvas_wasm::gradient::new_linear_gradient;
+use canvas_wasm::image::Image;
+use canvas_wasm::pattern::new_canvas_pattern_from_image;
+
+mod common;
+use common::*;
+
+fn last_message() -> String {
+  let msg = get_last_error_message();
+  assert!(!msg.is_null());
+  unsafe { CStr::from_ptr(msg) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn no_error_until_something_fails() {
+  clear_last_error();
+  assert_eq!(get_last_error(), ErrorCode::Ok as u32);
+  assert!(get_last_error_message().is_null());
+}
+
+#[test]
+fn unsupported_export_format_returns_null() {
+  let cx = new_ctx(10, 10);
+  let buf = unsafe {
+    canvas_save_as(&mut *(*cx).canvas, c_str("bmp"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut())
+  };
+  assert!(buf.is_null());
+  assert_eq!(get_last_error(), ErrorCode::NotSupported as u32);
+  assert!(last_message().contains("bmp"));
+
+  clear_last_error();
+  assert_eq!(get_last_error(), ErrorCode::Ok as u32);
+}
+
+#[test]
+fn png_export_still_succeeds() {
+  let cx = new_ctx(10, 10);
+  let buf = unsafe {
+    canvas_save_as(&mut *(*cx).canvas, c_str("png"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut())
+  };
+  assert!(!buf.is_null());
+  assert!(unsafe { (*buf).starts_with(b"\x89PNG") });
+}
+
+#[test]
+fn bad_pattern_repetition_returns_null() {
+  let image = Box::into_raw(Box::new(Image::new(None)));
+  let pattern = unsafe { new_canvas_pattern_from_image(image, c_str("sideways")) };
+  assert!(pattern.is_null());
+  assert_eq!(get_last_error(), ErrorCode::Syntax as u32);
+}
+
+#[test]
+fn short_gradient_arguments_return_null() {
+  let gradient = unsafe { new_linear_gradient(f32s(&[0.0, 0.0])) };
+  assert!(gradient.is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+}
+
+#[test]
+fn negative_ellipse_radius_is_an_index_size_error() {
+  let cx = new_ctx(10, 10);
+  let status = ellipse(cx, f32s(&[5.0, 5.0, -1.0, 2.0, 0.0, 0.0, 1.0, 0.0]));
+  assert_eq!(status, ErrorCode::IndexSize as u32);
+  assert_eq!(get_last_error(), ErrorCode::IndexSize as u32);
+}
+
+#[test]
+fn short_ellipse_arguments_are_a_type_error() {
+  let cx = new_ctx(10, 10);
+  let status = ellipse(cx, f32s(&[5.0, 5.0, 1.0, 2.0]));
+  assert_eq!(status, ErrorCode::Type as u32);
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+}
+
+#[test]
+fn odd_projection_coordinates_return_null() {
+  let cx = new_ctx(10, 10);
+  assert!(createProjection(cx, f32s(&[0.0, 0.0, 20.0]), f32s(&[])).is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+  clear_last_error();
+  assert!(createProjection(cx, f32s(&[0.0, 0.0, 20.0, 20.0]), f32s(&[10.0])).is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+}
+
+#[test]
+fn drawing_an_unloaded_image_is_an_invalid_state_error() {
+  let cx = new_ctx(10, 10);
+  let image = Box::into_raw(Box::new(Image::new(None)));
+  let status = drawImage(cx, image, f32s(&[0.0, 0.0]));
+  assert_eq!(status, ErrorCode::InvalidState as u32);
+  assert!(last_message().contains("incomplete image"));
+}
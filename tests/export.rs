@@ -0,0 +1,218 @@
+//
+// Encoded output of canvas_save_as.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::canvas_save_as;
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::jstypes::JsStrMap;
+use canvas_wasm::context::api::*;
+use canvas_wasm::document::*;
+use canvas_wasm::error::*;
+
+mod common;
+use common::*;
+
+fn save_as(cx: *mut Context2D, format: &str, quality: f32, density: f32) -> Option<Vec<u8>> {
+  unsafe {
+    let buf = canvas_save_as(&mut *(*cx).canvas, c_str(format), quality, density, c_str(""), c_str(""), null_mut(), 0, null_mut());
+    (!buf.is_null()).then(|| *Box::from_raw(buf))
+  }
+}
+
+fn save_svg(cx: *mut Context2D, outline: bool) -> String {
+  unsafe {
+    let buf = canvas_save_as(&mut *(*cx).canvas, c_str("svg"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), outline as u32, null_mut());
+    String::from_utf8(*Box::from_raw(buf)).unwrap()
+  }
+}
+
+fn text_scene() -> *mut Context2D {
+  let cx = new_ctx(200, 60);
+  set_roboto(cx, 24.0);
+  set_fill(cx, "#036");
+  fillText(cx, c_str("Revenue"), f32s(&[10.0, 40.0]));
+  cx
+}
+
+fn scene() -> *mut Context2D {
+  let cx = new_ctx(40, 30);
+  set_fill(cx, "rgba(255, 0, 0, 0.5)");
+  fillRect(cx, f32s(&[5.0, 5.0, 20.0, 10.0]));
+  cx
+}
+
+fn riff_chunks(bytes: &[u8]) -> Vec<String> {
+  let mut chunks = vec![];
+  let mut at = 12;
+  while at + 8 <= bytes.len() {
+    let size = u32::from_le_bytes(bytes[at + 4..at + 8].try_into().unwrap()) as usize;
+    chunks.push(String::from_utf8_lossy(&bytes[at..at + 4]).into_owned());
+    at += 8 + size + size % 2;
+  }
+  chunks
+}
+
+#[test]
+fn webp_is_a_riff_container() {
+  let bytes = save_as(scene(), "webp", 0.8, 1.0).unwrap();
+  assert_eq!(&bytes[0..4], b"RIFF");
+  assert_eq!(&bytes[8..12], b"WEBP");
+  assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+}
+
+#[test]
+fn webp_quality_changes_the_encoding() {
+  let lossy = save_as(scene(), "webp", 0.5, 1.0).unwrap();
+  let lossless = save_as(scene(), "webp", 1.0, 1.0).unwrap();
+  assert!(riff_chunks(&lossless).contains(&"VP8L".to_string()));
+  assert!(!riff_chunks(&lossy).contains(&"VP8L".to_string()));
+}
+
+#[test]
+fn webp_density_is_written_as_exif() {
+  for quality in [0.8, 1.0] {
+    let bytes = save_as(scene(), "webp", quality, 2.0).unwrap();
+    let chunks = riff_chunks(&bytes);
+    assert_eq!(chunks.first().map(String::as_str), Some("VP8X"));
+    assert_eq!(chunks.last().map(String::as_str), Some("EXIF"));
+    assert_eq!(bytes[20] & 0x08, 0x08);
+    assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+
+    // canvas size is stored as 24-bit width-1, height-1 and should reflect the density
+    let width = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], 0]) + 1;
+    let height = u32::from_le_bytes([bytes[27], bytes[28], bytes[29], 0]) + 1;
+    assert_eq!((width, height), (80, 60));
+
+    let exif = &bytes[bytes.len() - 66..];
+    assert_eq!(&exif[50..54], &144u32.to_be_bytes());
+  }
+}
+
+#[test]
+fn png_density_is_written_as_phys() {
+  let bytes = save_as(scene(), "png", 1.0, 2.0).unwrap();
+  assert_eq!(&bytes[37..41], b"pHYs");
+}
+
+#[test]
+fn avif_reports_missing_encoder() {
+  assert!(save_as(scene(), "avif", 0.8, 1.0).is_none());
+  assert_eq!(get_last_error(), ErrorCode::NotSupported as u32);
+}
+
+#[test]
+fn svg_export_is_vector() {
+  let svg = save_svg(scene(), false);
+  assert!(svg.starts_with("<?xml"));
+  assert!(svg.contains("<svg"));
+  assert!(svg.contains("<rect"));
+  assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn svg_export_keeps_text_editable() {
+  let svg = save_svg(text_scene(), false);
+  assert!(svg.contains("<text"));
+  assert!(svg.contains("Revenue"));
+}
+
+#[test]
+fn svg_export_can_outline_text() {
+  let svg = save_svg(text_scene(), true);
+  assert!(!svg.contains("<text"));
+  assert!(svg.contains("<path"));
+}
+
+fn page_count(pdf: &[u8]) -> usize {
+  let text = String::from_utf8_lossy(pdf);
+  text.matches("/Type /Page\n").count() + text.matches("/Type /Page ").count() + text.matches("/Type /Page>").count()
+}
+
+#[test]
+fn single_page_pdf() {
+  let pdf = save_as(scene(), "pdf", 1.0, 1.0).unwrap();
+  assert!(pdf.starts_with(b"%PDF"));
+  assert_eq!(page_count(&pdf), 1);
+}
+
+#[test]
+fn pdf_document_collects_pages_from_several_contexts() {
+  unsafe {
+    let doc = new_pdf_document(1.0, 1.0, null_mut());
+    assert_eq!(pdf_document_add_page(doc, scene(), 0.0, 0.0, c_str(""), c_str(""), null_mut()), 0);
+    assert_eq!(pdf_document_add_page(doc, text_scene(), 595.0, 842.0, c_str(""), c_str("#fff"), null_mut()), 0);
+    assert_eq!(pdf_document_page_count(doc), 2);
+
+    let pdf = *Box::from_raw(pdf_document_close(doc));
+    assert!(pdf.starts_with(b"%PDF"));
+    assert_eq!(page_count(&pdf), 2);
+    assert!(String::from_utf8_lossy(&pdf).contains("/MediaBox [0 0 595 842]"));
+    drop_pdf_document(doc);
+  }
+}
+
+#[test]
+fn pdf_document_pages_can_be_successive_frames() {
+  unsafe {
+    let doc = new_pdf_document(1.0, 1.0, null_mut());
+    let cx = new_ctx(50, 50);
+    for color in ["#f00", "#0f0", "#00f"] {
+      set_fill(cx, color);
+      fillRect(cx, f32s(&[0.0, 0.0, 50.0, 50.0]));
+      assert_eq!(pdf_document_add_page(doc, cx, 0.0, 0.0, c_str(""), c_str(""), null_mut()), 0);
+      reset(cx);
+    }
+    let pdf = *Box::from_raw(pdf_document_close(doc));
+    assert_eq!(page_count(&pdf), 3);
+    drop_pdf_document(doc);
+  }
+}
+
+#[test]
+fn closed_pdf_document_rejects_more_pages() {
+  unsafe {
+    let doc = new_pdf_document(1.0, 1.0, null_mut());
+    pdf_document_add_page(doc, scene(), 0.0, 0.0, c_str(""), c_str(""), null_mut());
+    assert!(!pdf_document_close(doc).is_null());
+
+    let status = pdf_document_add_page(doc, scene(), 0.0, 0.0, c_str(""), c_str(""), null_mut());
+    assert_eq!(status, ErrorCode::InvalidState as u32);
+    assert!(pdf_document_close(doc).is_null());
+    drop_pdf_document(doc);
+  }
+}
+
+fn str_map(pairs: &[(&str, &str)]) -> *mut JsStrMap {
+  Box::into_raw(Box::new(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()))
+}
+
+#[test]
+fn pdf_carries_document_info() {
+  let info = str_map(&[
+    ("title", "Quarterly Invoice"),
+    ("author", "Billing"),
+    ("keywords", "invoice, q1"),
+    ("creationDate", "1704164645000"),
+  ]);
+  let pdf = unsafe {
+    let buf = canvas_save_as(&mut *(*scene()).canvas, c_str("pdf"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, info);
+    String::from_utf8_lossy(&*Box::from_raw(buf)).into_owned()
+  };
+  assert!(pdf.contains("(Quarterly Invoice)"));
+  assert!(pdf.contains("(Billing)"));
+  assert!(pdf.contains("(invoice, q1)"));
+  assert!(pdf.contains("D:20240102030405"));
+}
+
+#[test]
+fn pdf_keeps_link_annotations() {
+  let cx = scene();
+  annotateLinkUrl(cx, 5.0, 5.0, 20.0, 10.0, c_str("https://example.com/pay"));
+  annotateNamedDestination(cx, 0.0, 0.0, c_str("totals"));
+  annotateLinkToDestination(cx, 0.0, 20.0, 20.0, 10.0, c_str("totals"));
+  let pdf = String::from_utf8_lossy(&save_as(cx, "pdf", 1.0, 1.0).unwrap()).into_owned();
+  assert!(pdf.contains("https://example.com/pay"));
+  assert!(pdf.contains("/Link"));
+  assert!(pdf.contains("/totals"));
+}
@@ -0,0 +1,108 @@
+//
+// Reference-image regression tests (see tests/common/golden.rs for the harness)
+//
+#![allow(non_snake_case)]
+use std::f32::consts::PI;
+use std::ptr::null_mut;
+
+use canvas_wasm::context::api::*;
+
+mod common;
+use common::*;
+use common::golden::{check_scene, Tolerance};
+
+#[test]
+fn shapes() {
+  check_scene("shapes", 120, 80, Tolerance::default(), |cx| {
+    set_fill(cx, "#3366cc");
+    fillRect(cx, f32s(&[10.0, 10.0, 40.0, 30.0]));
+
+    set_stroke(cx, "#cc3333");
+    set_lineWidth(cx, 4.0);
+    beginPath(cx);
+    arc(cx, f32s(&[85.0, 40.0, 25.0, 0.0, 2.0 * PI, 0.0]));
+    stroke(cx, null_mut());
+
+    set_fill(cx, "rgba(0, 128, 0, 0.5)");
+    beginPath(cx);
+    moveTo(cx, f32s(&[10.0, 70.0]));
+    lineTo(cx, f32s(&[50.0, 45.0]));
+    lineTo(cx, f32s(&[70.0, 75.0]));
+    closePath(cx);
+    fill(cx, null_mut(), 0);
+  });
+}
+
+#[test]
+fn shadows() {
+  check_scene("shadows", 120, 80, Tolerance::default(), |cx| {
+    set_shadowColor(cx, c_str("rgba(0, 0, 0, 0.6)"));
+    set_shadowBlur(cx, 8.0);
+    set_shadowOffsetX(cx, 6.0);
+    set_shadowOffsetY(cx, 4.0);
+    set_fill(cx, "#f80");
+    fillRect(cx, f32s(&[15.0, 15.0, 40.0, 40.0]));
+
+    // a scaled shadow should blur in device space
+    save(cx);
+    scale(cx, f32s(&[2.0, 2.0]));
+    set_fill(cx, "#08f");
+    fillRect(cx, f32s(&[35.0, 8.0, 12.0, 12.0]));
+    restore(cx);
+  });
+}
+
+#[test]
+fn blend_modes() {
+  let modes = ["source-over", "multiply", "screen", "source-in", "destination-out", "xor"];
+  for mode in modes {
+    check_scene(&format!("blend-{}", mode), 60, 60, Tolerance::default(), |cx| {
+      set_fill(cx, "#36c");
+      fillRect(cx, f32s(&[5.0, 5.0, 35.0, 35.0]));
+      set_globalCompositeOperation(cx, c_str(mode));
+      set_fill(cx, "rgba(230, 60, 30, 0.8)");
+      beginPath(cx);
+      arc(cx, f32s(&[38.0, 38.0, 18.0, 0.0, 2.0 * PI, 0.0]));
+      fill(cx, null_mut(), 0);
+    });
+  }
+}
+
+#[test]
+fn text_baselines() {
+  // glyph rasterization varies slightly between font backends, so allow a little slack
+  let tolerance = || Tolerance{ channel: 8, mismatch: 0.002 };
+  for baseline in ["top", "hanging", "middle", "alphabetic", "ideographic", "bottom"] {
+    check_scene(&format!("text-{}", baseline), 160, 60, tolerance(), |cx| {
+      set_stroke(cx, "#c00");
+      beginPath(cx);
+      moveTo(cx, f32s(&[0.0, 30.0]));
+      lineTo(cx, f32s(&[160.0, 30.0]));
+      stroke(cx, null_mut());
+
+      set_roboto(cx, 24.0);
+      set_fill(cx, "#000");
+      set_textBaseline(cx, c_str(baseline));
+      fillText(cx, c_str("Abgx"), f32s(&[10.0, 30.0]));
+    });
+  }
+}
+
+#[test]
+fn compare_forgives_subpixel_edges() {
+  // a one-pixel horizontal shift of a soft edge should be reported as anti-aliasing, not a mismatch
+  let row = |offset: usize| {
+    let mut px = vec![0u8; 8 * 4];
+    for x in offset..8 {
+      px[x * 4 + 3] = 255;
+    }
+    px[offset * 4 + 3] = 128;
+    px
+  };
+  let (diff, _) = common::golden::compare(&row(3), &row(4), 8, 1, 2);
+  assert_eq!(diff.mismatched, 0);
+  assert!(diff.antialiased > 0);
+
+  let (diff, _) = common::golden::compare(&row(1), &row(5), 8, 1, 2);
+  assert!(diff.mismatched > 0);
+}
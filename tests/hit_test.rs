@@ -0,0 +1,114 @@
+//
+// Retained mode: tagging drawing with element ids and hit testing them afterwards.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::utils::*;
+
+mod common;
+use common::*;
+
+fn hit(cx: *mut Context2D, x: f32, y: f32) -> String {
+  char_to_string(hitTest(cx, x, y))
+}
+
+fn retained_ctx() -> *mut Context2D {
+  let cx = new_ctx(100, 100);
+  set_retained(cx, 1);
+  assert_eq!(get_retained(cx), 1);
+  cx
+}
+
+#[test]
+fn topmost_element_wins() {
+  let cx = retained_ctx();
+  set_elementId(cx, c_str("back"));
+  fillRect(cx, f32s(&[0.0, 0.0, 60.0, 60.0]));
+  set_elementId(cx, c_str("front"));
+  fillRect(cx, f32s(&[40.0, 40.0, 60.0, 60.0]));
+  assert_eq!(char_to_string(get_elementId(cx)), "front");
+
+  assert_eq!(hit(cx, 10.0, 10.0), "back");
+  assert_eq!(hit(cx, 50.0, 50.0), "front");
+  assert_eq!(hit(cx, 90.0, 10.0), "");
+}
+
+#[test]
+fn untagged_drawing_is_not_retained() {
+  let cx = retained_ctx();
+  fillRect(cx, f32s(&[0.0, 0.0, 100.0, 100.0]));
+  assert_eq!(hit(cx, 50.0, 50.0), "");
+
+  // nor is anything drawn outside of retained mode
+  let cx = new_ctx(100, 100);
+  set_elementId(cx, c_str("box"));
+  fillRect(cx, f32s(&[0.0, 0.0, 100.0, 100.0]));
+  assert_eq!(hit(cx, 50.0, 50.0), "");
+}
+
+#[test]
+fn transforms_and_clips_are_honored() {
+  let cx = retained_ctx();
+  set_elementId(cx, c_str("moved"));
+  save(cx);
+  translate(cx, f32s(&[50.0, 50.0]));
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  restore(cx);
+  assert_eq!(hit(cx, 55.0, 55.0), "moved");
+  assert_eq!(hit(cx, 5.0, 5.0), "");
+
+  set_elementId(cx, c_str("clipped"));
+  beginPath(cx);
+  rect(cx, f32s(&[0.0, 0.0, 20.0, 100.0]));
+  clip(cx, null_mut(), 0);
+  fillRect(cx, f32s(&[0.0, 0.0, 40.0, 40.0]));
+  assert_eq!(hit(cx, 10.0, 10.0), "clipped");
+  assert_eq!(hit(cx, 30.0, 10.0), "");
+}
+
+#[test]
+fn strokes_are_hit_along_their_width() {
+  let cx = retained_ctx();
+  set_elementId(cx, c_str("ring"));
+  set_lineWidth(cx, 10.0);
+  strokeRect(cx, f32s(&[20.0, 20.0, 60.0, 60.0]));
+  assert_eq!(hit(cx, 20.0, 50.0), "ring");
+  assert_eq!(hit(cx, 16.0, 50.0), "ring");
+  assert_eq!(hit(cx, 50.0, 50.0), "");
+  assert_eq!(hit(cx, 10.0, 50.0), "");
+}
+
+#[test]
+fn images_and_text_are_hit_by_their_boxes() {
+  let cx = retained_ctx();
+  set_elementId(cx, c_str("picture"));
+  let image = get_image(new_ctx(10, 10));
+  assert_eq!(drawImage(cx, image, f32s(&[70.0, 70.0])), 0);
+  assert_eq!(hit(cx, 75.0, 75.0), "picture");
+
+  set_elementId(cx, c_str("label"));
+  set_roboto(cx, 20.0);
+  fillText(cx, c_str("Hi"), f32s(&[10.0, 30.0]));
+  assert_eq!(hit(cx, 15.0, 25.0), "label");
+  assert_eq!(hit(cx, 15.0, 50.0), "");
+
+  let bounds = unsafe { *Box::from_raw(getElementBounds(cx, c_str("picture"))) };
+  assert_eq!(bounds, [70.0, 70.0, 10.0, 10.0]);
+  assert!(unsafe { (*getElementBounds(cx, c_str("nothing"))).is_empty() });
+}
+
+#[test]
+fn clearing_the_canvas_forgets_elements() {
+  let cx = retained_ctx();
+  set_elementId(cx, c_str("box"));
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  clearRect(cx, f32s(&[0.0, 0.0, 100.0, 100.0]));
+  assert_eq!(hit(cx, 5.0, 5.0), "");
+
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  reset(cx);
+  assert_eq!(hit(cx, 5.0, 5.0), "");
+  assert_eq!(get_retained(cx), 1);
+}
@@ -0,0 +1,328 @@
+//
+// Decoding images: createImageBitmap-style bitmaps, EXIF orientation, embedded color profiles and SVG.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::canvas_save_as;
+use canvas_wasm::context::{new_dye_from_pattern, Context2D};
+use canvas_wasm::context::api::*;
+use canvas_wasm::error::*;
+use canvas_wasm::image::*;
+use canvas_wasm::pattern::*;
+use canvas_wasm::utils::char_to_string;
+
+mod common;
+use common::*;
+
+/// A 4×2 image that is red on the left and blue on the right.
+fn halves(format: &str) -> Vec<u8> {
+  let cx = new_ctx(4, 2);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 2.0, 2.0]));
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[2.0, 0.0, 2.0, 2.0]));
+  unsafe {
+    let buf = canvas_save_as(&mut *(*cx).canvas, c_str(format), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut());
+    *Box::from_raw(buf)
+  }
+}
+
+/// Insert an EXIF segment carrying `orientation` right after the JPEG's start-of-image marker.
+fn with_orientation(jpeg: &[u8], orientation: u16) -> Vec<u8> {
+  let mut exif = vec![0xFF, 0xE1, 0, 34];
+  exif.extend(b"Exif\0\0");
+  exif.extend(b"MM\0\x2A\0\0\0\x08"); // big-endian TIFF header, first IFD at offset 8
+  exif.extend([0, 1]); // one entry
+  exif.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1]); // Orientation, SHORT, count 1
+  exif.extend(orientation.to_be_bytes());
+  exif.extend([0, 0, 0, 0, 0, 0]); // padding, then no next IFD
+  [&jpeg[..2], &exif, &jpeg[2..]].concat()
+}
+
+fn bitmap(bytes: Vec<u8>, crop: &[f32], opts: &[(&str, &str)]) -> *mut Image {
+  unsafe { new_image_bitmap(Box::into_raw(Box::new(bytes)), f32s(crop), str_map(opts)) }
+}
+
+fn size(image: *mut Image) -> (f32, f32) {
+  (image_get_width(image), image_get_height(image))
+}
+
+fn drawn(image: *mut Image) -> *mut Context2D {
+  let (width, height) = size(image);
+  let cx = new_ctx(width as i32, height as i32);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+  cx
+}
+
+fn is_red(px: [u8; 4]) -> bool {
+  px[0] > 200 && px[2] < 50
+}
+
+fn is_blue(px: [u8; 4]) -> bool {
+  px[2] > 200 && px[0] < 50
+}
+
+#[test]
+fn bitmaps_decode_eagerly() {
+  let image = bitmap(halves("png"), &[], &[]);
+  assert!(!image.is_null());
+  assert_eq!(size(image), (4.0, 2.0));
+  let cx = drawn(image);
+  assert_eq!(pixel(cx, 0, 0), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 3, 1), [0, 0, 255, 255]);
+}
+
+#[test]
+fn bitmaps_can_be_cropped_and_resized() {
+  let right = bitmap(halves("png"), &[2.0, 0.0, 2.0, 2.0], &[]);
+  assert_eq!(size(right), (2.0, 2.0));
+  assert_eq!(pixel(drawn(right), 0, 0), [0, 0, 255, 255]);
+
+  // a single dimension keeps the aspect ratio
+  let doubled = bitmap(halves("png"), &[], &[("resizeWidth", "8"), ("resizeQuality", "pixelated")]);
+  assert_eq!(size(doubled), (8.0, 4.0));
+  let cx = drawn(doubled);
+  assert_eq!(pixel(cx, 3, 3), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 4, 3), [0, 0, 255, 255]);
+}
+
+#[test]
+fn exif_orientation_is_applied_unless_disabled() {
+  let jpeg = with_orientation(&halves("jpeg"), 6); // rotated 90° clockwise
+
+  let upright = bitmap(jpeg.clone(), &[], &[]);
+  assert_eq!(size(upright), (2.0, 4.0));
+  let cx = drawn(upright);
+  assert!(is_red(pixel(cx, 0, 0)));
+  assert!(is_blue(pixel(cx, 0, 3)));
+
+  let raw = bitmap(jpeg, &[], &[("imageOrientation", "none")]);
+  assert_eq!(size(raw), (4.0, 2.0));
+}
+
+#[test]
+fn flip_y_turns_the_bitmap_upside_down() {
+  let cx = new_ctx(2, 2);
+  set_fill(cx, "#0f0");
+  fillRect(cx, f32s(&[0.0, 0.0, 2.0, 1.0]));
+  let source = get_image(cx);
+  let flipped = unsafe { image_bitmap_from_image(source, f32s(&[]), str_map(&[("imageOrientation", "flipY")])) };
+  let cx = drawn(flipped);
+  assert_eq!(pixel(cx, 0, 0)[3], 0);
+  assert_eq!(pixel(cx, 0, 1), [0, 255, 0, 255]);
+}
+
+#[test]
+fn unpremultiplied_bitmaps_draw_the_same() {
+  let cx = new_ctx(2, 2);
+  set_fill(cx, "rgba(255, 0, 0, 0.5)");
+  fillRect(cx, f32s(&[0.0, 0.0, 2.0, 2.0]));
+  let source = get_image(cx);
+  let premul = unsafe { image_bitmap_from_image(source, f32s(&[]), null_mut()) };
+  let unpremul = unsafe { image_bitmap_from_image(source, f32s(&[]), str_map(&[("premultiplyAlpha", "none")])) };
+  assert_eq!(pixel(drawn(premul), 1, 1), pixel(drawn(unpremul), 1, 1));
+}
+
+#[test]
+fn invalid_bitmap_requests_fail() {
+  assert!(bitmap(halves("png"), &[], &[("imageOrientation", "sideways")]).is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+  assert!(bitmap(halves("png"), &[], &[("resizeHeight", "0")]).is_null());
+  assert_eq!(get_last_error(), ErrorCode::InvalidState as u32);
+
+  assert!(bitmap(halves("png"), &[0.0, 0.0, 0.0, 2.0], &[]).is_null());
+  assert_eq!(get_last_error(), ErrorCode::IndexSize as u32);
+
+  assert!(bitmap(b"not an image".to_vec(), &[], &[]).is_null());
+  assert_eq!(get_last_error(), ErrorCode::InvalidState as u32);
+}
+
+fn loaded(bytes: Vec<u8>, honor_orientation: bool) -> *mut Image {
+  let image = new_image();
+  image_set_honor_orientation(image, honor_orientation as u32);
+  assert_eq!(image_set_data(image, Box::into_raw(Box::new(bytes))), 1);
+  image
+}
+
+/// A Display P3 ICC profile (v2, matrix/TRC) with the given description.
+fn icc_profile(description: &str) -> Vec<u8> {
+  let s15 = |v: f32| ((v * 65536.0).round() as i32).to_be_bytes();
+  let xyz = |values: [f32; 3]| {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    values.iter().for_each(|v| tag.extend(s15(*v)));
+    tag
+  };
+  let d50 = [0.9642, 1.0, 0.8249];
+
+  let mut desc = b"desc\0\0\0\0".to_vec();
+  desc.extend((description.len() as u32 + 1).to_be_bytes());
+  desc.extend(description.as_bytes());
+  desc.push(0);
+
+  // the sRGB transfer function as a parametric curve
+  let mut trc = b"para\0\0\0\0\0\x03\0\0".to_vec();
+  [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045].iter().for_each(|v| trc.extend(s15(*v)));
+
+  let tags: [(&[u8], Vec<u8>); 8] = [
+    (b"desc", desc), (b"wtpt", xyz(d50)),
+    (b"rXYZ", xyz([0.5151, 0.2412, -0.0011])),
+    (b"gXYZ", xyz([0.2920, 0.6922, 0.0419])),
+    (b"bXYZ", xyz([0.1571, 0.0666, 0.7841])),
+    (b"rTRC", trc.clone()), (b"gTRC", trc.clone()), (b"bTRC", trc),
+  ];
+  let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+  let mut data = vec![];
+  for (sig, tag) in tags {
+    table.extend(sig);
+    table.extend(((128 + 4 + 12 * 8 + data.len()) as u32).to_be_bytes());
+    table.extend((tag.len() as u32).to_be_bytes());
+    data.extend(tag);
+    data.resize((data.len() + 3) / 4 * 4, 0);
+  }
+
+  let mut icc = vec![0; 128];
+  icc[8..12].copy_from_slice(&[2, 0x10, 0, 0]);
+  icc[12..16].copy_from_slice(b"mntr");
+  icc[16..20].copy_from_slice(b"RGB ");
+  icc[20..24].copy_from_slice(b"XYZ ");
+  icc[36..40].copy_from_slice(b"acsp");
+  icc[68..80].copy_from_slice(&xyz(d50)[8..]);
+  icc.extend(table);
+  icc.extend(data);
+  let size = (icc.len() as u32).to_be_bytes();
+  icc[0..4].copy_from_slice(&size);
+  icc
+}
+
+fn with_icc_profile(jpeg: &[u8], icc: &[u8]) -> Vec<u8> {
+  let mut app2 = vec![0xFF, 0xE2];
+  app2.extend((2 + 14 + icc.len() as u16).to_be_bytes());
+  app2.extend(b"ICC_PROFILE\0\x01\x01");
+  app2.extend(icc);
+  [&jpeg[..2], &app2, &jpeg[2..]].concat()
+}
+
+#[test]
+fn loaded_images_are_upright_by_default() {
+  let jpeg = with_orientation(&halves("jpeg"), 6);
+  let image = loaded(jpeg.clone(), true);
+  assert_eq!(image_get_orientation(image), 6);
+  assert_eq!(size(image), (2.0, 4.0));
+  let cx = drawn(image);
+  assert!(is_red(pixel(cx, 1, 0)));
+  assert!(is_blue(pixel(cx, 1, 3)));
+
+  let sideways = loaded(jpeg, false);
+  assert_eq!(image_get_orientation(sideways), 6);
+  assert_eq!(size(sideways), (4.0, 2.0));
+
+  // switching orientation handling re-decodes the image
+  image_set_honor_orientation(sideways, 1);
+  assert_eq!(size(sideways), (2.0, 4.0));
+}
+
+#[test]
+fn untagged_images_report_no_orientation_or_profile() {
+  let image = loaded(halves("png"), true);
+  assert_eq!(image_get_orientation(image), 1);
+  assert_eq!(char_to_string(image_get_color_profile(image)), "");
+}
+
+#[test]
+fn embedded_profiles_are_named() {
+  let jpeg = with_icc_profile(&halves("jpeg"), &icc_profile("Display P3"));
+  let image = loaded(jpeg, true);
+  assert_eq!(char_to_string(image_get_color_profile(image)), "Display P3");
+  assert_eq!(size(image), (4.0, 2.0));
+}
+
+#[test]
+fn embedded_profiles_are_converted_when_drawn() {
+  let jpeg = with_icc_profile(&halves("jpeg"), &icc_profile("Display P3"));
+  let untagged = loaded(halves("jpeg"), true);
+  let tagged = loaded(jpeg.clone(), true);
+  let rotated = loaded(with_orientation(&jpeg, 6), true);
+
+  let draw_in = |image: *mut Image, color_space: &str| {
+    let (width, height) = size(image);
+    let cx = new_ctx_in(width as i32, height as i32, color_space);
+    assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0])), 0);
+    pixel(cx, 0, 0)
+  };
+  let near = |px: [u8; 4], expected: [u8; 4]| px.iter().zip(expected).all(|(a, b)| (*a as i32 - b as i32).abs() <= 8);
+
+  // the profile's red is outside sRGB, so it only survives in a wide-gamut context
+  let p3 = draw_in(tagged, "display-p3");
+  assert!(near(p3, [255, 0, 0, 255]), "{:?}", p3);
+  let p3 = draw_in(rotated, "display-p3");
+  assert!(near(p3, [255, 0, 0, 255]), "{:?}", p3);
+  let srgb = draw_in(tagged, "srgb");
+  assert!(near(srgb, [255, 0, 0, 255]), "{:?}", srgb);
+
+  // untagged images are sRGB, whose red is duller in display-p3
+  let p3 = draw_in(untagged, "display-p3");
+  assert!(near(p3, [234, 51, 35, 255]), "{:?}", p3);
+
+  // colorSpaceConversion: none keeps the encoded values and drops the profile
+  let raw = unsafe { new_image_bitmap(Box::into_raw(Box::new(jpeg)), f32s(&[]), str_map(&[("colorSpaceConversion", "none")])) };
+  let p3 = draw_in(raw, "display-p3");
+  assert!(near(p3, [234, 51, 35, 255]), "{:?}", p3);
+}
+
+/// A square icon whose left half is red.
+const ICON: &str = r#"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+  <rect width="5" height="10" fill="#f00"/>
+</svg>"#;
+
+fn svg(source: &str) -> *mut Image {
+  loaded(source.as_bytes().to_vec(), true)
+}
+
+#[test]
+fn svg_size_comes_from_its_root_element() {
+  assert_eq!(size(svg(ICON)), (10.0, 10.0));
+  assert_eq!(size(svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" viewBox="0 0 10 5"/>"#)), (40.0, 20.0));
+  assert_eq!(size(svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="12pt" height='1in'/>"#)), (16.0, 96.0));
+  assert_eq!(size(svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="50%"/>"#)), (300.0, 150.0));
+}
+
+#[test]
+fn svg_images_stay_sharp_when_scaled() {
+  let image = svg(ICON);
+  let cx = new_ctx(40, 40);
+  assert_eq!(drawImage(cx, image, f32s(&[0.0, 0.0, 40.0, 40.0])), 0);
+  assert_eq!(pixel(cx, 19, 20), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 20, 20), [0, 0, 0, 0]);
+}
+
+#[test]
+fn svg_source_rects_are_clipped() {
+  let image = svg(ICON);
+  let cx = new_ctx(20, 20);
+  // draw only the right (empty) half of the icon, scaled up
+  assert_eq!(drawImage(cx, image, f32s(&[5.0, 0.0, 5.0, 10.0, 10.0, 0.0, 10.0, 20.0])), 0);
+  assert_eq!(pixel(cx, 5, 10), [0, 0, 0, 0]);
+  assert_eq!(pixel(cx, 15, 10), [0, 0, 0, 0]);
+}
+
+#[test]
+fn svg_images_can_be_patterns() {
+  let image = svg(ICON);
+  let cx = new_ctx(20, 10);
+  unsafe {
+    let pattern = new_canvas_pattern_from_image(image, c_str("repeat"));
+    assert!(!pattern.is_null());
+    set_fillStyle(cx, new_dye_from_pattern(pattern));
+  }
+  fillRect(cx, f32s(&[0.0, 0.0, 20.0, 10.0]));
+  assert_eq!(pixel(cx, 12, 5), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, 17, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn malformed_svg_fails_to_load() {
+  let image = new_image();
+  assert_eq!(image_set_data(image, Box::into_raw(Box::new(b"<svg".to_vec()))), 0);
+}
@@ -0,0 +1,167 @@
+//
+// beginLayer / endLayer: grouping drawing and compositing it as a unit.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::error::*;
+use canvas_wasm::filter::FilterSpec;
+
+mod common;
+use common::*;
+
+fn near(actual: [u8; 4], expected: [u8; 4]) -> bool {
+  actual.iter().zip(expected).all(|(a, e)| (*a as i32 - e as i32).abs() <= 2)
+}
+
+fn begin(cx: *mut Context2D, alpha: f32, operation: &str) {
+  assert_eq!(beginLayer(cx, alpha, c_str(operation), null_mut()), 0);
+}
+
+#[test]
+fn layers_are_composited_as_a_unit() {
+  // overlapping shapes at 50% each would show through one another...
+  let cx = new_ctx(20, 10);
+  set_globalAlpha(cx, 0.5);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 15.0, 10.0]));
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[5.0, 0.0, 15.0, 10.0]));
+  assert!(near(pixel(cx, 10, 5), [85, 0, 170, 191]), "{:?}", pixel(cx, 10, 5));
+
+  // ...while a 50% layer of opaque shapes only shows the one on top
+  let cx = new_ctx(20, 10);
+  begin(cx, 0.5, "");
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 15.0, 10.0]));
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[5.0, 0.0, 15.0, 10.0]));
+  assert_eq!(endLayer(cx), 0);
+  assert!(near(pixel(cx, 10, 5), [0, 0, 255, 128]), "{:?}", pixel(cx, 10, 5));
+  assert!(near(pixel(cx, 2, 5), [255, 0, 0, 128]), "{:?}", pixel(cx, 2, 5));
+}
+
+#[test]
+fn layer_options_default_to_the_current_state_and_reset_inside() {
+  let cx = new_ctx(10, 10);
+  set_globalAlpha(cx, 0.5);
+  begin(cx, f32::NAN, "");
+  assert_eq!(get_globalAlpha(cx), 1.0);
+  set_fill(cx, "#0f0");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  assert_eq!(endLayer(cx), 0);
+  assert_eq!(get_globalAlpha(cx), 0.5);
+  assert!(near(pixel(cx, 5, 5), [0, 255, 0, 128]), "{:?}", pixel(cx, 5, 5));
+}
+
+#[test]
+fn layer_blend_modes_apply_to_the_whole_group() {
+  let cx = new_ctx(10, 10);
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  begin(cx, 1.0, "destination-out");
+  set_fill(cx, "#000");
+  fillRect(cx, f32s(&[0.0, 0.0, 5.0, 10.0]));
+  assert_eq!(endLayer(cx), 0);
+  assert_eq!(pixel(cx, 2, 5)[3], 0);
+  assert_eq!(pixel(cx, 7, 5), [255, 0, 0, 255]);
+
+  assert_eq!(beginLayer(cx, 1.0, c_str("sideways"), null_mut()), ErrorCode::Type as u32);
+}
+
+#[test]
+fn layer_filters_apply_to_the_whole_group() {
+  let cx = new_ctx(20, 20);
+  let mut filter = CanvasFilter{ css: "blur(4px)".to_string(), specs: vec![FilterSpec::Plain{ name: "blur".to_string(), value: 4.0 }] };
+  assert_eq!(beginLayer(cx, 1.0, c_str(""), &mut filter), 0);
+  set_fill(cx, "#000");
+  fillRect(cx, f32s(&[5.0, 5.0, 10.0, 10.0]));
+  assert_eq!(endLayer(cx), 0);
+  // the edge is softened on both sides
+  assert!(pixel(cx, 4, 10)[3] > 0);
+  assert!(pixel(cx, 5, 10)[3] < 255);
+}
+
+#[test]
+fn restore_cannot_escape_a_layer() {
+  let cx = new_ctx(10, 10);
+  set_globalAlpha(cx, 0.25);
+  save(cx);
+  begin(cx, 1.0, "");
+  translate(cx, f32s(&[5.0, 0.0]));
+  save(cx);
+  save(cx);
+  restore(cx);
+  restore(cx);
+  restore(cx); // ignored, since it would close the layer
+  assert_eq!(get_globalAlpha(cx), 1.0);
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[0.0, 0.0, 5.0, 10.0]));
+
+  // unbalanced saves are discarded when the layer ends
+  save(cx);
+  assert_eq!(endLayer(cx), 0);
+  assert_eq!(get_globalAlpha(cx), 0.25);
+  assert_eq!(pixel(cx, 7, 5), [0, 0, 255, 255]);
+  assert_eq!(pixel(cx, 2, 5)[3], 0);
+
+  restore(cx);
+  assert_eq!(get_globalAlpha(cx), 0.25);
+}
+
+#[test]
+fn ending_without_a_layer_is_an_error() {
+  let cx = new_ctx(10, 10);
+  assert_eq!(endLayer(cx), ErrorCode::InvalidState as u32);
+
+  begin(cx, 1.0, "");
+  reset(cx);
+  assert_eq!(endLayer(cx), ErrorCode::InvalidState as u32);
+}
+
+#[test]
+fn nested_layers_and_reads() {
+  let cx = new_ctx(10, 10);
+  begin(cx, 0.5, "");
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  // open layers haven't been composited yet, so reading doesn't see them
+  assert_eq!(pixel(cx, 5, 5), [0, 0, 0, 0]);
+  begin(cx, 1.0, "");
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[5.0, 0.0, 5.0, 10.0]));
+  assert_eq!(endLayer(cx), 0);
+  assert_eq!(endLayer(cx), 0);
+  assert!(near(pixel(cx, 2, 5), [255, 0, 0, 128]), "{:?}", pixel(cx, 2, 5));
+  assert!(near(pixel(cx, 7, 5), [0, 0, 255, 128]), "{:?}", pixel(cx, 7, 5));
+}
+
+#[test]
+fn reading_back_includes_open_layers() {
+  let cx = new_ctx(20, 10);
+  set_fill(cx, "#00f");
+  fillRect(cx, f32s(&[0.0, 0.0, 5.0, 10.0]));
+  begin(cx, 0.5, "");
+  set_fill(cx, "#f00");
+  fillRect(cx, f32s(&[10.0, 0.0, 10.0, 10.0]));
+
+  // the open layer shows up as it will once it's ended...
+  assert!(near(pixel(cx, 2, 5), [0, 0, 255, 255]), "{:?}", pixel(cx, 2, 5));
+  assert!(near(pixel(cx, 15, 5), [255, 0, 0, 128]), "{:?}", pixel(cx, 15, 5));
+
+  // ...including any layers nested inside it
+  begin(cx, 0.5, "");
+  set_fill(cx, "#0f0");
+  fillRect(cx, f32s(&[5.0, 0.0, 5.0, 10.0]));
+  assert!(near(pixel(cx, 7, 5), [0, 255, 0, 64]), "{:?}", pixel(cx, 7, 5));
+
+  // and reading didn't close them, so drawing continues inside
+  fillRect(cx, f32s(&[10.0, 0.0, 10.0, 5.0]));
+  assert_eq!(endLayer(cx), 0);
+  assert_eq!(endLayer(cx), 0);
+  assert!(near(pixel(cx, 7, 5), [0, 255, 0, 64]), "{:?}", pixel(cx, 7, 5));
+  assert!(near(pixel(cx, 15, 8), [255, 0, 0, 128]), "{:?}", pixel(cx, 15, 8));
+  // the green drawn after the read landed in the inner layer, over the outer layer's red
+  assert!(near(pixel(cx, 15, 2), [128, 128, 0, 128]), "{:?}", pixel(cx, 15, 2));
+}
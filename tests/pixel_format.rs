@@ -0,0 +1,130 @@
+//
+// Float16 / float32 contexts, high bit-depth ImageData and 16-bit PNG export.
+//
+use std::ptr::null_mut;
+
+use canvas_wasm::canvas::{canvas_save_as, new_canvas};
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use canvas_wasm::error::*;
+use canvas_wasm::surface::init_raster_surface;
+use canvas_wasm::utils::*;
+
+mod common;
+use common::*;
+
+fn new_ctx_as(width: i32, height: i32, color_type: &str) -> *mut Context2D {
+  unsafe {
+    let canvas = new_canvas(init_raster_surface(width, height), width as f32, height as f32);
+    let settings = new_js_str_map();
+    js_str_map_insert(settings, c_str("colorType"), c_str(color_type));
+    new_context(canvas as *mut _, settings)
+  }
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+  let exp = ((bits >> 10) & 0x1f) as i32;
+  let frac = (bits & 0x3ff) as f32 / 1024.0;
+  let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+  match exp {
+    0 => sign * frac * 2f32.powi(-14),
+    _ => sign * (1.0 + frac) * 2f32.powi(exp - 15),
+  }
+}
+
+fn read_floats(cx: *mut Context2D, pixel_format: &str) -> (Vec<f32>, String) {
+  unsafe {
+    let image_data = getImageData(cx, 0, 0, 1, 1, c_str(""), c_str(pixel_format));
+    assert!(!image_data.is_null());
+    let format = char_to_string(image_data_get_pixel_format(image_data));
+    let bytes = *Box::from_raw((*image_data).data);
+    let values = match format.as_str() {
+      "rgba-float16" => bytes.chunks(2).map(|b| half_to_f32(u16::from_le_bytes([b[0], b[1]]))).collect(),
+      "rgba-float32" => bytes.chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+      _ => bytes.iter().map(|b| *b as f32 / 255.0).collect(),
+    };
+    (values, format)
+  }
+}
+
+#[test]
+fn float_contexts_keep_values_between_8_bit_steps() {
+  let cx = new_ctx_as(1, 1, "float16");
+  assert_eq!(char_to_string(get_colorType(cx)), "float16");
+  set_fill(cx, "color(srgb 0.3 0.3 0.3)");
+  fillRect(cx, f32s(&[0.0, 0.0, 1.0, 1.0]));
+
+  // 0.3 sits between 76/255 and 77/255, so only the float readback can return it
+  let (floats, format) = read_floats(cx, "");
+  assert_eq!(format, "rgba-float16");
+  assert_eq!(floats.len(), 4);
+  assert!((floats[0] - 0.3).abs() < 0.001, "{:?}", floats);
+  assert_eq!(floats[3], 1.0);
+
+  let (bytes, format) = read_floats(cx, "rgba-unorm8");
+  assert_eq!(format, "rgba-unorm8");
+  assert!((bytes[0] - 0.3).abs() > 0.001);
+
+  let (floats, format) = read_floats(cx, "rgba-float32");
+  assert_eq!(format, "rgba-float32");
+  assert!((floats[0] - 0.3).abs() < 0.001, "{:?}", floats);
+}
+
+#[test]
+fn unorm8_remains_the_default() {
+  let cx = new_ctx(1, 1);
+  assert_eq!(char_to_string(get_colorType(cx)), "unorm8");
+  let (_, format) = read_floats(cx, "");
+  assert_eq!(format, "rgba-unorm8");
+}
+
+#[test]
+fn float16_image_data_round_trips() {
+  let cx = new_ctx_as(1, 1, "float16");
+  let half = |v: f32| -> [u8; 2] {
+    // exact for the values used below
+    let bits: u16 = if v == 0.0 { 0 } else if v == 1.0 { 15 << 10 } else { (13 << 10) | ((v * 4.0 - 1.0) * 1024.0) as u16 };
+    bits.to_le_bytes()
+  };
+  let bytes: Vec<u8> = [0.3125, 0.0, 0.4375, 1.0].iter().flat_map(|v| half(*v)).collect();
+  unsafe {
+    let data = new_image_data(Box::into_raw(Box::new(bytes)), 1.0, 1.0, c_str(""), c_str("rgba-float16"));
+    assert!(!data.is_null());
+    assert_eq!(putImageData(cx, data, f32s(&[0.0, 0.0])), 0);
+  }
+  let (floats, _) = read_floats(cx, "");
+  assert!((floats[0] - 0.3125).abs() < 0.001, "{:?}", floats);
+  assert!((floats[2] - 0.4375).abs() < 0.001, "{:?}", floats);
+}
+
+#[test]
+fn float_contexts_export_16_bit_pngs() {
+  let save = |cx: *mut Context2D| unsafe {
+    set_fill(cx, "#08f");
+    fillRect(cx, f32s(&[0.0, 0.0, 4.0, 4.0]));
+    let buf = canvas_save_as(&mut *(*cx).canvas, c_str("png"), 1.0, 1.0, c_str(""), c_str(""), null_mut(), 0, null_mut());
+    *Box::from_raw(buf)
+  };
+  // IHDR's bit depth byte follows the signature, chunk header, width and height
+  assert_eq!(save(new_ctx_as(4, 4, "float16"))[24], 16);
+  assert_eq!(save(new_ctx_as(4, 4, "float32"))[24], 16);
+  assert_eq!(save(new_ctx(4, 4))[24], 8);
+}
+
+#[test]
+fn unknown_formats_are_type_errors() {
+  assert!(new_ctx_as(1, 1, "unorm16").is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+  let cx = new_ctx(1, 1);
+  assert!(getImageData(cx, 0, 0, 1, 1, c_str(""), c_str("rgba-unorm16")).is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+  assert!(new_image_data(Box::into_raw(Box::new(vec![0; 8])), 1.0, 1.0, c_str(""), c_str("rgb")).is_null());
+  assert_eq!(get_last_error(), ErrorCode::Type as u32);
+
+  // a float16 pixel needs 8 bytes
+  assert!(new_image_data(Box::into_raw(Box::new(vec![0; 4])), 1.0, 1.0, c_str(""), c_str("rgba-float16")).is_null());
+  assert_eq!(get_last_error(), ErrorCode::InvalidState as u32);
+}
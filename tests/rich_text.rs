@@ -0,0 +1,212 @@
+//
+// Paragraphs built from differently styled runs of text.
+//
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::error::*;
+use canvas_wasm::image::Image;
+use canvas_wasm::rich_text::*;
+
+mod common;
+use common::*;
+
+// indices into the metrics array
+const WIDTH: usize = 0;
+const MAX_INTRINSIC_WIDTH: usize = 3;
+const LINE_COUNT: usize = 7;
+const DID_EXCEED_MAX_LINES: usize = 8;
+
+fn rich_text(runs: &[(&str, &[(&str, &str)])]) -> *mut RichText {
+  add_roboto();
+  let text = new_rich_text();
+  for (run, style) in runs {
+    let mut style = style.to_vec();
+    style.push(("fontFamily", "Roboto"));
+    unsafe { assert_eq!(rich_text_add_run(text, c_str(run), str_map(&style)), 0) };
+  }
+  text
+}
+
+fn placeholder(text: *mut RichText, width: f32, height: f32, options: &[(&str, &str)], image: *mut Image) -> u32 {
+  unsafe { rich_text_add_placeholder(text, width, height, str_map(options), image) }
+}
+
+fn placeholder_rects(text: *mut RichText) -> Vec<f32> {
+  unsafe { *Box::from_raw(rich_text_get_placeholder_rects(text)) }
+}
+
+fn metrics(text: *mut RichText) -> Vec<f32> {
+  unsafe { *Box::from_raw(rich_text_get_metrics(text)) }
+}
+
+fn draw(cx: *mut Context2D, text: *mut RichText, x: f32, y: f32) -> Vec<f32> {
+  unsafe { *Box::from_raw(drawRichText(cx, text, f32s(&[x, y]))) }
+}
+
+// the horizontal extent of the pixels that are mostly the given color
+fn columns_of(cx: *mut Context2D, width: i32, height: i32, rgb: [u8; 3]) -> Option<(i32, i32)> {
+  let data = pixels(cx, 0, 0, width, height);
+  let columns: Vec<i32> = data.chunks(4).enumerate()
+    .filter(|(_, px)| px[3] > 128 && px[..3].iter().zip(rgb).all(|(a, b)| (*a as i32 - b as i32).abs() < 64))
+    .map(|(i, _)| i as i32 % width)
+    .collect();
+  Some((*columns.iter().min()?, *columns.iter().max()?))
+}
+
+#[test]
+fn runs_keep_their_own_styles() {
+  let text = rich_text(&[
+    ("AAAA", &[("color", "#f00"), ("fontSize", "20")]),
+    ("BBBB", &[("color", "#00f"), ("fontSize", "20"), ("fontWeight", "bold")]),
+  ]);
+  let cx = new_ctx(200, 40);
+  let metrics = draw(cx, text, 0.0, 0.0);
+  assert_eq!(metrics[LINE_COUNT], 1.0);
+
+  let red = columns_of(cx, 200, 40, [255, 0, 0]).expect("red run");
+  let blue = columns_of(cx, 200, 40, [0, 0, 255]).expect("blue run");
+  assert!(red.1 < blue.0, "{:?} {:?}", red, blue);
+  assert!((blue.1 as f32) < metrics[WIDTH]);
+}
+
+#[test]
+fn width_wraps_and_max_lines_truncate() {
+  let text = rich_text(&[("one two three four five six", &[("fontSize", "16")])]);
+  let unwrapped = metrics(text);
+  assert_eq!(unwrapped[LINE_COUNT], 1.0);
+  assert_eq!(unwrapped[WIDTH], unwrapped[MAX_INTRINSIC_WIDTH].ceil());
+
+  unsafe { assert_eq!(rich_text_set_options(text, str_map(&[("width", "60")])), 0) };
+  let wrapped = metrics(text);
+  assert_eq!(wrapped[WIDTH], 60.0);
+  assert!(wrapped[LINE_COUNT] > 2.0, "{:?}", wrapped);
+  assert_eq!(wrapped[DID_EXCEED_MAX_LINES], 0.0);
+
+  unsafe { assert_eq!(rich_text_set_options(text, str_map(&[("width", "60"), ("maxLines", "2"), ("ellipsis", "…")])), 0) };
+  let truncated = metrics(text);
+  assert_eq!(truncated[LINE_COUNT], 2.0);
+  assert_eq!(truncated[DID_EXCEED_MAX_LINES], 1.0);
+}
+
+#[test]
+fn drawing_honors_the_position_and_global_alpha() {
+  let text = rich_text(&[("Hello", &[("background", "#0f0"), ("color", "#0f0"), ("fontSize", "20")])]);
+  let cx = new_ctx(100, 50);
+  set_globalAlpha(cx, 0.5);
+  let metrics = draw(cx, text, 10.0, 10.0);
+
+  assert_eq!(pixel(cx, 5, 5), [0, 0, 0, 0]);
+  let inside = pixel(cx, 12, 10 + metrics[1] as i32 / 2);
+  assert!(inside[1] > 250 && (inside[3] as i32 - 128).abs() <= 2, "{:?}", inside);
+
+  let dirty = unsafe { *Box::from_raw(get_dirtyRect(cx)) };
+  assert!(dirty[0] <= 10.0 && dirty[1] <= 10.0, "{:?}", dirty);
+}
+
+#[test]
+fn invalid_values_are_type_errors() {
+  let text = rich_text(&[("kept", &[])]);
+  unsafe {
+    for (key, value) in [("fontSize", "big"), ("fontWeight", "heavy"), ("color", "nope"), ("decoration", "sparkly")] {
+      assert_eq!(rich_text_add_run(text, c_str("dropped"), str_map(&[(key, value)])), ErrorCode::Type as u32);
+    }
+    assert_eq!(rich_text_set_options(text, str_map(&[("align", "sideways")])), ErrorCode::Type as u32);
+    assert_eq!(rich_text_set_options(text, str_map(&[("maxLines", "-1")])), ErrorCode::Type as u32);
+  }
+
+  // the rejected runs weren't added
+  let kept = metrics(text);
+  let only = rich_text(&[("kept", &[])]);
+  assert_eq!(kept[MAX_INTRINSIC_WIDTH], metrics(only)[MAX_INTRINSIC_WIDTH]);
+}
+
+#[test]
+fn letter_spacing_accepts_lengths() {
+  let width = |spacing: &str| metrics(rich_text(&[("Hello", &[("fontSize", "20"), ("letterSpacing", spacing)])]))[MAX_INTRINSIC_WIDTH];
+  let plain = width("0");
+  // bare numbers are pixels, and em lengths follow the run's font size
+  assert!((width("2") - plain - 10.0).abs() < 0.5, "{} {}", plain, width("2"));
+  assert!((width("2px") - plain - 10.0).abs() < 0.5);
+  assert!((width("0.1em") - plain - 10.0).abs() < 0.5, "{} {}", plain, width("0.1em"));
+
+  let text = rich_text(&[("kept", &[])]);
+  unsafe { assert_eq!(rich_text_add_run(text, c_str("dropped"), str_map(&[("letterSpacing", "10%")])), ErrorCode::Type as u32) };
+}
+
+#[test]
+fn decorations_are_drawn() {
+  let plain = rich_text(&[("____", &[("fontSize", "20"), ("color", "transparent")])]);
+  let underlined = rich_text(&[("____", &[("fontSize", "20"), ("color", "transparent"), ("decoration", "underline line-through"), ("decorationColor", "#f00")])]);
+  let cx = new_ctx(100, 40);
+  draw(cx, plain, 0.0, 0.0);
+  assert_eq!(columns_of(cx, 100, 40, [255, 0, 0]), None);
+  draw(cx, underlined, 0.0, 0.0);
+  assert!(columns_of(cx, 100, 40, [255, 0, 0]).is_some());
+}
+
+#[test]
+fn placeholders_take_up_room_in_the_line() {
+  let text = rich_text(&[("ab", &[("fontSize", "20")])]);
+  assert_eq!(placeholder(text, 30.0, 16.0, &[], std::ptr::null_mut()), 0);
+  unsafe { rich_text_add_run(text, c_str("cd"), str_map(&[("fontFamily", "Roboto"), ("fontSize", "20")])) };
+  let plain = rich_text(&[("abcd", &[("fontSize", "20")])]);
+
+  let widths = (metrics(text)[MAX_INTRINSIC_WIDTH], metrics(plain)[MAX_INTRINSIC_WIDTH]);
+  assert!((widths.0 - widths.1 - 30.0).abs() < 1.0, "{:?}", widths);
+
+  // by default the placeholder sits on the baseline
+  let rects = placeholder_rects(text);
+  assert_eq!(rects.len(), 4);
+  assert_eq!(rects[2..], [30.0, 16.0]);
+  let baseline = metrics(text)[4];
+  assert!((rects[1] + rects[3] - baseline).abs() < 0.5, "{:?} {}", rects, baseline);
+}
+
+#[test]
+fn placeholder_alignment() {
+  let top = rich_text(&[("Tall", &[("fontSize", "40")])]);
+  placeholder(top, 10.0, 10.0, &[("alignment", "top")], std::ptr::null_mut());
+  let bottom = rich_text(&[("Tall", &[("fontSize", "40")])]);
+  placeholder(bottom, 10.0, 10.0, &[("alignment", "bottom")], std::ptr::null_mut());
+  let middle = rich_text(&[("Tall", &[("fontSize", "40")])]);
+  placeholder(middle, 10.0, 10.0, &[("alignment", "middle")], std::ptr::null_mut());
+
+  let (top, bottom, middle) = (placeholder_rects(top)[1], placeholder_rects(bottom)[1], placeholder_rects(middle)[1]);
+  assert_eq!(top, 0.0);
+  assert!(top < middle && middle < bottom, "{} {} {}", top, middle, bottom);
+
+  let text = rich_text(&[]);
+  assert_eq!(placeholder(text, 10.0, 10.0, &[("alignment", "sideways")], std::ptr::null_mut()), ErrorCode::Type as u32);
+  assert_eq!(placeholder(text, -1.0, 10.0, &[], std::ptr::null_mut()), ErrorCode::Type as u32);
+  assert_eq!(placeholder_rects(text), Vec::<f32>::new());
+}
+
+#[test]
+fn images_are_drawn_into_their_placeholders() {
+  let source = new_ctx(10, 10);
+  set_fill(source, "#f00");
+  fillRect(source, f32s(&[0.0, 0.0, 10.0, 10.0]));
+  let image = get_image(source);
+
+  let text = rich_text(&[("x", &[("fontSize", "20"), ("color", "transparent")])]);
+  assert_eq!(placeholder(text, 10.0, 10.0, &[], image), 0);
+  let cx = new_ctx(60, 40);
+  draw(cx, text, 5.0, 5.0);
+
+  let rect = placeholder_rects(text);
+  let (x, y) = (5 + rect[0] as i32, 5 + rect[1] as i32);
+  assert_eq!(pixel(cx, x + 5, y + 5), [255, 0, 0, 255]);
+  assert_eq!(pixel(cx, x + 5, y - 2)[3], 0);
+
+  // images have to be loaded before they can be placed
+  let unloaded = Box::into_raw(Box::new(Image::new(None)));
+  assert_eq!(placeholder(text, 10.0, 10.0, &[], unloaded), ErrorCode::InvalidState as u32);
+}
+
+#[test]
+fn truncated_placeholders_have_no_rect() {
+  let text = rich_text(&[("first line", &[("fontSize", "16")])]);
+  unsafe { assert_eq!(rich_text_set_options(text, str_map(&[("width", "100"), ("maxLines", "1")])), 0) };
+  placeholder(text, 90.0, 10.0, &[], std::ptr::null_mut());
+  assert_eq!(placeholder_rects(text), Vec::<f32>::new());
+}
@@ -0,0 +1,104 @@
+//
+// Mapping between points and text positions in a rich-text paragraph, for carets and selections.
+//
+use canvas_wasm::rich_text::*;
+
+mod common;
+use common::*;
+
+fn paragraph(text: &str, options: &[(&str, &str)]) -> *mut RichText {
+  add_roboto();
+  let rich_text = new_rich_text();
+  unsafe {
+    assert_eq!(rich_text_add_run(rich_text, c_str(text), str_map(&[("fontFamily", "Roboto"), ("fontSize", "20")])), 0);
+    assert_eq!(rich_text_set_options(rich_text, str_map(options)), 0);
+  }
+  rich_text
+}
+
+fn caret(text: *mut RichText, index: u32) -> Vec<f32> {
+  unsafe { *Box::from_raw(rich_text_get_caret(text, index)) }
+}
+
+fn position_at(text: *mut RichText, x: f32, y: f32) -> Vec<f32> {
+  unsafe { *Box::from_raw(rich_text_get_position_at(text, x, y)) }
+}
+
+fn rects(text: *mut RichText, start: u32, end: u32) -> Vec<Vec<f32>> {
+  let nums = unsafe { *Box::from_raw(rich_text_get_rects_for_range(text, start, end)) };
+  nums.chunks(5).map(|rect| rect.to_vec()).collect()
+}
+
+#[test]
+fn carets_and_positions_agree() {
+  let text = paragraph("Hello world", &[]);
+  let carets: Vec<Vec<f32>> = (0..=11).map(|i| caret(text, i)).collect();
+  assert_eq!(carets[0][0], 0.0);
+  assert!(carets.windows(2).all(|pair| pair[0][0] < pair[1][0]), "{:?}", carets);
+  assert!(carets.iter().all(|c| c[1] == carets[0][1] && c[2] > 15.0));
+
+  // a point just past a caret maps back to its index
+  for i in [0, 3, 6, 11] {
+    let [x, y, height] = [carets[i][0], carets[i][1], carets[i][2]];
+    assert_eq!(position_at(text, x + 0.5, y + height / 2.0)[0], i as f32);
+  }
+
+  // out-of-range indices stick to the end
+  assert_eq!(caret(text, 100), carets[11]);
+}
+
+#[test]
+fn wrapped_lines() {
+  let text = paragraph("one two three four", &[("width", "70")]);
+  let first = caret(text, 0);
+  let last = caret(text, 18);
+  assert!(last[1] > first[1], "{:?} {:?}", first, last);
+
+  // at a soft wrap the caret begins the next line
+  let wrapped = (1..18).find(|&i| caret(text, i)[1] > first[1]).unwrap();
+  assert_eq!(caret(text, wrapped)[0], 0.0);
+
+  // a point below the text lands on the last line
+  assert!(position_at(text, 0.0, 1000.0)[0] >= wrapped as f32);
+
+  // selections are split across lines
+  let boxes = rects(text, 0, 18);
+  assert!(boxes.len() > 1);
+  assert!(boxes.iter().all(|b| b[4] == 0.0));
+  assert!(boxes.last().unwrap()[1] > boxes[0][1]);
+  assert!(rects(text, 5, 5).is_empty());
+}
+
+#[test]
+fn hard_breaks_and_empty_lines() {
+  let text = paragraph("ab\n\ncd", &[]);
+  let (a, before_break, empty, c) = (caret(text, 0), caret(text, 2), caret(text, 3), caret(text, 4));
+  assert!(before_break[0] > a[0] && before_break[1] == a[1]);
+  assert_eq!(empty[0], 0.0);
+  assert!(empty[1] > a[1] && c[1] > empty[1], "{:?} {:?} {:?}", a, empty, c);
+
+  let empty_paragraph = paragraph("", &[]);
+  assert_eq!(caret(empty_paragraph, 0)[0], 0.0);
+}
+
+#[test]
+fn word_boundaries() {
+  let text = paragraph("Hello brave world", &[]);
+  let boundary = |i| unsafe { *Box::from_raw(rich_text_get_word_boundary(text, i)) };
+  assert_eq!(boundary(0), [0.0, 5.0]);
+  assert_eq!(boundary(8), [6.0, 11.0]);
+  assert_eq!(boundary(14), [12.0, 17.0]);
+}
+
+#[test]
+fn bidi_text() {
+  // abc, then three hebrew letters
+  let text = paragraph("abc \u{5d0}\u{5d1}\u{5d2}", &[]);
+  let boxes = rects(text, 4, 7);
+  assert!(!boxes.is_empty() && boxes.iter().all(|b| b[4] == 1.0), "{:?}", boxes);
+  assert!(rects(text, 0, 3).iter().all(|b| b[4] == 0.0));
+
+  // carets move leftward through right-to-left runs
+  let (first, second) = (caret(text, 4), caret(text, 5));
+  assert!(second[0] < first[0], "{:?} {:?}", first, second);
+}
@@ -0,0 +1,154 @@
+//
+// measureText's TextMetrics: glyph ink, font and em boxes, and baselines.
+//
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use skia_safe::{Data, Typeface};
+
+mod common;
+use common::*;
+
+#[derive(Debug)]
+struct Metrics {
+  width: f32,
+  actual_left: f32,
+  actual_right: f32,
+  font_ascent: f32,
+  font_descent: f32,
+  actual_ascent: f32,
+  actual_descent: f32,
+  em_ascent: f32,
+  em_descent: f32,
+  hanging: f32,
+  alphabetic: f32,
+  ideographic: f32,
+}
+
+fn measure(cx: *mut Context2D, text: &str) -> Metrics {
+  unsafe {
+    let results = *Box::from_raw(measureText(cx, c_str(text), f32s(&[])));
+    let m = &*(results[0] as *mut JsF32Array);
+    Metrics{
+      width: m[0], actual_left: m[1], actual_right: m[2], font_ascent: m[3], font_descent: m[4],
+      actual_ascent: m[5], actual_descent: m[6], em_ascent: m[7], em_descent: m[8],
+      hanging: m[9], alphabetic: m[10], ideographic: m[11],
+    }
+  }
+}
+
+fn roboto_ctx(size: f32) -> *mut Context2D {
+  let cx = new_ctx(10, 10);
+  set_roboto(cx, size);
+  cx
+}
+
+#[test]
+fn actual_bounds_follow_the_glyphs() {
+  let cx = roboto_ctx(40.0);
+  let x = measure(cx, "x");
+  let h = measure(cx, "H");
+  let g = measure(cx, "g");
+
+  // glyphs sit inside the font box, and only descenders dip below the baseline
+  assert!(x.actual_ascent > 0.0 && x.actual_ascent < h.actual_ascent, "{:?} {:?}", x, h);
+  assert!(h.actual_ascent < h.font_ascent, "{:?}", h);
+  assert!(h.actual_descent.abs() < 0.5, "{:?}", h);
+  assert!(g.actual_descent > 5.0 && g.actual_descent < g.font_descent, "{:?}", g);
+
+  // side bearings keep the ink inside the advance
+  assert!(h.actual_left < 0.0 && h.actual_right < h.width, "{:?}", h);
+
+  // the font box doesn't depend on the text
+  assert_eq!((x.font_ascent, x.font_descent), (g.font_ascent, g.font_descent));
+
+  let empty = measure(cx, "");
+  assert_eq!((empty.width, empty.actual_ascent, empty.actual_right), (0.0, 0.0, 0.0));
+  assert_eq!(empty.font_ascent, x.font_ascent);
+}
+
+#[test]
+fn em_box_spans_the_font_size() {
+  for size in [10.0, 40.0] {
+    let m = measure(roboto_ctx(size), "Hi");
+    assert!((m.em_ascent + m.em_descent - size).abs() < 0.01, "{:?}", m);
+    assert!(m.em_ascent > m.em_descent);
+  }
+}
+
+#[test]
+fn baselines_are_relative_to_the_text_baseline() {
+  let cx = roboto_ctx(40.0);
+  let alphabetic = measure(cx, "Hg");
+  assert_eq!(alphabetic.alphabetic, 0.0);
+  assert!(alphabetic.hanging > 0.0);
+  assert!(alphabetic.ideographic < 0.0);
+
+  // measuring from the top moves everything down by the same amount
+  set_textBaseline(cx, c_str("top"));
+  let top = measure(cx, "Hg");
+  let shift = alphabetic.alphabetic - top.alphabetic;
+  assert!(shift > 0.0);
+  for (a, b) in [
+    (alphabetic.hanging, top.hanging), (alphabetic.ideographic, top.ideographic),
+    (alphabetic.actual_ascent, top.actual_ascent), (alphabetic.font_ascent, top.font_ascent),
+    (alphabetic.em_ascent, top.em_ascent),
+  ] {
+    assert!((a - b - shift).abs() < 0.01, "{} {} {}", a, b, shift);
+  }
+  assert!((top.actual_descent - alphabetic.actual_descent - shift).abs() < 0.01);
+
+  set_textBaseline(cx, c_str("hanging"));
+  assert_eq!(measure(cx, "Hg").hanging, 0.0);
+  set_textBaseline(cx, c_str("ideographic"));
+  assert_eq!(measure(cx, "Hg").ideographic, 0.0);
+}
+
+#[test]
+fn alignment_moves_the_ink_box() {
+  let cx = roboto_ctx(40.0);
+  let start = measure(cx, "Hello");
+  set_textAlign(cx, c_str("right"));
+  let right = measure(cx, "Hello");
+  assert!((right.actual_left - (start.actual_left + start.width)).abs() < 0.5, "{:?} {:?}", start, right);
+  assert!((right.actual_right - (start.actual_right - start.width)).abs() < 0.5, "{:?} {:?}", start, right);
+}
+
+#[test]
+fn ink_follows_letter_spacing() {
+  let cx = roboto_ctx(40.0);
+  let plain = measure(cx, "Hi");
+  set_letterSpacing(cx, c_str("10px"));
+  let spaced = measure(cx, "Hi");
+
+  // the second glyph moves over by the spacing, the first stays where it was
+  assert!((spaced.actual_right - plain.actual_right - 10.0).abs() < 0.5, "{:?} {:?}", plain, spaced);
+  assert!((spaced.actual_left - plain.actual_left).abs() < 0.01, "{:?} {:?}", plain, spaced);
+  assert_eq!(spaced.actual_ascent, plain.actual_ascent);
+}
+
+#[test]
+fn ink_uses_the_face_that_draws_each_character() {
+  add_roboto();
+  add_monaco();
+  let covers = |font: &[u8], c: char| {
+    let face = Typeface::from_data(Data::new_copy(font), None).expect("a valid font");
+    face.unichar_to_glyph(c as i32) != 0
+  };
+  let roboto_only = ['Ж', 'Ω', 'Ł', 'ő'].into_iter()
+    .find(|c| !covers(MONACO, *c) && covers(ROBOTO, *c))
+    .expect("a character only Roboto covers");
+
+  // measured alone in each font...
+  let cx = new_ctx(10, 10);
+  set_families(cx, &["Monaco"], 40.0);
+  let lead = measure(cx, "x");
+  set_families(cx, &["Roboto"], 40.0);
+  let fallback = measure(cx, &roboto_only.to_string());
+
+  // ...and together, with Roboto filling in for what Monaco lacks
+  set_families(cx, &["Monaco", "Roboto"], 40.0);
+  let mixed = measure(cx, &format!("x{}", roboto_only));
+  assert!((mixed.actual_right - (lead.width + fallback.actual_right)).abs() < 0.5, "{:?} {:?} {:?}", lead, fallback, mixed);
+  assert!((mixed.actual_ascent - lead.actual_ascent.max(fallback.actual_ascent)).abs() < 0.5, "{:?}", mixed);
+}
@@ -0,0 +1,138 @@
+//
+// The context's letterSpacing, wordSpacing, fontKerning, fontStretch, fontVariantCaps and
+// textRendering properties.
+//
+use canvas_wasm::context::Context2D;
+use canvas_wasm::context::api::*;
+use canvas_wasm::context::jstypes::*;
+use canvas_wasm::utils::char_to_string;
+use std::os::raw::c_char;
+
+mod common;
+use common::*;
+
+type Getter = extern "C" fn(*mut Context2D) -> *mut c_char;
+type Setter = extern "C" fn(*mut Context2D, *mut c_char);
+
+fn get(cx: *mut Context2D, getter: Getter) -> String {
+  char_to_string(getter(cx))
+}
+
+fn width(cx: *mut Context2D, text: &str) -> f32 {
+  unsafe {
+    let results = *Box::from_raw(measureText(cx, c_str(text), f32s(&[])));
+    (*(results[0] as *mut JsF32Array))[0]
+  }
+}
+
+fn roboto_ctx() -> *mut Context2D {
+  let cx = new_ctx(10, 10);
+  set_roboto(cx, 40.0);
+  cx
+}
+
+#[test]
+fn defaults_and_invalid_values() {
+  let cx = roboto_ctx();
+  let props: [(Getter, Setter, &str, &str, &str); 6] = [
+    (get_letterSpacing, set_letterSpacing, "0px", "3px", "wide"),
+    (get_wordSpacing, set_wordSpacing, "0px", "1.5em", "10%"),
+    (get_fontKerning, set_fontKerning, "auto", "none", "off"),
+    (get_fontStretch, set_fontStretch, "normal", "condensed", "narrow"),
+    (get_fontVariantCaps, set_fontVariantCaps, "normal", "small-caps", "Small-Caps"),
+    (get_textRendering, set_textRendering, "auto", "optimizeSpeed", "optimizespeed"),
+  ];
+  for (getter, setter, default, valid, invalid) in props {
+    assert_eq!(get(cx, getter), default);
+    setter(cx, c_str(valid));
+    assert_eq!(get(cx, getter), valid);
+    setter(cx, c_str(invalid));
+    assert_eq!(get(cx, getter), valid, "{} should be ignored", invalid);
+  }
+
+  // lengths need a unit unless they're zero
+  set_letterSpacing(cx, c_str("2"));
+  assert_eq!(get(cx, get_letterSpacing), "3px");
+}
+
+#[test]
+fn spacing_widens_the_text() {
+  let cx = roboto_ctx();
+  let plain = (width(cx, "Hello"), width(cx, "a b c"));
+
+  set_letterSpacing(cx, c_str("2px"));
+  let tracked = width(cx, "Hello");
+  assert!((tracked - plain.0 - 10.0).abs() < 0.5, "{} {}", plain.0, tracked);
+
+  // font-relative lengths use the current font size
+  set_letterSpacing(cx, c_str("0.1em"));
+  assert!((width(cx, "Hello") - plain.0 - 20.0).abs() < 0.5);
+  set_letterSpacing(cx, c_str("0px"));
+
+  set_wordSpacing(cx, c_str("10px"));
+  assert_eq!(width(cx, "Hello"), plain.0);
+  assert!((width(cx, "a b c") - plain.1 - 20.0).abs() < 0.5, "{} {}", plain.1, width(cx, "a b c"));
+}
+
+#[test]
+fn kerning_can_be_turned_off() {
+  let cx = roboto_ctx();
+  let kerned = width(cx, "AVAVAV");
+  set_fontKerning(cx, c_str("normal"));
+  assert_eq!(width(cx, "AVAVAV"), kerned);
+  set_fontKerning(cx, c_str("none"));
+  let unkerned = width(cx, "AVAVAV");
+  assert!(unkerned > kerned, "{} {}", kerned, unkerned);
+
+  // optimizeSpeed skips kerning unless it was asked for explicitly
+  set_fontKerning(cx, c_str("auto"));
+  set_textRendering(cx, c_str("optimizeSpeed"));
+  assert_eq!(width(cx, "AVAVAV"), unkerned);
+  set_fontKerning(cx, c_str("normal"));
+  assert_eq!(width(cx, "AVAVAV"), kerned);
+}
+
+#[test]
+fn properties_are_saved_and_restored() {
+  let cx = roboto_ctx();
+  save(cx);
+  set_letterSpacing(cx, c_str("5px"));
+  set_fontStretch(cx, c_str("expanded"));
+  set_fontKerning(cx, c_str("none"));
+  restore(cx);
+  assert_eq!(get(cx, get_letterSpacing), "0px");
+  assert_eq!(get(cx, get_fontStretch), "normal");
+  assert_eq!(get(cx, get_fontKerning), "auto");
+}
+
+#[test]
+fn setting_the_font_resets_stretch_and_caps() {
+  let cx = roboto_ctx();
+  set_fontStretch(cx, c_str("condensed"));
+  set_fontVariantCaps(cx, c_str("all-small-caps"));
+  set_letterSpacing(cx, c_str("1px"));
+  set_roboto(cx, 20.0);
+  assert_eq!(get(cx, get_fontStretch), "normal");
+  assert_eq!(get(cx, get_fontVariantCaps), "normal");
+  assert_eq!(get(cx, get_letterSpacing), "1px");
+}
+
+#[test]
+fn em_spacing_follows_the_font_size() {
+  let cx = new_ctx(10, 10);
+  set_roboto(cx, 10.0);
+  set_letterSpacing(cx, c_str("0.5em"));
+  set_roboto(cx, 40.0);
+  assert_eq!(get(cx, get_letterSpacing), "0.5em");
+
+  // five letters, each 20px wider at the new size
+  let spaced = width(cx, "Hello");
+  set_letterSpacing(cx, c_str("0px"));
+  let plain = width(cx, "Hello");
+  assert!((spaced - plain - 100.0).abs() < 0.5, "{} {}", plain, spaced);
+
+  // exponents are part of the number, not the unit
+  set_letterSpacing(cx, c_str("1e1px"));
+  assert_eq!(get(cx, get_letterSpacing), "1e1px");
+  assert!((width(cx, "Hello") - plain - 50.0).abs() < 0.5);
+}